@@ -1,11 +1,15 @@
 use anyhow::{anyhow, Result};
 use std::path::{Path, PathBuf};
+use glam::Mat4;
 use gltf::{buffer::Data as BufferData, image::Data as ImageData, Document, Primitive};
+use zenith_core::collections::hashmap::HashMap;
 use zenith_core::file::load_with_memory_mapping;
 use zenith_core::log::info;
-use crate::render::{Material, MaterialBuilder, Mesh, MeshBuilder, MeshCollection, TextureBuilder, TextureFormat, Vertex};
+use crate::render::{Material, MaterialBuilder, Mesh, MeshBuilder, MeshCollection, MorphTarget, Skin, TextureBuilder, TextureFormat, Vertex};
 use crate::{Asset, RawResourceBaker, AssetRegistry, RawResource, RawResourceLoader, AssetUrl, serialize_asset};
 
+const KHR_DRACO_MESH_COMPRESSION: &str = "KHR_draco_mesh_compression";
+
 #[derive(Debug, Clone)]
 pub struct GltfLoader;
 
@@ -66,9 +70,12 @@ impl RawGltfProcessor {
         node: &gltf::Node,
         buffers: &[BufferData],
         registry: &AssetRegistry,
-        meshes_url: &mut Vec<AssetUrl>,
+        meshes_url: &mut Vec<(AssetUrl, Mat4, Option<usize>)>,
         main_url: &str,
+        parent_transform: Mat4,
     ) -> Result<()> {
+        let transform = parent_transform * Mat4::from_cols_array_2d(&node.transform().matrix());
+
         if let Some(mesh) = node.mesh() {
             for primitive in mesh.primitives() {
                 // TODO: abstract asset serialize and register logic
@@ -78,13 +85,13 @@ impl RawGltfProcessor {
                 let asset_serialize_path = base_directory.join(&url);
                 serialize_asset(&mesh_asset, &asset_serialize_path)?;
 
-                meshes_url.push(url.clone());
+                meshes_url.push((url.clone(), transform, node.skin().map(|skin| skin.index())));
                 registry.register(url, mesh_asset);
             }
         }
 
         for child in node.children() {
-            Self::process_node(base_directory, &child, buffers, registry, meshes_url, main_url)?;
+            Self::process_node(base_directory, &child, buffers, registry, meshes_url, main_url, transform)?;
         }
 
         Ok(())
@@ -95,6 +102,14 @@ impl RawGltfProcessor {
         primitive: &Primitive,
         buffers: &[BufferData],
     ) -> Result<Mesh> {
+        // Draco compressed attributes live in a separate buffer view the accessor reader can't decode.
+        if primitive.extension_value(KHR_DRACO_MESH_COMPRESSION).is_some() {
+            return Err(anyhow!(
+                "Primitive {} uses {}, which requires a Draco decoder",
+                primitive.index(), KHR_DRACO_MESH_COMPRESSION
+            ));
+        }
+
         let reader = primitive.reader(|buffer| Some(&*buffers[buffer.index()]));
 
         let positions = reader
@@ -116,13 +131,43 @@ impl RawGltfProcessor {
             vec![[0.0, 0.0]; positions.len()]
         };
 
+        let tangents = if let Some(tangents) = reader.read_tangents() {
+            tangents.collect::<Vec<_>>()
+        } else {
+            // Default tangent along +X with a right-handed bitangent
+            vec![[1.0, 0.0, 0.0, 1.0]; positions.len()]
+        };
+
         let indices = reader
             .read_indices()
             .ok_or(anyhow!("Missing indices"))?
             .into_u32()
             .collect::<Vec<_>>();
 
-        if positions.len() != normals.len() || positions.len() != tex_coords.len() {
+        // Welding and the vertex fetch remap index by these, so reject malformed assets here.
+        if let Some(&index) = indices.iter().find(|&&index| index as usize >= positions.len()) {
+            return Err(anyhow!(
+                "Primitive {} has index {} out of range for {} vertices",
+                primitive.index(), index, positions.len()
+            ));
+        }
+
+        // Only the first joint set is read, so at most 4 joints influence a vertex.
+        let joints = if let Some(joints) = reader.read_joints(0) {
+            joints.into_u16().collect::<Vec<_>>()
+        } else {
+            // Rigid mesh, fully bound to the first joint
+            vec![[0; 4]; positions.len()]
+        };
+
+        let weights = if let Some(weights) = reader.read_weights(0) {
+            weights.into_f32().collect::<Vec<_>>()
+        } else {
+            vec![[1.0, 0.0, 0.0, 0.0]; positions.len()]
+        };
+
+        if positions.len() != normals.len() || positions.len() != tex_coords.len() || positions.len() != tangents.len()
+            || positions.len() != joints.len() || positions.len() != weights.len() {
             return Err(anyhow!("Vertex attribute count mismatch"));
         }
 
@@ -130,23 +175,132 @@ impl RawGltfProcessor {
             .into_iter()
             .zip(normals.into_iter())
             .zip(tex_coords.into_iter())
-            .map(|((pos, norm), uv)| {
+            .zip(tangents.into_iter())
+            .zip(joints.into_iter().zip(weights.into_iter()))
+            .map(|((((pos, norm), uv), tangent), (joints, weights))| {
                 Vertex::new(
                     glam::Vec3::from_array(pos),
                     glam::Vec3::from_array(norm),
                     glam::Vec2::from_array(uv),
+                    glam::Vec4::from_array(tangent),
                 )
+                .with_skinning(joints, weights)
             })
             .collect();
 
-        let mesh = MeshBuilder::default()
+        let morph_targets = reader
+            .read_morph_targets()
+            .map(|(positions, normals, _tangents)| {
+                let target = MorphTarget {
+                    position_deltas: positions.map(|p| p.collect()).unwrap_or_default(),
+                    normal_deltas: normals.map(|n| n.collect()).unwrap_or_default(),
+                };
+
+                let valid = |deltas: &Vec<[f32; 3]>| deltas.is_empty() || deltas.len() == vertices.len();
+                if valid(&target.position_deltas) && valid(&target.normal_deltas) {
+                    Ok(target)
+                } else {
+                    Err(anyhow!("Morph target attribute count mismatch"))
+                }
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut mesh = MeshBuilder::default()
             .vertices(vertices)
             .indices(indices)
+            .morph_targets(morph_targets)
             .build()?;
 
+        let num_raw_vertices = mesh.vertices.len();
+        Self::weld_vertices(&mut mesh);
+        Self::optimize_vertex_fetch(&mut mesh);
+        info!("Mesh vertices welded: {} -> {}", num_raw_vertices, mesh.vertices.len());
+
         Ok(mesh)
     }
 
+    /// Merge vertices whose attributes (and morph deltas) are equal within `WELD_EPSILON` and remap indices.
+    /// The first occurrence of a vertex is kept, so the result only depends on the input order.
+    #[profiling::function]
+    fn weld_vertices(mesh: &mut Mesh) {
+        const WELD_EPSILON: f32 = 1e-6;
+        let quantize = |value: f32| (value / WELD_EPSILON).round() as i64;
+
+        let mut unique: HashMap<Vec<i64>, u32> = HashMap::with_capacity(mesh.vertices.len());
+        let mut remap = Vec::with_capacity(mesh.vertices.len());
+        let mut kept = Vec::with_capacity(mesh.vertices.len());
+
+        for (index, vertex) in mesh.vertices.iter().enumerate() {
+            let morph_deltas = mesh.morph_targets
+                .iter()
+                .flat_map(|target| target.position_deltas.get(index).into_iter().chain(target.normal_deltas.get(index)))
+                .flatten();
+            let key = vertex.position.iter()
+                .chain(&vertex.normal)
+                .chain(&vertex.tex_coord)
+                .chain(&vertex.tangent)
+                .chain(&vertex.weights)
+                .chain(morph_deltas)
+                .map(|&value| quantize(value))
+                .chain(vertex.joints.iter().map(|&joint| joint as i64))
+                .collect::<Vec<_>>();
+
+            let next = kept.len() as u32;
+            let welded = *unique.entry(key).or_insert(next);
+            if welded == next {
+                kept.push(index);
+            }
+            remap.push(welded);
+        }
+
+        if kept.len() == mesh.vertices.len() {
+            return;
+        }
+
+        Self::apply_vertex_order(mesh, &kept, &remap);
+    }
+
+    /// Reorder vertices by first use in the index buffer, improving vertex fetch locality.
+    #[profiling::function]
+    fn optimize_vertex_fetch(mesh: &mut Mesh) {
+        let mut remap = vec![u32::MAX; mesh.vertices.len()];
+        let mut order = Vec::with_capacity(mesh.vertices.len());
+
+        for &index in &mesh.indices {
+            let slot = &mut remap[index as usize];
+            if *slot == u32::MAX {
+                *slot = order.len() as u32;
+                order.push(index as usize);
+            }
+        }
+
+        // Keep unreferenced vertices at the end.
+        for (index, slot) in remap.iter_mut().enumerate() {
+            if *slot == u32::MAX {
+                *slot = order.len() as u32;
+                order.push(index);
+            }
+        }
+
+        Self::apply_vertex_order(mesh, &order, &remap);
+    }
+
+    /// Keep the vertices at `order` (in that order) and remap indices through `remap`.
+    fn apply_vertex_order(mesh: &mut Mesh, order: &[usize], remap: &[u32]) {
+        mesh.vertices = order.iter().map(|&i| mesh.vertices[i]).collect();
+        for target in &mut mesh.morph_targets {
+            if !target.position_deltas.is_empty() {
+                target.position_deltas = order.iter().map(|&i| target.position_deltas[i]).collect();
+            }
+            if !target.normal_deltas.is_empty() {
+                target.normal_deltas = order.iter().map(|&i| target.normal_deltas[i]).collect();
+            }
+        }
+        for index in &mut mesh.indices {
+            *index = remap[*index as usize];
+        }
+    }
+
     #[profiling::function]
     fn generate_flat_normals(positions: &Vec<[f32; 3]>) -> Result<Vec<[f32; 3]>> {
         if positions.len() % 3 != 0 {
@@ -170,6 +324,28 @@ impl RawGltfProcessor {
         Ok(normals)
     }
 
+    /// Read the joints and inverse bind matrices of all skins.
+    /// Missing inverse bind matrices default to identity, as the glTF spec requires.
+    #[profiling::function]
+    fn bake_skins(gltf: &Document, buffers: &[BufferData]) -> Vec<Skin> {
+        gltf.skins()
+            .map(|skin| {
+                let joints = skin.joints().map(|joint| joint.index()).collect::<Vec<_>>();
+                let inverse_bind_matrices = skin
+                    .reader(|buffer| Some(&*buffers[buffer.index()]))
+                    .read_inverse_bind_matrices()
+                    .map(|matrices| matrices.collect())
+                    .unwrap_or_else(|| vec![Mat4::IDENTITY.to_cols_array_2d(); joints.len()]);
+
+                Skin {
+                    name: skin.name().map(str::to_owned),
+                    joints,
+                    inverse_bind_matrices,
+                }
+            })
+            .collect()
+    }
+
     #[profiling::function]
     fn bake_materials(gltf: &Document, images: &[ImageData]) -> Result<Vec<Material>> {
         let mut materials = Vec::new();
@@ -336,16 +512,17 @@ impl RawResourceBaker for RawGltfProcessor {
         let mut meshes_urls = Vec::with_capacity(material_urls.len());
         for scene in gltf.scenes() {
             for node in scene.nodes() {
-                Self::process_node(&base_directory, &node, &buffers, registry, &mut meshes_urls, asset_url)?;
+                Self::process_node(&base_directory, &node, &buffers, registry, &mut meshes_urls, asset_url, Mat4::IDENTITY)?;
             }
         }
 
         assert_eq!(meshes_urls.len(), material_urls.len());
 
         let mut mesh_collection = MeshCollection::new(&url);
-        for (mat, mesh) in material_urls.into_iter().zip(meshes_urls.into_iter()) {
-            mesh_collection.add_mesh(mesh, mat);
+        for (mat, (mesh, transform, skin)) in material_urls.into_iter().zip(meshes_urls.into_iter()) {
+            mesh_collection.add_mesh(mesh, mat, transform, skin);
         }
+        mesh_collection.skins = Self::bake_skins(&gltf, &buffers);
 
         let mesh_collection_url = mesh_collection.url(asset_url);
         let asset_serialize_path = base_directory.join(&mesh_collection_url);
@@ -22,15 +22,33 @@ pub mod gltf_loader;
 static ASSET_REGISTRY: OnceLock<AssetRegistry> = OnceLock::new();
 
 pub fn initialize() -> Result<()> {
-    ASSET_REGISTRY.set(AssetRegistry::new()).map_err(|_| anyhow!("Failed to initialize asset registry!"))
+    let registry = AssetRegistry::new();
+    manager::register_raw_sources(&registry);
+    ASSET_REGISTRY.set(registry).map_err(|_| anyhow!("Failed to initialize asset registry!"))
 }
 
 type AssetId = (AssetUrl, TypeId);
 type AssetMap = HashMap<AssetId, Arc<dyn Asset>>;
 
+/// Re-bakes the raw source of an asset, registering the fresh assets into the registry.
+pub type AssetReloader = Arc<dyn Fn(&AssetRegistry) -> Result<()> + Send + Sync>;
+
+/// Where the raw resources baked into an asset type live.
+#[derive(Clone, Debug)]
+pub struct RawSource {
+    /// Folder containing raw resources.
+    pub content_dir: PathBuf,
+    /// Folder baked assets are serialized to.
+    pub cache_dir: PathBuf,
+    /// Extension of the raw resource, e.g. `gltf`.
+    pub extension: &'static str,
+}
+
 #[derive(Default)]
 pub struct AssetRegistry {
     assets_map: RwLock<AssetMap>,
+    reloaders: RwLock<HashMap<AssetId, AssetReloader>>,
+    raw_sources: RwLock<HashMap<AssetType, RawSource>>,
 }
 
 unsafe impl Send for AssetRegistry {}
@@ -55,6 +73,37 @@ impl AssetRegistry {
         self.assets_map.write().remove(&key).is_some()
     }
 
+    /// Set where raw resources of an asset type live, used by [`AssetHandle::get_or_load`].
+    pub fn register_raw_source(&self, ty: AssetType, source: RawSource) {
+        self.raw_sources.write().insert(ty, source);
+    }
+
+    fn raw_source(&self, ty: AssetType) -> Option<RawSource> {
+        self.raw_sources.read().get(&ty).cloned()
+    }
+
+    /// Set how to re-bake an asset from its raw source, used by [`AssetRegistry::reload`].
+    pub fn set_reloader<A: Asset>(&self, url: impl Into<AssetUrl>, reloader: AssetReloader) {
+        let key = (url.into(), TypeId::of::<A>());
+        self.reloaders.write().insert(key, reloader);
+    }
+
+    /// Re-bake an asset from its raw source and swap it in the registry.
+    ///
+    /// Fresh assets replace the `Arc`s in the registry under the write lock, so outstanding [`AssetRef`]s
+    /// keep reading the old data until they are dropped. GPU resources derived from the asset are not
+    /// touched, the renderer must rebuild them after a reload.
+    pub fn reload<A: Asset>(&self, url: impl Into<AssetUrl>) -> Result<()> {
+        let key = (url.into(), TypeId::of::<A>());
+        // Clone out of the lock, baking may take a while.
+        let reloader = self.reloaders.read()
+            .get(&key)
+            .cloned()
+            .ok_or(anyhow!("No raw source to reload asset {:?} from", key.0))?;
+
+        reloader(self)
+    }
+
     /// Get an asset by url. Return None is this asset had NOT been loaded.
     fn get<A: Asset>(&self, url: AssetUrl) -> Option<AssetRef<'_, A>> {
         let assets = self.assets_map.read();
@@ -175,17 +224,43 @@ impl<A: Asset> AssetHandle<A> {
     pub fn get(&self) -> Option<AssetRef<'_, A>> {
         ASSET_REGISTRY.get().unwrap().get(self.url.clone())
     }
+
+    /// Get the underlying asset data, loading and baking its raw resource synchronously on a miss.
+    /// The raw resource is found through the [`RawSource`] registered for the asset type.
+    ///
+    /// Blocks until the asset is available, use [`AssetHandle::get`] for the non-blocking variant.
+    pub fn get_or_load<L, B>(&self) -> Result<AssetRef<'_, A>>
+    where
+        L: RawResourceLoader,
+        B: RawResourceBaker<Raw = L::Raw>,
+    {
+        if let Some(asset) = self.get() {
+            return Ok(asset);
+        }
+
+        let registry = ASSET_REGISTRY.get().unwrap();
+        let ty = self.url.ty();
+        let source = registry.raw_source(ty).ok_or(anyhow!("No raw source registered for {:?}", ty))?;
+
+        let relative_path = self.url.path.with_extension(source.extension);
+        let raw = L::load(&source.content_dir.join(&relative_path))?;
+        B::bake(raw, registry, &source.cache_dir, &AssetUrl::from(relative_path.clone()))?;
+
+        self.get().ok_or(anyhow!("Baking {:?} didn't produce asset {:?}", relative_path, self.url))
+    }
 }
 
 pub struct AssetRef<'a, A> {
-    asset: Arc<dyn Asset>,
+    asset: Arc<A>,
     _marker: PhantomData<&'a A>,
 }
 
 impl<'a, A: Asset> AssetRef<'a, A> {
+    /// Return None if the asset is not an `A`.
     fn new(asset: Arc<dyn Asset>) -> Option<Self> {
+        let asset: Arc<dyn Any + Send + Sync> = asset;
         Some(Self {
-            asset,
+            asset: asset.downcast::<A>().ok()?,
             _marker: PhantomData,
         })
     }
@@ -195,10 +270,7 @@ impl<'a, A: Asset> Deref for AssetRef<'a, A> {
     type Target = A;
 
     fn deref(&self) -> &Self::Target {
-        unsafe {
-            // Safety: asset type is checked by TypeId in AssetRegistry when calling get()
-            self.asset.as_ref().as_any().downcast_ref::<A>().unwrap_unchecked()
-        }
+        &self.asset
     }
 }
 
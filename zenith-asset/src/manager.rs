@@ -1,9 +1,11 @@
 ﻿use std::ffi::OsStr;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use anyhow::Result;
+use parking_lot::Mutex;
 use zenith_core::log::info;
 use crate::gltf_loader::{GltfLoader, RawGltfProcessor};
-use crate::{RawResourceBaker, AssetLoadRequest, AssetType, RawResourceLoadRequest, RawResourceLoader, ASSET_REGISTRY, RawResourceLoadRequestBuilder, AssetLoadRequestBuilder, Asset, AssetUrl, deserialize_asset};
+use crate::{RawResourceBaker, AssetLoadRequest, AssetType, RawResourceLoadRequest, RawResourceLoader, ASSET_REGISTRY, RawResourceLoadRequestBuilder, AssetLoadRequestBuilder, Asset, AssetUrl, AssetRegistry, AssetReloader, RawSource, deserialize_asset};
 use crate::render::{Material, Mesh, MeshCollection, Texture};
 
 fn workspace_root() -> PathBuf {
@@ -26,12 +28,26 @@ fn workspace_root() -> PathBuf {
     PathBuf::from(env!("CARGO_MANIFEST_DIR")).parent().unwrap().to_path_buf()
 }
 
+/// Baked meshes, materials and mesh collections come from glTF files under content/.
+pub(crate) fn register_raw_sources(registry: &AssetRegistry) {
+    let root = workspace_root();
+    for ty in [AssetType::Mesh, AssetType::Material, AssetType::MeshCollection] {
+        registry.register_raw_source(ty, RawSource {
+            content_dir: root.join("content/"),
+            cache_dir: root.join("cache/"),
+            extension: "gltf",
+        });
+    }
+}
+
 /// Managing the loading, registering of assets and maintaining assets' cache.
 /// Asset lifetime:
 ///     Load -> Register -> Unregister -> Unload
 pub struct AssetManager {
     cache_dir: PathBuf,
     content_dir: PathBuf,
+    /// Raw assets requested so far, checked for changes by `reload_modified`.
+    watched: Mutex<Vec<PathBuf>>,
 }
 
 impl AssetManager {
@@ -40,6 +56,7 @@ impl AssetManager {
         Self {
             cache_dir: root.to_owned().join("cache/"),
             content_dir: root.join("content/"),
+            watched: Mutex::new(Vec::new()),
         }
     }
 
@@ -62,19 +79,72 @@ impl AssetManager {
             info!("load raw asset {:?}", url);
 
             self.request_load_raw(RawResourceLoadRequestBuilder::default()
-                .relative_path(url)
-                .build()?)
+                .relative_path(url.clone())
+                .build()?)?;
         } else {
             info!("load asset {:?}", url);
 
             // TODO: this should be validate as AssetUrl
-            let mut url = url;
+            let mut url = url.clone();
             url.set_extension(MeshCollection::extension());
 
             self.request_load_asset(AssetLoadRequestBuilder::default()
                 .url(url)
-                .build()?)
+                .build()?)?;
+        }
+
+        self.register_reloaders(&url)?;
+
+        let mut watched = self.watched.lock();
+        if !watched.contains(&url) {
+            watched.push(url);
+        }
+        Ok(())
+    }
+
+    /// Re-bake every requested raw asset modified on disk since it was baked.
+    /// Acts as a polling file watcher, call it periodically (e.g. once per frame).
+    ///
+    /// Returns the raw assets reloaded, GPU resources derived from them must be rebuilt by the renderer.
+    #[profiling::function]
+    pub fn reload_modified(&self) -> Result<Vec<PathBuf>> {
+        let modified = self.watched.lock()
+            .iter()
+            .filter(|path| self.should_bake_asset(path))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        for path in &modified {
+            info!("reload modified raw asset {:?}", path);
+
+            Self::bake_raw(&self.content_dir, &self.cache_dir, path, ASSET_REGISTRY.get().unwrap())?;
+            self.register_reloaders(path)?;
         }
+
+        Ok(modified)
+    }
+
+    /// Let [`AssetRegistry::reload`] re-bake the assets baked from the raw asset at `relative_path`.
+    fn register_reloaders(&self, relative_path: &Path) -> Result<()> {
+        let collection_url = MeshCollection::new(relative_path).asset_url();
+        let collection: MeshCollection = deserialize_asset(&self.cache_dir.join(&collection_url))?;
+
+        let reloader: AssetReloader = {
+            let content_dir = self.content_dir.clone();
+            let cache_dir = self.cache_dir.clone();
+            let relative_path = relative_path.to_owned();
+            Arc::new(move |registry| Self::bake_raw(&content_dir, &cache_dir, &relative_path, registry))
+        };
+
+        let registry = ASSET_REGISTRY.get().unwrap();
+        for mesh_url in collection.meshes {
+            registry.set_reloader::<Mesh>(mesh_url, reloader.clone());
+        }
+        for mat_url in collection.materials {
+            registry.set_reloader::<Material>(mat_url, reloader.clone());
+        }
+
+        Ok(())
     }
 
     #[profiling::function]
@@ -116,17 +186,22 @@ impl AssetManager {
 
     #[profiling::function]
     fn request_load_raw(&self, load_request: RawResourceLoadRequest) -> Result<()> {
+        Self::bake_raw(&self.content_dir, &self.cache_dir, &load_request.relative_path, ASSET_REGISTRY.get().unwrap())
+    }
+
+    #[profiling::function]
+    fn bake_raw(content_dir: &Path, cache_dir: &PathBuf, relative_path: &Path, registry: &AssetRegistry) -> Result<()> {
         // TODO: support other types of raw asset
-        assert_eq!(load_request.relative_path.extension(), Some(OsStr::new("gltf")));
+        assert_eq!(relative_path.extension(), Some(OsStr::new("gltf")));
 
-        let raw_content_path = self.content_dir.join(&load_request.relative_path);
+        let raw_content_path = content_dir.join(relative_path);
 
         // Load the raw asset synchronously
         let raw = GltfLoader::load(&raw_content_path)?;
 
         // Bake the asset synchronously
-        let asset_url = AssetUrl::from(load_request.relative_path.clone());
-        RawGltfProcessor::bake(raw, ASSET_REGISTRY.get().unwrap(), &self.cache_dir, &asset_url)?;
+        let asset_url = AssetUrl::from(relative_path.to_owned());
+        RawGltfProcessor::bake(raw, registry, cache_dir, &asset_url)?;
 
         info!("Successfully baked asset {:?}", raw_content_path);
         Ok(())
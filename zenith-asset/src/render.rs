@@ -3,7 +3,7 @@ use std::path::{Path, PathBuf};
 use bincode::{Decode, Encode};
 use bytemuck::{NoUninit, Pod, Zeroable};
 use derive_builder::Builder;
-use glam::{Vec2, Vec3};
+use glam::{Mat4, Vec2, Vec3, Vec4};
 use serde::{Deserialize, Serialize};
 use super::{Asset, AssetUrl};
 
@@ -13,16 +13,50 @@ pub struct Vertex {
     pub position: [f32; 3],
     pub normal: [f32; 3],
     pub tex_coord: [f32; 2],
+    /// Tangent xyz with the bitangent sign in w, as in glTF.
+    pub tangent: [f32; 4],
+    /// Indices into the skin joints. At most 4 joints influence a vertex.
+    pub joints: [u16; 4],
+    /// Weight of each joint in `joints`, summing to one.
+    pub weights: [f32; 4],
 }
 
 impl Vertex {
-    pub fn new(position: Vec3, normal: Vec3, tex_coord: Vec2) -> Self {
+    pub fn new(position: Vec3, normal: Vec3, tex_coord: Vec2, tangent: Vec4) -> Self {
         Self {
             position: position.to_array(),
             normal: normal.to_array(),
             tex_coord: tex_coord.to_array(),
+            tangent: tangent.to_array(),
+            joints: [0; 4],
+            weights: [1.0, 0.0, 0.0, 0.0],
         }
     }
+
+    pub fn with_skinning(mut self, joints: [u16; 4], weights: [f32; 4]) -> Self {
+        self.joints = joints;
+        self.weights = weights;
+        self
+    }
+}
+
+/// Joints and bind pose of a glTF skin.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Encode, Decode)]
+pub struct Skin {
+    pub name: Option<String>,
+    /// Node index of each joint, vertex joint indices index into this list.
+    pub joints: Vec<usize>,
+    /// Column-major inverse bind matrix of each joint.
+    pub inverse_bind_matrices: Vec<[[f32; 4]; 4]>,
+}
+
+/// Per-vertex deltas of a blend shape, applied on top of the base mesh weighted by the target weight.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Encode, Decode)]
+pub struct MorphTarget {
+    /// Position delta per vertex, empty if the target doesn't displace positions.
+    pub position_deltas: Vec<[f32; 3]>,
+    /// Normal delta per vertex, empty if the target doesn't displace normals.
+    pub normal_deltas: Vec<[f32; 3]>,
 }
 
 #[derive(Debug, Clone, Builder, Serialize, Deserialize, Encode, Decode)]
@@ -33,6 +67,8 @@ pub struct Mesh<V = Vertex> {
     #[builder(default)]
     #[bincode(with_serde)]
     pub material: Option<usize>,
+    #[builder(default)]
+    pub morph_targets: Vec<MorphTarget>,
 }
 
 impl<V: NoUninit> Mesh<V> {
@@ -41,6 +77,7 @@ impl<V: NoUninit> Mesh<V> {
             vertices,
             indices,
             material,
+            morph_targets: vec![],
         }
     }
     
@@ -182,6 +219,11 @@ pub struct MeshCollection {
     pub meshes: Vec<AssetUrl>,
     #[bincode(with_serde)]
     pub materials: Vec<AssetUrl>,
+    /// Column-major model-to-world transform of each mesh, accumulated down the node hierarchy.
+    pub transforms: Vec<[[f32; 4]; 4]>,
+    pub skins: Vec<Skin>,
+    /// Index into `skins` of each mesh, `None` for rigid meshes.
+    pub mesh_skins: Vec<Option<usize>>,
 }
 
 impl Asset for MeshCollection {
@@ -206,12 +248,22 @@ impl MeshCollection {
             raw_asset_path: raw_asset_path.as_ref().into(),
             meshes: vec![],
             materials: vec![],
+            transforms: vec![],
+            skins: vec![],
+            mesh_skins: vec![],
         }
     }
 
-    pub fn add_mesh(&mut self, mesh_url: AssetUrl, mat_url: AssetUrl) {
+    pub fn add_mesh(&mut self, mesh_url: AssetUrl, mat_url: AssetUrl, transform: Mat4, skin: Option<usize>) {
         self.meshes.push(mesh_url);
         self.materials.push(mat_url);
+        self.transforms.push(transform.to_cols_array_2d());
+        self.mesh_skins.push(skin);
+    }
+
+    /// Model-to-world transform of the mesh at `index`.
+    pub fn transform(&self, index: usize) -> Mat4 {
+        Mat4::from_cols_array_2d(&self.transforms[index])
     }
 
     // "mesh/cerberus/scene.gltf" -> "mesh/cerberus/scene.mscl"
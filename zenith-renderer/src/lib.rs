@@ -1,3 +1,5 @@
 mod triangle;
+mod shadow;
 
 pub use triangle::TriangleRenderer;
+pub use shadow::{ShadowCaster, ShadowMapRenderer};
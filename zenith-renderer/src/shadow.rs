@@ -0,0 +1,176 @@
+use std::sync::Arc;
+use zenith_rhi::{vk, RenderDevice, Buffer, Shader, TextureDesc, TextureState, BufferState, Texture, VertexAttribute, VertexBinding};
+use zenith_rendergraph::{
+    DepthStencilDescBuilder, RenderGraphBuilder, RenderGraphResource,
+    GraphicShaderInputBuilder, GraphicPipelineStateBuilder,
+};
+use zenith_rhi::pipeline::RasterizationStateBuilder;
+
+/// A mesh drawn into the shadow map.
+///
+/// Only the position is read, as a `float3` at offset 0 of each `vertex_stride` sized vertex.
+/// The buffers must already be in the [`BufferState::Vertex`] and [`BufferState::Index`] states,
+/// e.g. by the upload that filled them.
+#[derive(Clone)]
+pub struct ShadowCaster {
+    pub vertex_buffer: Arc<Buffer>,
+    pub index_buffer: Arc<Buffer>,
+    pub vertex_stride: u32,
+    pub index_count: u32,
+    pub index_type: vk::IndexType,
+}
+
+/// Renders shadow casters into a depth-only shadow map from the light's point of view.
+pub struct ShadowMapRenderer {
+    vertex_shader: Arc<Shader>,
+    resolution: u32,
+    format: vk::Format,
+}
+
+impl ShadowMapRenderer {
+    pub fn new(device: &RenderDevice, resolution: u32) -> anyhow::Result<Self> {
+        let format = TextureDesc::best_depth_format(device, false)?;
+
+        let vertex_shader = Shader::from_file(
+            "shader.shadow.vs",
+            device,
+            std::path::Path::new("content/shaders/shadow.slang"),
+            "vsmain",
+            zenith_rhi::ShaderStage::Vertex,
+            &Default::default(),
+        )?;
+
+        Ok(Self {
+            vertex_shader: Arc::new(vertex_shader),
+            resolution,
+            format,
+        })
+    }
+
+    #[inline]
+    pub fn resolution(&self) -> u32 { self.resolution }
+
+    /// Render `casters` into a new shadow map with the column-major `light_view_proj` matrix.
+    ///
+    /// The node has a depth attachment only and no fragment shader. Front faces are culled, so the
+    /// stored depth is that of back faces, which keeps lit surfaces clear of shadow acne.
+    /// Read the returned texture as [`TextureState::Sampled`] in the main pass.
+    pub fn render(
+        &self,
+        builder: &mut RenderGraphBuilder,
+        casters: &[ShadowCaster],
+        light_view_proj: [[f32; 4]; 4],
+    ) -> RenderGraphResource<Texture> {
+        let mut shadow_map = builder.create(TextureDesc {
+            format: self.format,
+            usage: vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT | vk::ImageUsageFlags::SAMPLED,
+            ..TextureDesc::new_depth("shadow.map", self.resolution, self.resolution)
+        });
+
+        let casters = casters
+            .iter()
+            .map(|caster| {
+                let vb = builder.import_buffer(caster.vertex_buffer.clone(), BufferState::Vertex);
+                let ib = builder.import_buffer(caster.index_buffer.clone(), BufferState::Index);
+                (vb, ib, caster)
+            })
+            .collect::<Vec<_>>();
+
+        let mut node = builder.add_graphic_node("shadow");
+
+        let draws = casters
+            .into_iter()
+            .map(|(vb, ib, caster)| {
+                let vb = node.read(&vb, BufferState::Vertex);
+                let ib = node.read(&ib, BufferState::Index);
+                (vb, ib, caster.vertex_stride, caster.index_count, caster.index_type)
+            })
+            .collect::<Vec<_>>();
+        let depth_rt = node.write(&mut shadow_map, TextureState::DepthStencil);
+
+        // Every caster shares the pipeline, so they must agree on the vertex stride.
+        let stride = draws.first().map(|(_, _, stride, _, _)| *stride).unwrap_or(0);
+        debug_assert!(
+            draws.iter().all(|(_, _, s, _, _)| *s == stride),
+            "shadow casters must share one vertex stride"
+        );
+
+        let shader = GraphicShaderInputBuilder::default()
+            .vertex_shader(self.vertex_shader.clone())
+            .push_vertex_binding(VertexBinding {
+                binding: 0,
+                stride,
+                input_rate: vk::VertexInputRate::VERTEX,
+            })
+            .push_vertex_attribute(VertexAttribute {
+                location: 0,
+                binding: 0,
+                format: vk::Format::R32G32B32_SFLOAT,
+                offset: 0,
+            })
+            .build()
+            .expect("shadow map shader input is incomplete");
+
+        let depth_info = DepthStencilDescBuilder::default()
+            .depth_test_enable(true)
+            .depth_write_enable(true)
+            .depth_compare_op(vk::CompareOp::LESS_OR_EQUAL)
+            .build()
+            .expect("shadow map depth state is incomplete");
+
+        let state = GraphicPipelineStateBuilder::default()
+            .rasterization(
+                RasterizationStateBuilder::default()
+                    .cull_mode(vk::CullModeFlags::FRONT)
+                    .build()
+                    .expect("shadow map rasterization state is incomplete"),
+            )
+            .build();
+
+        {
+            let mut binder = node.pipeline(shader, state);
+            binder.depth(depth_rt, depth_info);
+            binder.finish();
+        }
+
+        let resolution = self.resolution;
+
+        node.execute(move |ctx| {
+            let extent = vk::Extent2D { width: resolution, height: resolution };
+            let encoder = ctx.encoder();
+
+            ctx.begin_rendering(extent);
+            ctx.bind_pipeline();
+
+            let viewport = vk::Viewport {
+                x: 0.0,
+                y: 0.0,
+                width: resolution as f32,
+                height: resolution as f32,
+                min_depth: 0.0,
+                max_depth: 1.0,
+            };
+            encoder.set_viewport(0, &[viewport]);
+
+            let scissor = vk::Rect2D {
+                offset: vk::Offset2D { x: 0, y: 0 },
+                extent,
+            };
+            encoder.set_scissor(0, &[scissor]);
+
+            ctx.push_constants(vk::ShaderStageFlags::ALL_GRAPHICS, 0, &light_view_proj);
+
+            for (vb, ib, _, index_count, index_type) in &draws {
+                ctx.bind_vertex_buffer(vb);
+                ctx.bind_index_buffer(ib, *index_type);
+                ctx.draw_indexed(*index_count);
+            }
+
+            ctx.end_rendering();
+
+            Ok(())
+        });
+
+        shadow_map
+    }
+}
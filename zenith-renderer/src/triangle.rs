@@ -55,6 +55,7 @@ impl TriangleRenderer {
             std::path::Path::new("content/shaders/triangle.slang"),
             "vsmain",
             zenith_rhi::ShaderStage::Vertex,
+            &Default::default(),
         )?;
 
         let fragment_shader = Shader::from_file(
@@ -63,6 +64,7 @@ impl TriangleRenderer {
             std::path::Path::new("content/shaders/triangle.slang"),
             "psmain",
             zenith_rhi::ShaderStage::Fragment,
+            &Default::default(),
         )?;
 
         Ok(Self {
@@ -167,10 +169,10 @@ impl TriangleRenderer {
             };
             encoder.set_scissor(0, &[scissor]);
 
-            encoder.bind_vertex_buffers(0, &[ctx.get(&vb).handle()], &[0]);
-            encoder.bind_index_buffer(ctx.get(&ib).handle(), 0, vk::IndexType::UINT16);
+            ctx.bind_vertex_buffer(&vb);
+            ctx.bind_index_buffer(&ib, vk::IndexType::UINT16);
 
-            encoder.draw_indexed(3, 1, 0, 0, 0);
+            ctx.draw_indexed(3);
 
             ctx.end_rendering();
 
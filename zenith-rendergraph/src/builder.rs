@@ -1,21 +1,22 @@
 use crate::graph::{GraphicNodeExecutionContext, LambdaNodeExecutionContext, RenderGraph};
 use crate::interface::{ResourceDescriptor, ResourceState};
-use crate::node::{NodePipelineState, RenderGraphNode};
+use crate::node::{GraphicPipelineDescriptor, NodePipelineState, RenderGraphNode, ResolveTarget};
 use crate::resource::{
     ExportResourceStorage, ExportedRenderGraphResource, GraphImportExportResource,
     GraphResource, GraphResourceDescriptor, GraphResourceId,
     GraphResourceView, InitialResourceStorage,
-    RenderGraphResource, RenderGraphResourceAccess, Rt, Srv, Uav};
+    RenderGraphResource, RenderGraphResourceAccess, Rt, Srv, TextureSlice, Uav};
 use log::warn;
 use std::marker::PhantomData;
 use std::sync::Arc;
-use zenith_rhi::{vk, ColorAttachmentDesc, DepthStencilDesc, GraphicPipelineDesc, GraphicPipelineState, GraphicShaderInput, GraphicPipelineAttachments};
+use zenith_rhi::{vk, Buffer, BufferState, ColorAttachmentDesc, ColorInfo, DepthStencilDesc, GraphicPipelineDesc, GraphicPipelineState, GraphicShaderInput, GraphicPipelineAttachments};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub(crate) struct ResourceAccessStorage {
     pub(crate) id: GraphResourceId,
     pub(crate) access: ResourceState,
     pub(crate) stage_hint: Option<vk::PipelineStageFlags2>,
+    pub(crate) slice: Option<TextureSlice>,
 }
 
 #[derive(Default)]
@@ -63,6 +64,19 @@ impl RenderGraphBuilder {
         GraphImportExportResource::import(import_resource, self, access)
     }
 
+    /// Import an externally owned buffer (e.g. a persistent vertex buffer) into the graph.
+    ///
+    /// `initial` seeds the buffer's state tracker, so the first barrier recorded for it transitions
+    /// from this state. It must match the buffer's actual GPU state or that barrier will be wrong.
+    #[must_use]
+    pub fn import_buffer(
+        &mut self,
+        buffer: impl Into<Arc<Buffer>>,
+        initial: BufferState,
+    ) -> RenderGraphResource<Buffer> {
+        GraphImportExportResource::import(buffer, self, initial)
+    }
+
     #[must_use]
     pub fn export<R: GraphImportExportResource>(
         &mut self,
@@ -81,9 +95,9 @@ impl RenderGraphBuilder {
             inputs: vec![],
             outputs: vec![],
             pipeline_state: NodePipelineState::Graphic {
+                descriptor: GraphicPipelineDescriptor::default(),
                 pipeline_desc: None,
-                color_attachments: vec![],
-                depth_attachment: None,
+                resolve_targets: vec![],
                 job_functor: None,
             },
         });
@@ -154,6 +168,7 @@ impl CommonNodeBuilder<'_, '_> {
         let access = RenderGraphResourceAccess {
             id: resource.id,
             access: access.into(),
+            slice: None,
             _marker: PhantomData,
         };
 
@@ -181,6 +196,7 @@ impl CommonNodeBuilder<'_, '_> {
         let access = RenderGraphResourceAccess {
             id: resource.id,
             access: access.into(),
+            slice: None,
             _marker: PhantomData,
         };
 
@@ -207,6 +223,7 @@ impl CommonNodeBuilder<'_, '_> {
         let access = RenderGraphResourceAccess {
             id: resource.id,
             access: access.into(),
+            slice: None,
             _marker: PhantomData,
         };
 
@@ -234,6 +251,7 @@ impl CommonNodeBuilder<'_, '_> {
         let access = RenderGraphResourceAccess {
             id: resource.id,
             access: access.into(),
+            slice: None,
             _marker: PhantomData,
         };
 
@@ -250,6 +268,34 @@ impl CommonNodeBuilder<'_, '_> {
 
         access
     }
+
+    #[must_use]
+    fn write_slice<V: GraphResourceView>(
+        &mut self,
+        resource: &mut RenderGraphResource<crate::interface::Texture>,
+        access: crate::interface::TextureState,
+        slice: TextureSlice,
+    ) -> RenderGraphResourceAccess<crate::interface::Texture, V>  {
+        let access = RenderGraphResourceAccess {
+            id: resource.id,
+            access: access.into(),
+            slice: Some(slice),
+            _marker: PhantomData,
+        };
+
+        if !self.node.outputs.iter().any(|h| h.id == resource.id) {
+            self.node.outputs.push(access.as_untyped());
+        } else {
+            let name = self.resources
+                .get(resource.id as usize)
+                .expect("Graph resource id out of bound!")
+                .name();
+
+            warn!("Try to write to resource[{name}] multiple time!")
+        }
+
+        access
+    }
 }
 
 macro_rules! inject_common_node_builder_methods {
@@ -295,6 +341,19 @@ macro_rules! inject_common_node_builder_methods {
         ) -> RenderGraphResourceAccess<R, $write_view>  {
             self.common.write_hint(resource, access, stage_hint)
         }
+
+        /// Write only a slice of a texture (e.g. one cubemap face). Barriers and attachment views
+        /// are restricted to that slice.
+        #[must_use]
+        #[inline]
+        pub fn write_slice(
+            &mut self,
+            resource: &mut RenderGraphResource<crate::interface::Texture>,
+            access: crate::interface::TextureState,
+            slice: TextureSlice,
+        ) -> RenderGraphResourceAccess<crate::interface::Texture, $write_view>  {
+            self.common.write_slice(resource, access, slice)
+        }
     };
 }
 
@@ -317,14 +376,73 @@ impl<'node, 'res> GraphicNodeBuilder<'node, 'res> {
         }
     }
 
+    /// Write `msaa` as an attachment and resolve it into the single-sampled `resolved` when rendering ends.
+    ///
+    /// `state` must be `Color` or `DepthStencil`, both textures are transitioned to it, except a depth
+    /// `resolved` which goes to `DepthStencilResolve` as resolves write it as a color attachment.
+    /// Bind the returned access with `push_color` or `depth`; downstream nodes read `resolved`. Color is
+    /// averaged and depth takes sample zero, use [`Self::write_resolve_mode`] for integer color formats
+    /// or other depth modes.
+    #[must_use]
+    pub fn write_resolve(
+        &mut self,
+        msaa: &mut RenderGraphResource<crate::interface::Texture>,
+        resolved: &mut RenderGraphResource<crate::interface::Texture>,
+        state: crate::interface::TextureState,
+    ) -> RenderGraphResourceAccess<crate::interface::Texture, Rt> {
+        let mode = match state {
+            crate::interface::TextureState::DepthStencil => vk::ResolveModeFlags::SAMPLE_ZERO,
+            _ => vk::ResolveModeFlags::AVERAGE,
+        };
+        self.write_resolve_mode(msaa, resolved, state, mode)
+    }
+
+    /// [`Self::write_resolve`] with an explicit resolve mode. Depth modes must be supported by the device,
+    /// see [`zenith_rhi::core::DepthStencilResolveSupport`], which is checked when the graph is compiled.
+    #[must_use]
+    pub fn write_resolve_mode(
+        &mut self,
+        msaa: &mut RenderGraphResource<crate::interface::Texture>,
+        resolved: &mut RenderGraphResource<crate::interface::Texture>,
+        state: crate::interface::TextureState,
+        mode: vk::ResolveModeFlags,
+    ) -> RenderGraphResourceAccess<crate::interface::Texture, Rt> {
+        // Resolves run in the color attachment output stage with color attachment access, for depth too.
+        let (msaa_stage, resolve_state) = match state {
+            crate::interface::TextureState::Color => (
+                vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT,
+                crate::interface::TextureState::Color,
+            ),
+            crate::interface::TextureState::DepthStencil => (
+                vk::PipelineStageFlags2::EARLY_FRAGMENT_TESTS | vk::PipelineStageFlags2::LATE_FRAGMENT_TESTS,
+                crate::interface::TextureState::DepthStencilResolve,
+            ),
+            other => panic!(
+                "write_resolve: node [{}] resolves with state {:?}, only Color and DepthStencil can be resolved.",
+                self.common.node.name(), other
+            ),
+        };
+
+        let access = self.common.write_hint(msaa, state, msaa_stage);
+        let resolve_stage = resolve_state.into_pipeline_stage(vk::PipelineStageFlags2::NONE);
+        let _: RenderGraphResourceAccess<crate::interface::Texture, Rt> = self.common.write_hint(resolved, resolve_state, resolve_stage);
+
+        if let NodePipelineState::Graphic { resolve_targets, .. } = &mut self.common.node.pipeline_state {
+            resolve_targets.push(ResolveTarget { msaa: msaa.id, resolved: resolved.id, mode });
+        } else {
+            unreachable!();
+        }
+        access
+    }
+
     pub fn pipeline(&mut self, shader: GraphicShaderInput, state: GraphicPipelineState) -> AttachmentBinder<'_, 'res> {
-        // Clear any previous attachment bindings / desc.
-        if let NodePipelineState::Graphic { pipeline_desc, color_attachments, depth_attachment, .. } =
-            &mut self.common.node.pipeline_state
-        {
-            *pipeline_desc = None;
-            color_attachments.clear();
-            *depth_attachment = None;
+        // Replace any previous pipeline along with its attachment bindings.
+        if let NodePipelineState::Graphic { descriptor, .. } = &mut self.common.node.pipeline_state {
+            *descriptor = GraphicPipelineDescriptor {
+                shader: Some(shader),
+                state,
+                ..Default::default()
+            };
         } else {
             unreachable!();
         }
@@ -332,29 +450,41 @@ impl<'node, 'res> GraphicNodeBuilder<'node, 'res> {
         AttachmentBinder {
             node: self.common.node,
             resources: self.common.resources,
-            shader: Some(shader),
-            state: Some(state),
-            finished: false,
         }
     }
 }
 
+/// Binds the attachments of a graphic node's pipeline.
+///
+/// The bound targets are stored in the node's [`GraphicPipelineDescriptor`], which becomes a
+/// [`GraphicPipelineDesc`] with formats taken from the materialized textures when the graph is compiled.
 pub struct AttachmentBinder<'node, 'res> {
     node: &'node mut RenderGraphNode,
     resources: &'res Vec<InitialResourceStorage>,
-    shader: Option<GraphicShaderInput>,
-    state: Option<GraphicPipelineState>,
-    finished: bool,
 }
 
 impl<'node, 'res> AttachmentBinder<'node, 'res> {
+    /// Append a color target with its own blend/load/store description.
+    ///
+    /// Call once per target for multiple render targets (e.g. a G-buffer). The order of the calls
+    /// is the attachment order of both the blend state and `begin_rendering`, i.e. `location = N`
+    /// in the fragment shader writes the N-th pushed target.
     pub fn push_color(
         &mut self,
         rt: RenderGraphResourceAccess<crate::interface::Texture, Rt>,
         desc: ColorAttachmentDesc,
     ) -> &mut Self {
-        if let NodePipelineState::Graphic { color_attachments, .. } = &mut self.node.pipeline_state {
-            color_attachments.push((rt.id, desc));
+        self.with_color(rt, desc.into())
+    }
+
+    /// Append a color target described by a [`ColorInfo`], see [`Self::push_color`].
+    pub fn with_color(
+        &mut self,
+        rt: RenderGraphResourceAccess<crate::interface::Texture, Rt>,
+        info: ColorInfo,
+    ) -> &mut Self {
+        if let NodePipelineState::Graphic { descriptor, .. } = &mut self.node.pipeline_state {
+            descriptor.color_attachments.push((rt.id, info));
         } else {
             unreachable!();
         }
@@ -366,64 +496,56 @@ impl<'node, 'res> AttachmentBinder<'node, 'res> {
         rt: RenderGraphResourceAccess<crate::interface::Texture, Rt>,
         desc: DepthStencilDesc,
     ) -> &mut Self {
-        if let NodePipelineState::Graphic { depth_attachment, .. } = &mut self.node.pipeline_state {
-            *depth_attachment = Some((rt.id, desc));
+        if let NodePipelineState::Graphic { descriptor, .. } = &mut self.node.pipeline_state {
+            descriptor.depth_attachment = Some((rt.id, desc));
         } else {
             unreachable!();
         }
         self
     }
 
-    pub fn finish(mut self) -> GraphicPipelineDesc {
-        let desc = self.finalize();
-        self.finished = true;
-        desc
-    }
-
-    fn finalize(&mut self) -> GraphicPipelineDesc {
-        let shader = self.shader.take().expect("AttachmentBinder finalized twice");
-        let mut state = self.state.take().expect("AttachmentBinder finalized twice");
-
-        let (color_ids, color_descs, depth_id, depth_desc) = match &mut self.node.pipeline_state {
-            NodePipelineState::Graphic { color_attachments, depth_attachment, .. } => {
-                let (ids, descs): (Vec<_>, Vec<_>) = color_attachments.iter().cloned().unzip();
-                let (depth_id, depth_desc) = depth_attachment.clone().map(|(id, d)| (Some(id), Some(d))).unwrap_or((None, None));
-                (ids, descs, depth_id, depth_desc)
+    /// The pipeline desc as bound so far, with formats of the textures known when building the graph.
+    pub fn finish(self) -> GraphicPipelineDesc {
+        match &self.node.pipeline_state {
+            NodePipelineState::Graphic { descriptor, .. } => {
+                graphic_pipeline_desc(descriptor, |id| texture_format(self.resources, id))
+                    .expect("AttachmentBinder is created with a shader")
             }
             _ => unreachable!(),
-        };
-
-        // Attachments formats (dynamic rendering order).
-        let mut attachments = GraphicPipelineAttachments::default();
-        attachments.color_formats = color_ids
-            .iter()
-            .map(|id| texture_format(self.resources, *id))
-            .collect();
-        attachments.depth_format = depth_id.map(|id| texture_format(self.resources, id));
-        attachments.stencil_format = None;
-
-        // Populate state attachment descs used for blend state + begin_rendering.
-        state.color_blend.attachments = color_descs;
-        if let Some(ds) = depth_desc {
-            state.depth_stencil = Some(ds);
-        }
-
-        let pipeline_desc = GraphicPipelineDesc::new(shader, state, attachments);
-
-        if let NodePipelineState::Graphic { pipeline_desc: slot, .. } = &mut self.node.pipeline_state {
-            *slot = Some(pipeline_desc.clone());
         }
-
-        pipeline_desc
     }
 }
 
-impl Drop for AttachmentBinder<'_, '_> {
-    fn drop(&mut self) {
-        if !self.finished {
-            let _ = self.finalize();
-        }
+/// Assemble the RHI pipeline desc of a graphic node, `None` until the node has a shader.
+///
+/// Each [`ColorInfo`] becomes the blend state and `begin_rendering` load/store/clear of its target,
+/// and [`GraphicPipelineAttachments`] takes the target formats given by `format`.
+pub(crate) fn graphic_pipeline_desc(
+    descriptor: &GraphicPipelineDescriptor,
+    mut format: impl FnMut(GraphResourceId) -> vk::Format,
+) -> Option<GraphicPipelineDesc> {
+    let shader = descriptor.shader.clone()?;
+
+    // Attachments formats (dynamic rendering order).
+    let mut attachments = GraphicPipelineAttachments::default();
+    attachments.color_formats = descriptor.color_attachments
+        .iter()
+        .map(|(id, _)| format(*id))
+        .collect();
+    attachments.depth_format = descriptor.depth_attachment.as_ref().map(|(id, _)| format(*id));
+    attachments.stencil_format = None;
+
+    // Populate state attachment descs used for blend state + begin_rendering.
+    let mut state = descriptor.state.clone();
+    state.color_blend.attachments = descriptor.color_attachments
+        .iter()
+        .map(|(_, info)| info.clone().into())
+        .collect();
+    if let Some((_, ds)) = &descriptor.depth_attachment {
+        state.depth_stencil = Some(ds.clone());
     }
+
+    Some(GraphicPipelineDesc::new(shader, state, attachments))
 }
 
 fn texture_format(resources: &Vec<InitialResourceStorage>, id: GraphResourceId) -> vk::Format {
@@ -431,7 +553,8 @@ fn texture_format(resources: &Vec<InitialResourceStorage>, id: GraphResourceId)
     match storage {
         InitialResourceStorage::ManagedTexture(desc) => desc.format,
         InitialResourceStorage::ImportedTexture(tex, _) => tex.format(),
-        _ => panic!("AttachmentBinder expects a texture resource id."),
+        // Reported with the node name when the graph is compiled.
+        _ => vk::Format::UNDEFINED,
     }
 }
 
@@ -1,15 +1,19 @@
 //! Render graph execution and resource management.
 
+use crate::builder::graphic_pipeline_desc;
 use crate::interface::{Buffer, BufferState, ResourceState, Texture, TextureState};
-use crate::node::{NodePipelineState, RenderGraphNode};
-use crate::resource::{GraphResource, GraphResourceId, GraphResourceState, GraphResourceView, InitialResourceStorage, RenderGraphResourceAccess};
-use std::cell::Cell;
+use crate::node::{NodePipelineState, RenderGraphNode, ResolveTarget};
+use crate::profiler::{FrameTimings, GpuProfiler};
+use crate::resource::{GraphResource, GraphResourceId, GraphResourceState, GraphResourceView, InitialResourceStorage, RenderGraphResourceAccess, TextureSlice};
+use std::cell::RefCell;
+use std::ops::Range;
 use std::sync::Arc;
+use bytemuck::NoUninit;
 use zenith_core::collections::SmallVec;
 use zenith_rhi::{CommandEncoder, BufferBarrier, TextureBarrier, PipelineStages, ShaderReflection, CommandPool};
 use zenith_rhi::{
     vk, GraphicPipeline, GraphicPipelineDesc, PipelineCache, RenderDevice,
-    DescriptorSetBinder, Swapchain,
+    DescriptorSetBinder, PendingCapture, Swapchain,
 };
 
 pub enum ResourceStorage {
@@ -34,11 +38,30 @@ pub enum ResourceStorage {
 }
 
 impl ResourceStorage {
+    pub(crate) fn name(&self) -> &str {
+        match self {
+            ResourceStorage::ManagedBuffer { resource, .. } => resource.name(),
+            ResourceStorage::ImportedBuffer { resource, .. } => resource.name(),
+            ResourceStorage::ManagedTexture { resource, .. } => resource.name(),
+            ResourceStorage::ImportedTexture { resource, .. } => resource.name(),
+        }
+    }
+
+    /// Kind of the resource, for diagnostics.
+    pub(crate) fn kind(&self) -> &'static str {
+        match self {
+            ResourceStorage::ManagedBuffer { .. } => "managed buffer",
+            ResourceStorage::ImportedBuffer { .. } => "imported buffer",
+            ResourceStorage::ManagedTexture { .. } => "managed texture",
+            ResourceStorage::ImportedTexture { .. } => "imported texture",
+        }
+    }
+
     pub(crate) fn as_buffer(&self) -> &Buffer {
         match self {
             ResourceStorage::ManagedBuffer { resource, .. } => resource,
             ResourceStorage::ImportedBuffer { resource, .. } => resource,
-            _ => unreachable!("Expected buffer, but resource is a texture!"),
+            _ => self.kind_mismatch("buffer"),
         }
     }
 
@@ -46,35 +69,123 @@ impl ResourceStorage {
         match self {
             ResourceStorage::ManagedTexture { resource, .. } => resource,
             ResourceStorage::ImportedTexture { resource, .. } => resource,
-            _ => unreachable!("Expected texture, but resource is a buffer!"),
+            _ => self.kind_mismatch("texture"),
+        }
+    }
+
+    /// Kept out of line so the accessors stay a single predictable branch.
+    #[cold]
+    #[inline(never)]
+    fn kind_mismatch(&self, expected: &str) -> ! {
+        panic!(
+            "Render graph resource [{}] accessed as a {}, but it is a {}.",
+            self.name(), expected, self.kind()
+        )
+    }
+
+    /// Name, current access and last reading node, for write-after-read diagnostics.
+    #[cfg(debug_assertions)]
+    pub(crate) fn hazard_state(&self) -> (&str, ResourceState, &RefCell<Option<String>>) {
+        match self {
+            ResourceStorage::ManagedBuffer { resource, state_tracker, .. } => (resource.name(), state_tracker.current_access().into(), &state_tracker.last_reader),
+            ResourceStorage::ImportedBuffer { resource, state_tracker } => (resource.name(), state_tracker.current_access().into(), &state_tracker.last_reader),
+            ResourceStorage::ManagedTexture { resource, state_tracker, .. } => (resource.name(), state_tracker.current_access().into(), &state_tracker.last_reader),
+            ResourceStorage::ImportedTexture { resource, state_tracker } => (resource.name(), state_tracker.current_access().into(), &state_tracker.last_reader),
         }
     }
 }
 
 pub struct ResourceStateTracker<S: GraphResourceState> {
-    current_access: Cell<S>,
-    current_stage: Cell<vk::PipelineStageFlags2>,
+    /// Access and stage per subresource, mip-major (`mip * num_layers + layer`).
+    /// Buffers have a single entry.
+    states: RefCell<Vec<(S, vk::PipelineStageFlags2)>>,
+    num_layers: u32,
+    /// Node that read the resource since its last write.
+    #[cfg(debug_assertions)]
+    last_reader: RefCell<Option<String>>,
 }
 
 impl<S: GraphResourceState> ResourceStateTracker<S> {
     pub(crate) fn new(access: S) -> Self {
+        Self::with_subresources(access, 1, 1)
+    }
+
+    pub(crate) fn with_subresources(access: S, num_mips: u32, num_layers: u32) -> Self {
         Self {
-            current_access: Cell::new(access),
-            current_stage: Cell::new(vk::PipelineStageFlags2::NONE),
+            states: RefCell::new(vec![(access, vk::PipelineStageFlags2::NONE); (num_mips * num_layers) as usize]),
+            num_layers,
+            #[cfg(debug_assertions)]
+            last_reader: RefCell::new(None),
         }
     }
 
     pub(crate) fn current_access(&self) -> S {
-        self.current_access.get()
+        self.states.borrow()[0].0
     }
 
     pub(crate) fn current_stage(&self) -> vk::PipelineStageFlags2 {
-        self.current_stage.get()
+        self.states.borrow()[0].1
     }
 
     pub(crate) fn transition_to(&self, next_access: S, next_stage: vk::PipelineStageFlags2) {
-        self.current_access.set(next_access);
-        self.current_stage.set(next_stage);
+        self.states.borrow_mut().fill((next_access, next_stage));
+    }
+}
+
+/// Subresources of a texture to transition and their previous access and stage.
+type TextureTransition = (Range<u32>, Range<u32>, TextureState, vk::PipelineStageFlags2);
+
+impl ResourceStateTracker<TextureState> {
+    /// Transition `slice` (or the whole texture if `None`) to `next_access`.
+    ///
+    /// Returns the (mips, layers) ranges that need a barrier. A uniformly tracked range yields a
+    /// single transition, otherwise every subresource that changes is transitioned on its own.
+    pub(crate) fn transition_slice(
+        &self,
+        slice: Option<TextureSlice>,
+        next_access: TextureState,
+        next_stage: vk::PipelineStageFlags2,
+    ) -> SmallVec<[TextureTransition; 4]> {
+        let mut states = self.states.borrow_mut();
+        let num_layers = self.num_layers;
+        let num_mips = states.len() as u32 / num_layers;
+
+        let (mips, layers) = match slice {
+            Some(slice) => (slice.mip..slice.mip + 1, slice.base_layer..slice.base_layer + slice.layer_count),
+            None => (0..num_mips, 0..num_layers),
+        };
+        let index = |mip: u32, layer: u32| (mip * num_layers + layer) as usize;
+
+        let first = states[index(mips.start, layers.start)];
+        let uniform = mips.clone()
+            .all(|mip| layers.clone().all(|layer| states[index(mip, layer)] == first));
+
+        let mut transitions = SmallVec::new();
+        if uniform {
+            if first.0 != next_access {
+                transitions.push((mips.clone(), layers.clone(), first.0, first.1));
+            }
+        } else {
+            for mip in mips.clone() {
+                for layer in layers.clone() {
+                    let (access, stage) = states[index(mip, layer)];
+                    if access != next_access {
+                        transitions.push((mip..mip + 1, layer..layer + 1, access, stage));
+                    }
+                }
+            }
+        }
+
+        for mip in mips {
+            for layer in layers.clone() {
+                let state = &mut states[index(mip, layer)];
+                if state.0 != next_access {
+                    *state = (next_access, next_stage);
+                }
+            }
+        }
+
+        transitions
     }
 }
 
@@ -84,6 +195,20 @@ pub struct RenderGraph {
 }
 
 impl RenderGraph {
+    /// Dump nodes and their resource dependencies as a Graphviz DOT graph.
+    /// Managed resources are blue, imported ones are orange.
+    pub fn to_dot(&self) -> String {
+        let resources = self.initial_resources
+            .iter()
+            .map(|res| {
+                let imported = matches!(res, InitialResourceStorage::ImportedBuffer(..) | InitialResourceStorage::ImportedTexture(..));
+                (res.name(), imported)
+            })
+            .collect::<Vec<_>>();
+
+        utility::to_dot(self.nodes.iter(), &resources)
+    }
+
     #[profiling::function]
     pub fn compile(
         mut self,
@@ -109,10 +234,15 @@ impl RenderGraph {
                         let resource = device
                             .acquire_texture(&desc)
                             .expect("Failed to create texture");
+                        let state_tracker = ResourceStateTracker::with_subresources(
+                            TextureState::Undefined,
+                            desc.mip_levels,
+                            desc.array_layers,
+                        );
                         ResourceStorage::ManagedTexture {
                             desc,
                             resource,
-                            state_tracker: ResourceStateTracker::new(TextureState::Undefined),
+                            state_tracker,
                         }
                     }
                     InitialResourceStorage::ImportedBuffer(buffer, initial_state) => ResourceStorage::ImportedBuffer {
@@ -120,8 +250,12 @@ impl RenderGraph {
                         state_tracker: ResourceStateTracker::new(initial_state),
                     },
                     InitialResourceStorage::ImportedTexture(tex, initial_state) => ResourceStorage::ImportedTexture {
+                        state_tracker: ResourceStateTracker::with_subresources(
+                            initial_state,
+                            tex.desc().mip_levels,
+                            tex.desc().array_layers,
+                        ),
                         resource: tex.clone(),
-                        state_tracker: ResourceStateTracker::new(initial_state),
                     },
                 }
             })
@@ -136,12 +270,23 @@ impl RenderGraph {
             }
 
             match &mut node.pipeline_state {
-                NodePipelineState::Graphic { pipeline_desc, .. } => {
-                    let Some(pipeline_desc) = pipeline_desc.as_mut() else {
+                NodePipelineState::Graphic { descriptor, pipeline_desc, resolve_targets, .. } => {
+                    // Attachment formats always come from the textures actually bound to the node.
+                    let resolved = graphic_pipeline_desc(descriptor, |id| utility::attachment_format(&resources, id, &node.name));
+                    let Some(pipeline_desc) = resolved.map(|desc| pipeline_desc.insert(desc)) else {
                         graphic_pipelines.push(None);
                         continue;
                     };
 
+                    for resolve in resolve_targets.iter() {
+                        let is_depth = descriptor.depth_attachment.as_ref().is_some_and(|(id, _)| *id == resolve.msaa);
+                        let is_attachment = is_depth || descriptor.color_attachments.iter().any(|(id, _)| *id == resolve.msaa);
+                        utility::check_resolve_target(&resources, &node.name, resolve, is_attachment, pipeline_desc.state.multisample.samples);
+                        if is_depth {
+                            utility::check_depth_resolve_mode(device, &resources, &node.name, resolve);
+                        }
+                    }
+
                     let pipeline = pipeline_cache
                         .get_or_create(&format!("pipeline.{}", node.name), device, pipeline_desc)
                         .expect("Failed to create graphics pipeline");
@@ -200,14 +345,46 @@ pub struct CompiledRenderGraph {
 }
 
 impl CompiledRenderGraph {
+    /// Dump the compiled nodes (including present nodes) as a Graphviz DOT graph, see [`RenderGraph::to_dot`].
+    pub fn to_dot(&self) -> String {
+        let resources = self.resources
+            .iter()
+            .map(|res| match res {
+                ResourceStorage::ManagedBuffer { resource, .. } => (resource.name(), false),
+                ResourceStorage::ManagedTexture { resource, .. } => (resource.name(), false),
+                ResourceStorage::ImportedBuffer { resource, .. } => (resource.name(), true),
+                ResourceStorage::ImportedTexture { resource, .. } => (resource.name(), true),
+            })
+            .collect::<Vec<_>>();
+        utility::to_dot(self.serial_nodes.iter().chain(&self.present_nodes), &resources)
+    }
+
     #[profiling::function]
     pub fn execute(&mut self, device: &RenderDevice, cmd_pool: &CommandPool) -> anyhow::Result<()>  {
+        self.execute_impl(device, cmd_pool, None).map(|_| ())
+    }
+
+    /// Like [`Self::execute`], also bracketing every graphic node with GPU timestamps.
+    ///
+    /// Returns the timings recorded the last time the current frame slot was used, once the GPU has
+    /// written them. Lambda nodes are not timed. Present nodes are timed by [`Self::present_profiled`].
+    #[profiling::function]
+    pub fn execute_profiled(&mut self, device: &RenderDevice, cmd_pool: &CommandPool, profiler: &mut GpuProfiler) -> anyhow::Result<Option<FrameTimings>> {
+        self.execute_impl(device, cmd_pool, Some(profiler))
+    }
+
+    fn execute_impl(&mut self, device: &RenderDevice, cmd_pool: &CommandPool, mut profiler: Option<&mut GpuProfiler>) -> anyhow::Result<Option<FrameTimings>> {
         let encoder = CommandEncoder::new("cmd.rendergraph.execute", device, cmd_pool)?;
         
         encoder.begin(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT)?;
 
+        let timings = match profiler.as_deref_mut() {
+            Some(profiler) => profiler.begin_frame(&encoder, device.current_frame_index())?,
+            None => None,
+        };
+
         let nodes = std::mem::take(&mut self.serial_nodes);
-        self.record_nodes(device, &encoder, nodes);
+        self.record_nodes(device, &encoder, nodes, profiler);
 
         encoder.end()?;
 
@@ -221,11 +398,44 @@ impl CompiledRenderGraph {
             device.frame_resource_fence(),
         );
 
-        Ok(())
+        Ok(timings)
+    }
+
+    pub fn present(self, device: &mut RenderDevice, cmd_pool: &CommandPool, swapchain: &mut Swapchain) -> anyhow::Result<RetiredRenderGraph> {
+        self.present_impl(device, cmd_pool, swapchain, false, None).map(|(retired, _)| retired)
     }
 
-    pub fn present(mut self, device: &mut RenderDevice, cmd_pool: &CommandPool, swapchain: &mut Swapchain) -> anyhow::Result<RetiredRenderGraph> {
-        let (image_index, _) = swapchain.acquire_next_image(device.handle())?;
+    /// Like [`Self::present`], also bracketing every graphic present node with GPU timestamps.
+    ///
+    /// Must follow [`Self::execute_profiled`] with the same profiler, the timings of present nodes are
+    /// returned along with the executed ones.
+    pub fn present_profiled(self, device: &mut RenderDevice, cmd_pool: &CommandPool, swapchain: &mut Swapchain, profiler: &mut GpuProfiler) -> anyhow::Result<RetiredRenderGraph> {
+        self.present_impl(device, cmd_pool, swapchain, false, Some(profiler)).map(|(retired, _)| retired)
+    }
+
+    /// Like [`Self::present`], also copying the swapchain image right before its transition to `Present`.
+    ///
+    /// Pass the returned capture to [`Swapchain::finish_capture`] before the next frame is acquired.
+    /// No capture is returned when nothing is presented this frame (e.g. the window is minimized).
+    pub fn present_captured(self, device: &mut RenderDevice, cmd_pool: &CommandPool, swapchain: &mut Swapchain) -> anyhow::Result<(RetiredRenderGraph, Option<PendingCapture>)> {
+        self.present_impl(device, cmd_pool, swapchain, true, None)
+    }
+
+    fn present_impl(
+        mut self,
+        device: &mut RenderDevice,
+        cmd_pool: &CommandPool,
+        swapchain: &mut Swapchain,
+        capture: bool,
+        profiler: Option<&mut GpuProfiler>,
+    ) -> anyhow::Result<(RetiredRenderGraph, Option<PendingCapture>)> {
+        let Some((image_index, _)) = swapchain.acquire_next_image(device.handle())? else {
+            // Minimized window or out of date swapchain, nothing to present this frame.
+            // The swapchain is recreated before the next frame.
+            return Ok((RetiredRenderGraph {
+                resources: self.resources,
+            }, None));
+        };
         swapchain.reset_current_fence(device.handle())?;
         device.reset_frame_resources();
         cmd_pool.reset()?;
@@ -244,12 +454,23 @@ impl CompiledRenderGraph {
         encoder.begin(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT)?;
 
         let nodes = std::mem::take(&mut self.present_nodes);
-        self.record_nodes(device, &encoder, nodes);
+        self.record_nodes(device, &encoder, nodes, profiler);
+
+        // the image is handed over to the presentation engine below, so it can only be read back now
+        let pending_capture = if capture && self.swapchain_tex_id != GraphResourceId::MAX {
+            Self::transition_resources(
+                device, &encoder, None, &self.resources,
+                [(self.swapchain_tex_id, TextureState::TransferSrc.into(), Some(vk::PipelineStageFlags2::TRANSFER), None)].into_iter(),
+            );
+            Some(swapchain.record_capture(device, &encoder, image_index)?)
+        } else {
+            None
+        };
 
         // make sure the swapchain texture has the right image layout for presentation
         Self::transition_resources(
             device, &encoder, None, &self.resources,
-            [(self.swapchain_tex_id, TextureState::Present.into(), Some(vk::PipelineStageFlags2::BOTTOM_OF_PIPE))].into_iter(),
+            [(self.swapchain_tex_id, TextureState::Present.into(), Some(vk::PipelineStageFlags2::BOTTOM_OF_PIPE), None)].into_iter(),
         );
 
         encoder.end()?;
@@ -268,9 +489,9 @@ impl CompiledRenderGraph {
 
         swapchain.present(device.present_queue(), image_index)?;
 
-        Ok(RetiredRenderGraph {
+        Ok((RetiredRenderGraph {
             resources: self.resources,
-        })
+        }, pending_capture))
     }
 
     fn record_nodes(
@@ -278,23 +499,27 @@ impl CompiledRenderGraph {
         device: &RenderDevice,
         encoder: &CommandEncoder,
         nodes: impl IntoIterator<Item = RenderGraphNode>,
+        mut profiler: Option<&mut GpuProfiler>,
     ) {
         for node in nodes {
             let transition_resources = |reflection| {
                 profiling::scope!("rendergraph::barriers");
+                #[cfg(debug_assertions)]
+                utility::check_write_after_read(&self.resources, &node.name, &node.inputs, &node.outputs);
+
                 let output_iter = node.outputs.iter()
-                    .map(|res| (res.id, res.access, res.stage_hint));
+                    .map(|res| (res.id, res.access, res.stage_hint, res.slice));
 
                 Self::transition_resources(
                     device, encoder, reflection, &self.resources,
                     node.inputs.iter()
-                        .map(|res| (res.id, res.access, res.stage_hint))
+                        .map(|res| (res.id, res.access, res.stage_hint, res.slice))
                         .chain(output_iter),
                 );
             };
 
             match node.pipeline_state {
-                NodePipelineState::Graphic { pipeline_desc, color_attachments, depth_attachment, mut job_functor } => {
+                NodePipelineState::Graphic { descriptor, pipeline_desc, resolve_targets, mut job_functor } => {
                     transition_resources(pipeline_desc.as_ref().map(|desc| &desc.shader.merged_reflection));
 
                     let pipeline_desc = pipeline_desc.as_ref();
@@ -302,10 +527,15 @@ impl CompiledRenderGraph {
                     let pipeline = self.graphic_pipelines.get(self.graphic_pipe_index as usize).unwrap();
                     let pipeline = pipeline.as_ref().map(|pipe| pipe.as_ref());
 
-                    let color_attachment_ids: SmallVec<[GraphResourceId; 8]> =
-                        color_attachments.iter().map(|(id, _)| *id).collect();
-                    let depth_attachment_id: Option<GraphResourceId> =
-                        depth_attachment.as_ref().map(|(id, _)| *id);
+                    let output_slice = |id: GraphResourceId| {
+                        node.outputs.iter().find(|output| output.id == id).and_then(|output| output.slice)
+                    };
+                    let color_attachment_ids: SmallVec<[(GraphResourceId, Option<TextureSlice>); 8]> =
+                        descriptor.color_attachments.iter().map(|(id, _)| (*id, output_slice(*id))).collect();
+                    let depth_attachment_id: Option<(GraphResourceId, Option<TextureSlice>)> =
+                        descriptor.depth_attachment.as_ref().map(|(id, _)| (*id, output_slice(*id)));
+                    let resolve_targets: SmallVec<[(ResolveTarget, Option<TextureSlice>); 2]> =
+                        resolve_targets.iter().map(|resolve| (*resolve, output_slice(resolve.resolved))).collect();
                     self.graphic_pipe_index += 1;
 
                     if let Some(record) = job_functor.take() {
@@ -323,8 +553,16 @@ impl CompiledRenderGraph {
                             encoder,
                             color_attachment_ids,
                             depth_attachment_id,
+                            resolve_targets,
                         };
+
+                        let frame_index = device.current_frame_index();
+                        let end_query = profiler.as_deref_mut()
+                            .and_then(|profiler| profiler.begin_node(encoder, frame_index, &name));
                         record(&mut ctx).expect("Failed to record graphic node.");
+                        if let (Some(profiler), Some(query)) = (profiler.as_deref(), end_query) {
+                            profiler.end_node(encoder, frame_index, query);
+                        }
                     } else {
                         log::warn!("Missing job of graphic node {}!", name);
                     }
@@ -352,7 +590,7 @@ impl CompiledRenderGraph {
         encoder: &CommandEncoder,
         merged_reflection: Option<&ShaderReflection>,
         resource_storage: &Vec<ResourceStorage>,
-        resources_to_transition: impl Iterator<Item = (GraphResourceId, ResourceState, Option<vk::PipelineStageFlags2>)>,
+        resources_to_transition: impl Iterator<Item = (GraphResourceId, ResourceState, Option<vk::PipelineStageFlags2>, Option<TextureSlice>)>,
     ) {
         let mut image_barriers: Vec<TextureBarrier> = Vec::new();
         let mut buffer_barriers: Vec<BufferBarrier> = Vec::new();
@@ -368,7 +606,7 @@ impl CompiledRenderGraph {
             .map(shader_stage_to_pipeline_stage)
             .unwrap_or(vk::PipelineStageFlags2::ALL_COMMANDS);
 
-        for (id, access, stage_hint) in resources_to_transition {
+        for (id, access, stage_hint, slice) in resources_to_transition {
             let storage = utility::resource_storage_ref(resource_storage, id);
 
             match storage {
@@ -422,63 +660,60 @@ impl CompiledRenderGraph {
                 }
                 ResourceStorage::ManagedTexture { resource, state_tracker, .. } => {
                     let ResourceState::Texture(next_state) = access else { continue; };
-                    let prev_state = state_tracker.current_access();
-                    if prev_state == next_state { continue; }
 
                     let dst_stage_vk = stage_hint.unwrap_or(combined_shader_stage);
-                    let src_stage = PipelineStages::from_vk(state_tracker.current_stage());
+                    let transitions = state_tracker.transition_slice(slice, next_state, next_state.into_pipeline_stage(dst_stage_vk));
+                    if transitions.is_empty() { continue; }
+
                     let dst_stage = PipelineStages::from_vk(dst_stage_vk);
                     if dst_stage_vk == vk::PipelineStageFlags2::ALL_COMMANDS {
                         log::warn!("Render graph texture resource [{}] may cause serve pipeline stall due to unknown pipeline stage usage. Use read_hint() or write_hint() to get better performance.", resource.name())
                     }
 
-                    image_barriers.push(TextureBarrier::new(
-                        resource.as_range(.., ..).unwrap(),
-                        prev_state,
-                        next_state,
-                        src_stage,
-                        dst_stage,
-                        queue,
-                        queue,
-                        false,
-                        prev_state == TextureState::Undefined,
-                    ));
-                    state_tracker.transition_to(next_state, next_state.into_pipeline_stage(dst_stage_vk));
+                    for (mips, layers, prev_state, prev_stage) in transitions {
+                        image_barriers.push(TextureBarrier::new(
+                            resource.as_range(mips, layers).unwrap(),
+                            prev_state,
+                            next_state,
+                            PipelineStages::from_vk(prev_stage),
+                            dst_stage,
+                            queue,
+                            queue,
+                            false,
+                            prev_state == TextureState::Undefined,
+                        ));
+                    }
                 }
                 ResourceStorage::ImportedTexture { resource, state_tracker } => {
                     let ResourceState::Texture(next_state) = access else { continue; };
-                    let prev_state = state_tracker.current_access();
-                    if prev_state == next_state { continue; }
 
                     let dst_stage_vk = stage_hint.unwrap_or(combined_shader_stage);
-                    let src_stage = PipelineStages::from_vk(state_tracker.current_stage());
+                    let transitions = state_tracker.transition_slice(slice, next_state, next_state.into_pipeline_stage(dst_stage_vk));
+                    if transitions.is_empty() { continue; }
+
                     let dst_stage = PipelineStages::from_vk(dst_stage_vk);
                     if dst_stage_vk == vk::PipelineStageFlags2::ALL_COMMANDS {
                         log::warn!("Render graph texture resource [{}] may cause serve pipeline stall due to unknown pipeline stage usage. Use read_hint() or write_hint() to get better performance.", resource.name())
                     }
 
-                    image_barriers.push(TextureBarrier::new(
-                        resource.as_range(.., ..).unwrap(),
-                        prev_state,
-                        next_state,
-                        src_stage,
-                        dst_stage,
-                        queue,
-                        queue,
-                        false,
-                        prev_state == TextureState::Undefined,
-                    ));
-                    state_tracker.transition_to(next_state, next_state.into_pipeline_stage(dst_stage_vk));
+                    for (mips, layers, prev_state, prev_stage) in transitions {
+                        image_barriers.push(TextureBarrier::new(
+                            resource.as_range(mips, layers).unwrap(),
+                            prev_state,
+                            next_state,
+                            PipelineStages::from_vk(prev_stage),
+                            dst_stage,
+                            queue,
+                            queue,
+                            false,
+                            prev_state == TextureState::Undefined,
+                        ));
+                    }
                 }
             }
         }
 
-        if !image_barriers.is_empty() {
-            encoder.texture_barriers(&image_barriers);
-        }
-        if !buffer_barriers.is_empty() {
-            encoder.buffer_barriers(&buffer_barriers);
-        }
+        encoder.pipeline_barrier(&[], &buffer_barriers, &image_barriers);
     }
 }
 
@@ -512,15 +747,16 @@ pub struct GraphicNodeExecutionContext<'node> {
     resources: &'node Vec<ResourceStorage>,
     pipeline: Option<&'node GraphicPipeline>,
     encoder: &'node CommandEncoder<'node>,
-    color_attachment_ids: SmallVec<[GraphResourceId; 8]>,
-    depth_attachment_id: Option<GraphResourceId>,
+    color_attachment_ids: SmallVec<[(GraphResourceId, Option<TextureSlice>); 8]>,
+    depth_attachment_id: Option<(GraphResourceId, Option<TextureSlice>)>,
+    /// Resolve targets and the slice written to them, keyed by the multisampled attachment.
+    resolve_targets: SmallVec<[(ResolveTarget, Option<TextureSlice>); 2]>,
 }
 
 impl<'node> GraphicNodeExecutionContext<'node> {
     #[inline]
     pub fn get<R: GraphResource, V: GraphResourceView>(&self, resource: &RenderGraphResourceAccess<R, V>) -> &R {
-        let storage = self.resources.get(resource.id as usize)
-            .expect("Graph resource index out of bound!");
+        let storage = utility::resource_storage_ref(self.resources, resource.id);
         R::from_storage(storage)
     }
 
@@ -536,6 +772,27 @@ impl<'node> GraphicNodeExecutionContext<'node> {
     #[inline]
     pub fn encoder(&self) -> &CommandEncoder<'node> { self.encoder }
 
+    /// Push constants to the node's pipeline, checked against the reflected push constant range.
+    ///
+    /// Panics if the node has no pipeline or the bytes fall outside the reflected range.
+    pub fn push_constants<T: NoUninit>(&self, stages: vk::ShaderStageFlags, offset: u32, data: &T) {
+        let Some(pipeline) = self.pipeline else {
+            panic!("push_constants: node has no graphic pipeline to push constants to");
+        };
+
+        let shader = &self.pipeline_desc.shader;
+        let end = offset as usize + std::mem::size_of::<T>();
+        let range_end = (shader.push_constant_offset + shader.push_constant_size) as usize;
+        if offset < shader.push_constant_offset || end > range_end {
+            panic!(
+                "push_constants: bytes {offset}..{end} are outside the reflected push constant range {}..{range_end}",
+                shader.push_constant_offset,
+            );
+        }
+
+        self.encoder.push_constants(pipeline.layout(), stages, offset, data);
+    }
+
     pub fn begin_rendering(&self, extent: vk::Extent2D) {
         let color_infos = &self.pipeline_desc.state.color_blend.attachments;
         if self.color_attachment_ids.len() != color_infos.len() {
@@ -550,10 +807,16 @@ impl<'node> GraphicNodeExecutionContext<'node> {
             .color_attachment_ids
             .iter()
             .zip(color_infos.iter())
-            .map(|(id, info)| {
+            .map(|((id, slice), info)| {
                 let texture = utility::resource_storage_ref(self.resources, *id).as_texture();
-                vk::RenderingAttachmentInfo::default()
-                    .image_view(texture.as_range(.., ..).unwrap().view().expect("Texture view not created"))
+                debug_assert_eq!(
+                    texture.desc().samples,
+                    self.pipeline_desc.state.multisample.samples,
+                    "begin_rendering: sample count of '{}' does not match the pipeline multisample state",
+                    texture.name()
+                );
+                let attachment = vk::RenderingAttachmentInfo::default()
+                    .image_view(utility::attachment_view(texture, *slice))
                     .image_layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
                     .load_op(info.load_op)
                     .store_op(info.store_op)
@@ -561,7 +824,8 @@ impl<'node> GraphicNodeExecutionContext<'node> {
                         color: vk::ClearColorValue {
                             float32: info.clear_value,
                         },
-                    })
+                    });
+                self.with_resolve(attachment, *id, vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
             })
             .collect();
 
@@ -569,28 +833,38 @@ impl<'node> GraphicNodeExecutionContext<'node> {
             self.depth_attachment_id,
             self.pipeline_desc.state.depth_stencil.as_ref(),
         ) {
-            (Some(id), Some(info)) => {
+            (Some((id, slice)), Some(info)) => {
                 let texture = utility::resource_storage_ref(self.resources, id).as_texture();
-                Some(
-                    vk::RenderingAttachmentInfo::default()
-                        .image_view(texture.as_range(.., ..).unwrap().view().expect("Texture view not created"))
-                        .image_layout(vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL)
-                        .load_op(info.depth_load_op)
-                        .store_op(info.depth_store_op)
-                        .clear_value(vk::ClearValue {
-                            depth_stencil: vk::ClearDepthStencilValue {
-                                depth: info.depth_clear_value,
-                                stencil: info.stencil_clear_value,
-                            },
-                        }),
-                )
+                let attachment = vk::RenderingAttachmentInfo::default()
+                    .image_view(utility::attachment_view(texture, slice))
+                    .image_layout(vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL)
+                    .load_op(info.depth_load_op)
+                    .store_op(info.depth_store_op)
+                    .clear_value(vk::ClearValue {
+                        depth_stencil: vk::ClearDepthStencilValue {
+                            depth: info.depth_clear_value,
+                            stencil: info.stencil_clear_value,
+                        },
+                    });
+                Some(self.with_resolve(attachment, id, vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL))
             }
             _ => None,
         };
 
+        // Layered rendering into a slice covers all of its layers.
+        let layer_count = self.color_attachment_ids
+            .iter()
+            .map(|(_, slice)| *slice)
+            .chain(self.depth_attachment_id.map(|(_, slice)| slice))
+            .flatten()
+            .map(|slice| slice.layer_count)
+            .min()
+            .unwrap_or(1);
+
         let mut rendering_info = vk::RenderingInfo::default()
             .render_area(vk::Rect2D { offset: vk::Offset2D { x: 0, y: 0 }, extent })
-            .layer_count(1)
+            .layer_count(layer_count)
+            .view_mask(self.pipeline_desc.attachments.view_mask)
             .color_attachments(&color_attachments);
 
         if let Some(ref depth) = depth_attachment {
@@ -604,6 +878,44 @@ impl<'node> GraphicNodeExecutionContext<'node> {
         self.encoder.end_rendering();
     }
 
+    /// Bind the whole graph buffer `vertex` to vertex binding 0.
+    #[inline]
+    pub fn bind_vertex_buffer<V: GraphResourceView>(&self, vertex: &RenderGraphResourceAccess<Buffer, V>) {
+        self.bind_vertex_buffer_at(0, vertex);
+    }
+
+    /// Bind the whole graph buffer `vertex` to vertex `binding`, e.g. a per-instance stream.
+    #[inline]
+    pub fn bind_vertex_buffer_at<V: GraphResourceView>(&self, binding: u32, vertex: &RenderGraphResourceAccess<Buffer, V>) {
+        self.encoder.bind_vertex_buffers(binding, &[self.get(vertex).handle()], &[0]);
+    }
+
+    #[inline]
+    pub fn bind_index_buffer<V: GraphResourceView>(&self, index: &RenderGraphResourceAccess<Buffer, V>, index_type: vk::IndexType) {
+        self.encoder.bind_index_buffer(self.get(index).handle(), 0, index_type);
+    }
+
+    /// Draw `index_count` indices of the bound index buffer as a single instance.
+    /// Must be recorded between `begin_rendering` and `end_rendering`.
+    #[inline]
+    pub fn draw_indexed(&self, index_count: u32) {
+        self.encoder.draw_indexed(index_count, 1, 0, 0, 0);
+    }
+
+    /// Add the resolve target declared with `write_resolve` for attachment `id`, if any.
+    fn with_resolve<'a>(&self, attachment: vk::RenderingAttachmentInfo<'a>, id: GraphResourceId, layout: vk::ImageLayout) -> vk::RenderingAttachmentInfo<'a> {
+        match self.resolve_targets.iter().find(|(resolve, _)| resolve.msaa == id) {
+            Some((resolve, slice)) => {
+                let texture = utility::resource_storage_ref(self.resources, resolve.resolved).as_texture();
+                attachment
+                    .resolve_mode(resolve.mode)
+                    .resolve_image_view(utility::attachment_view(texture, *slice))
+                    .resolve_image_layout(layout)
+            }
+            None => attachment,
+        }
+    }
+
     /// Create a shader resource binder for this node's pipeline.
     /// Returns None if the pipeline has no descriptor bindings.
     pub fn create_binder(&self) -> DescriptorSetBinder<'_> {
@@ -619,9 +931,9 @@ impl<'node> GraphicNodeExecutionContext<'node> {
         let (pool, sets) = binder.finish();
         if let Some(pipeline) = self.pipeline {
             if !sets.is_empty() {
-                self.encoder.bind_descriptor_sets(
+                self.encoder.bind_pipeline_descriptor_sets(
                     vk::PipelineBindPoint::GRAPHICS,
-                    pipeline.layout(),
+                    pipeline,
                     0,
                     &sets,
                     &[],
@@ -632,6 +944,10 @@ impl<'node> GraphicNodeExecutionContext<'node> {
     }
 }
 
+/// Execution context of a lambda node, recording arbitrary commands such as custom blits or debug draws.
+///
+/// The resources declared with `read`/`write` on the node builder are transitioned to their declared
+/// states before the closure runs, the same as for graphic nodes, so commands can use them as they are.
 pub struct LambdaNodeExecutionContext<'node> {
     device: &'node RenderDevice,
     resources: &'node Vec<ResourceStorage>,
@@ -641,16 +957,29 @@ pub struct LambdaNodeExecutionContext<'node> {
 impl<'node> LambdaNodeExecutionContext<'node> {
     #[inline]
     pub fn get<R: GraphResource, V: GraphResourceView>(&self, resource: &RenderGraphResourceAccess<R, V>) -> &R {
-        let storage = self.resources.get(resource.id as usize)
-            .expect("Graph resource index out of bound!");
+        let storage = utility::resource_storage_ref(self.resources, resource.id);
         R::from_storage(storage)
     }
 
+    #[inline]
+    pub fn get_texture<V: GraphResourceView>(&self, resource: &RenderGraphResourceAccess<Texture, V>) -> &Texture {
+        self.get(resource)
+    }
+
+    #[inline]
+    pub fn get_buffer<V: GraphResourceView>(&self, resource: &RenderGraphResourceAccess<Buffer, V>) -> &Buffer {
+        self.get(resource)
+    }
+
     #[inline]
     pub fn device(&self) -> &RenderDevice { self.device }
 
     #[inline]
-    pub fn command_encoder(&self) -> &CommandEncoder<'node> { self.encoder }
+    pub fn encoder(&self) -> &CommandEncoder<'node> { self.encoder }
+
+    #[inline]
+    #[deprecated(note = "use `encoder()` to match the other node execution contexts")]
+    pub fn command_encoder(&self) -> &CommandEncoder<'node> { self.encoder() }
 }
 
 pub struct RetiredRenderGraph {
@@ -675,10 +1004,153 @@ impl RetiredRenderGraph {
 
 pub(crate) mod utility {
     use super::ResourceStorage;
-    use crate::resource::GraphResourceId;
+    use crate::interface::ResourceState;
+    #[cfg(debug_assertions)]
+    use crate::builder::ResourceAccessStorage;
+    use crate::node::RenderGraphNode;
+    use crate::resource::{GraphResourceId, TextureSlice};
+    use zenith_rhi::RenderDevice;
+    use std::fmt::Write;
+
+    /// DOT graph with a box per node and an ellipse per (name, imported) resource.
+    /// Reads are edges from resource to node, writes from node to resource, labeled with the state.
+    pub(crate) fn to_dot<'a>(nodes: impl Iterator<Item = &'a RenderGraphNode>, resources: &[(&str, bool)]) -> String {
+        let state_label = |state: ResourceState| match state {
+            ResourceState::Buffer(state) => format!("{:?}", state),
+            ResourceState::Texture(state) => format!("{:?}", state),
+        };
+
+        let mut dot = String::from("digraph render_graph {\n    rankdir=LR;\n");
+        for (id, (name, imported)) in resources.iter().enumerate() {
+            let color = if *imported { "orange" } else { "lightblue" };
+            writeln!(dot, "    r{id} [label=\"{}\", shape=ellipse, style=filled, fillcolor={color}];", name.escape_default()).unwrap();
+        }
+        for (index, node) in nodes.enumerate() {
+            writeln!(dot, "    n{index} [label=\"{}\", shape=box];", node.name.escape_default()).unwrap();
+            for input in &node.inputs {
+                writeln!(dot, "    r{} -> n{index} [label=\"{}\"];", input.id, state_label(input.access)).unwrap();
+            }
+            for output in &node.outputs {
+                writeln!(dot, "    n{index} -> r{} [label=\"{}\"];", output.id, state_label(output.access)).unwrap();
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// Warn when node `node_name` writes a resource an earlier node reads, and the states match so no
+    /// barrier separates the two accesses. Must run before the node's transitions are applied.
+    #[cfg(debug_assertions)]
+    pub(crate) fn check_write_after_read(
+        storage: &Vec<ResourceStorage>,
+        node_name: &str,
+        inputs: &[ResourceAccessStorage],
+        outputs: &[ResourceAccessStorage],
+    ) {
+        for output in outputs {
+            let (name, current_access, last_reader) = resource_storage_ref(storage, output.id).hazard_state();
+            if let Some(reader) = last_reader.borrow().as_deref() {
+                if reader != node_name && current_access == output.access {
+                    log::warn!(
+                        "Write-after-read hazard on render graph resource [{}]: node '{}' writes it while node '{}' reads it as {:?} without a barrier in between.",
+                        name, node_name, reader, current_access
+                    );
+                }
+            }
+        }
+
+        for input in inputs {
+            let (_, _, last_reader) = resource_storage_ref(storage, input.id).hazard_state();
+            *last_reader.borrow_mut() = Some(node_name.to_owned());
+        }
+        for output in outputs {
+            let (_, _, last_reader) = resource_storage_ref(storage, output.id).hazard_state();
+            *last_reader.borrow_mut() = None;
+        }
+    }
 
     #[inline]
     pub(crate) fn resource_storage_ref(storage: &Vec<ResourceStorage>, id: GraphResourceId) -> &ResourceStorage {
         storage.get(id as usize).expect("Graph resource id out of bound!")
     }
+
+    /// View of a texture (or a slice of it) bound as color/depth attachment.
+    pub(crate) fn attachment_view(texture: &zenith_rhi::Texture, slice: Option<TextureSlice>) -> zenith_rhi::vk::ImageView {
+        let range = match slice {
+            Some(slice) => texture.as_range(slice.mip..slice.mip + 1, slice.base_layer..slice.base_layer + slice.layer_count),
+            None => texture.as_range(.., ..),
+        };
+        range
+            .expect("Attachment slice out of texture range")
+            .view()
+            .expect("Texture view not created")
+    }
+
+    /// Validate a resolve target declared by `node_name` against the pipeline multisample state.
+    pub(crate) fn check_resolve_target(
+        storage: &Vec<ResourceStorage>,
+        node_name: &str,
+        resolve: &crate::node::ResolveTarget,
+        is_attachment: bool,
+        pipeline_samples: zenith_rhi::vk::SampleCountFlags,
+    ) {
+        use zenith_rhi::vk::SampleCountFlags;
+
+        let msaa = resource_storage_ref(storage, resolve.msaa).as_texture();
+        let resolved = resource_storage_ref(storage, resolve.resolved).as_texture();
+        if !is_attachment {
+            panic!(
+                "Render graph node [{}] resolves [{}] which is not bound as a color or depth attachment.",
+                node_name, msaa.name()
+            );
+        }
+        if msaa.desc().samples != pipeline_samples || msaa.desc().samples == SampleCountFlags::TYPE_1 {
+            panic!(
+                "Render graph node [{}] resolves [{}] with {:?} samples, but the pipeline uses {:?} and resolving needs more than one.",
+                node_name, msaa.name(), msaa.desc().samples, pipeline_samples
+            );
+        }
+        if resolved.desc().samples != SampleCountFlags::TYPE_1 {
+            panic!(
+                "Render graph node [{}] resolves into [{}] which is multisampled.",
+                node_name, resolved.name()
+            );
+        }
+        if msaa.format() != resolved.format() {
+            panic!(
+                "Render graph node [{}] resolves [{}] ({:?}) into [{}] of a different format ({:?}).",
+                node_name, msaa.name(), msaa.format(), resolved.name(), resolved.format()
+            );
+        }
+    }
+
+    /// Check the device can resolve the depth attachment `resolve.msaa` with `resolve.mode`.
+    pub(crate) fn check_depth_resolve_mode(
+        device: &RenderDevice,
+        storage: &Vec<ResourceStorage>,
+        node_name: &str,
+        resolve: &crate::node::ResolveTarget,
+    ) {
+        let msaa = resource_storage_ref(storage, resolve.msaa).as_texture();
+        let support = device.parent_physical_device().depth_stencil_resolve();
+        if !support.supports_depth_resolve(resolve.mode, msaa.format()) {
+            panic!(
+                "Render graph node [{}] resolves depth [{}] ({:?}) with {:?}, which the device does not support ({:?}).",
+                node_name, msaa.name(), msaa.format(), resolve.mode, support
+            );
+        }
+    }
+
+    /// Format of a texture bound as color/depth attachment of `node_name`.
+    pub(crate) fn attachment_format(storage: &Vec<ResourceStorage>, id: GraphResourceId, node_name: &str) -> zenith_rhi::vk::Format {
+        match resource_storage_ref(storage, id) {
+            ResourceStorage::ManagedTexture { resource, .. } => resource.format(),
+            ResourceStorage::ImportedTexture { resource, .. } => resource.format(),
+            buffer => panic!(
+                "Render graph node [{}] binds buffer [{}] as an attachment, only textures can be attachments.",
+                node_name,
+                buffer.as_buffer().name()
+            ),
+        }
+    }
 }
@@ -3,13 +3,14 @@ mod node;
 mod graph;
 mod interface;
 mod resource;
+mod profiler;
 
 pub use resource::{
-    RenderGraphResource, RenderGraphResourceAccess
+    RenderGraphResource, RenderGraphResourceAccess, TextureSlice,
 };
 pub use builder::{RenderGraphBuilder, GraphicNodeBuilder};
 pub use zenith_rhi::{
-    ColorAttachmentDesc, ColorAttachmentDescBuilder, ColorAttachmentDescBuilderError,
+    ColorAttachmentDesc, ColorAttachmentDescBuilder, ColorAttachmentDescBuilderError, ColorInfo,
     DepthStencilDesc, DepthStencilDescBuilder, DepthStencilDescBuilderError,
     GraphicPipelineDesc, GraphicShaderInput, GraphicPipelineState,
     GraphicShaderInputBuilder, GraphicShaderInputBuildError,
@@ -23,3 +24,4 @@ pub use graph::{
     RenderGraph, CompiledRenderGraph, RetiredRenderGraph,
     GraphicNodeExecutionContext, LambdaNodeExecutionContext,
 };
+pub use profiler::{FrameTimings, GpuProfiler};
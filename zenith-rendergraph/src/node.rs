@@ -1,4 +1,4 @@
-use zenith_rhi::{ColorAttachmentDesc, DepthStencilDesc, GraphicPipelineDesc};
+use zenith_rhi::{vk, ColorInfo, DepthStencilDesc, GraphicPipelineDesc, GraphicPipelineState, GraphicShaderInput};
 use crate::resource::GraphResourceId;
 use crate::graph::{GraphicNodeExecutionContext, LambdaNodeExecutionContext};
 use crate::builder::ResourceAccessStorage;
@@ -14,11 +14,24 @@ impl ComputePipelineDescriptor {
     }
 }
 
+/// Pipeline of a graphic node as set up by its builder, with attachments still referring to graph resources.
+///
+/// Turned into the RHI [`GraphicPipelineDesc`] by [`crate::builder::graphic_pipeline_desc`] when the graph is compiled.
+#[derive(Default)]
+pub(crate) struct GraphicPipelineDescriptor {
+    pub(crate) shader: Option<GraphicShaderInput>,
+    pub(crate) state: GraphicPipelineState,
+    /// Color targets in attachment order.
+    pub(crate) color_attachments: Vec<(GraphResourceId, ColorInfo)>,
+    pub(crate) depth_attachment: Option<(GraphResourceId, DepthStencilDesc)>,
+}
+
 pub(crate) enum NodePipelineState {
     Graphic {
+        descriptor: GraphicPipelineDescriptor,
+        /// Resolved from `descriptor` when the graph is compiled.
         pipeline_desc: Option<GraphicPipelineDesc>,
-        color_attachments: Vec<(GraphResourceId, ColorAttachmentDesc)>,
-        depth_attachment: Option<(GraphResourceId, DepthStencilDesc)>,
+        resolve_targets: Vec<ResolveTarget>,
         job_functor: Option<Box<dyn FnOnce(&mut GraphicNodeExecutionContext) -> anyhow::Result<()>>>,
     },
     #[allow(dead_code)]
@@ -31,10 +44,18 @@ pub(crate) enum NodePipelineState {
     }
 }
 
+/// Multisampled attachment resolved into a single-sampled texture at the end of rendering.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ResolveTarget {
+    pub(crate) msaa: GraphResourceId,
+    pub(crate) resolved: GraphResourceId,
+    pub(crate) mode: vk::ResolveModeFlags,
+}
+
 impl NodePipelineState {
     pub(crate) fn valid(&self) -> bool {
         match self {
-            NodePipelineState::Graphic { pipeline_desc, job_functor, .. } => pipeline_desc.is_some() && job_functor.is_some(),
+            NodePipelineState::Graphic { descriptor, job_functor, .. } => descriptor.shader.is_some() && job_functor.is_some(),
             NodePipelineState::Compute { .. } => {
                 false
             }
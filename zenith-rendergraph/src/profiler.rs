@@ -0,0 +1,89 @@
+//! Per-node GPU timing of the render graph.
+
+use zenith_rhi::{vk, CommandEncoder, QueryPool, RenderDevice};
+
+/// GPU time of every timed node of a frame.
+#[derive(Debug, Clone, Default)]
+pub struct FrameTimings {
+    /// Node name and GPU time in milliseconds, in execution order.
+    pub per_node: Vec<(String, f64)>,
+}
+
+/// Timestamp queries of a single frame in flight.
+struct ProfiledFrame {
+    pool: QueryPool,
+    node_names: Vec<String>,
+}
+
+/// Brackets graphic nodes with timestamps, see [`crate::CompiledRenderGraph::execute_profiled`] and
+/// [`crate::CompiledRenderGraph::present_profiled`].
+///
+/// Keeps a query pool per frame in flight, so results of a frame are read back when its frame slot
+/// is reused and the GPU is done with it, without stalling.
+pub struct GpuProfiler {
+    frames: Vec<ProfiledFrame>,
+    max_nodes: u32,
+    timestamp_period: f64,
+}
+
+impl GpuProfiler {
+    pub fn new(device: &RenderDevice, max_nodes: u32) -> Result<Self, vk::Result> {
+        let frames = (0..device.num_frames())
+            .map(|index| {
+                Ok(ProfiledFrame {
+                    pool: QueryPool::new_timestamp(&format!("query_pool.rendergraph.{index}"), device, max_nodes * 2)?,
+                    node_names: Vec::new(),
+                })
+            })
+            .collect::<Result<Vec<_>, vk::Result>>()?;
+
+        Ok(Self {
+            frames,
+            max_nodes,
+            timestamp_period: device.timestamp_period() as f64,
+        })
+    }
+
+    /// Read back timings last recorded in `frame_index` and reset its queries for recording.
+    pub(crate) fn begin_frame(&mut self, encoder: &CommandEncoder, frame_index: usize) -> anyhow::Result<Option<FrameTimings>> {
+        let timestamp_period = self.timestamp_period;
+        let frame = &mut self.frames[frame_index];
+
+        let mut timings = None;
+        if !frame.node_names.is_empty() {
+            let num_queries = frame.node_names.len() as u32 * 2;
+            if let Some(timestamps) = frame.pool.timestamps(0, num_queries)? {
+                let per_node = frame.node_names
+                    .drain(..)
+                    .zip(timestamps.chunks_exact(2))
+                    .map(|(name, ticks)| {
+                        let nanos = ticks[1].wrapping_sub(ticks[0]) as f64 * timestamp_period;
+                        (name, nanos / 1_000_000.0)
+                    })
+                    .collect();
+                timings = Some(FrameTimings { per_node });
+            }
+            frame.node_names.clear();
+        }
+
+        encoder.reset_query_pool(&frame.pool, 0, self.max_nodes * 2);
+        Ok(timings)
+    }
+
+    /// Write the start timestamp of `node_name`, returns the query to end it with, if any is left.
+    pub(crate) fn begin_node(&mut self, encoder: &CommandEncoder, frame_index: usize, node_name: &str) -> Option<u32> {
+        let frame = &mut self.frames[frame_index];
+        let node_index = frame.node_names.len() as u32;
+        if node_index >= self.max_nodes {
+            return None;
+        }
+
+        frame.node_names.push(node_name.to_owned());
+        encoder.write_timestamp(&frame.pool, vk::PipelineStageFlags2::ALL_COMMANDS, node_index * 2);
+        Some(node_index * 2 + 1)
+    }
+
+    pub(crate) fn end_node(&self, encoder: &CommandEncoder, frame_index: usize, query: u32) {
+        encoder.write_timestamp(&self.frames[frame_index].pool, vk::PipelineStageFlags2::ALL_COMMANDS, query);
+    }
+}
@@ -60,10 +60,31 @@ impl<R: GraphResource> RenderGraphResource<R> {
     }
 }
 
+/// A single mip level and a range of array layers of a texture, e.g. one cubemap face or shadow cascade.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TextureSlice {
+    pub mip: u32,
+    pub base_layer: u32,
+    pub layer_count: u32,
+}
+
+impl TextureSlice {
+    pub fn new(mip: u32, base_layer: u32, layer_count: u32) -> Self {
+        Self { mip, base_layer, layer_count }
+    }
+
+    /// A single array layer of a single mip level.
+    pub fn layer(mip: u32, layer: u32) -> Self {
+        Self::new(mip, layer, 1)
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct RenderGraphResourceAccess<R: GraphResource, V: GraphResourceView> {
     pub(crate) id: GraphResourceId,
     pub(crate) access: ResourceState,
+    /// Accessed texture slice, the whole resource if `None`.
+    pub(crate) slice: Option<TextureSlice>,
     pub(crate) _marker: PhantomData<(R, V)>,
 }
 
@@ -79,6 +100,7 @@ impl<R: GraphResource, V: GraphResourceView> RenderGraphResourceAccess<R, V> {
             id: self.id,
             access: self.access,
             stage_hint: None,
+            slice: self.slice,
         }
     }
 
@@ -87,6 +109,7 @@ impl<R: GraphResource, V: GraphResourceView> RenderGraphResourceAccess<R, V> {
             id: self.id,
             access: self.access,
             stage_hint: Some(stage_hint),
+            slice: self.slice,
         }
     }
 }
@@ -57,7 +57,11 @@ pub fn DeviceObject(_attr: TokenStream, item: TokenStream) -> TokenStream {
     expanded.into()
 }
 
-#[proc_macro_derive(VertexLayout)]
+/// Derive `VertexLayout` for a `#[repr(C)]` struct, one attribute per field in declaration order.
+///
+/// Field formats are inferred from `f32`/`u32`/`i32` scalars and `[T; 2..=4]` arrays. A `u32` field can
+/// opt into a packed 32-bit format instead, e.g. `#[vertex(format = "a2b10g10r10_snorm")]` for normals.
+#[proc_macro_derive(VertexLayout, attributes(vertex))]
 pub fn derive_vertex_layout(input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(input as DeriveInput);
 
@@ -104,8 +108,12 @@ pub fn derive_vertex_layout(input: TokenStream) -> TokenStream {
             }
         };
 
-        let fmt = match vk_format_for_type(&field.ty) {
-            Ok(ts) => ts,
+        let fmt = match format_override(field) {
+            Ok(Some(ts)) => ts,
+            Ok(None) => match vk_format_for_type(&field.ty) {
+                Ok(ts) => ts,
+                Err(e) => return e.to_compile_error().into(),
+            },
             Err(e) => return e.to_compile_error().into(),
         };
 
@@ -156,6 +164,56 @@ fn has_repr_c(attrs: &[syn::Attribute]) -> bool {
     false
 }
 
+/// Packed formats a `u32` field can be overridden with, by `#[vertex(format = "...")]` name.
+const PACKED_FORMAT_OVERRIDES: [(&str, &str); 8] = [
+    ("a2b10g10r10_snorm", "A2B10G10R10_SNORM_PACK32"),
+    ("a2b10g10r10_unorm", "A2B10G10R10_UNORM_PACK32"),
+    ("a2r10g10b10_snorm", "A2R10G10B10_SNORM_PACK32"),
+    ("a2r10g10b10_unorm", "A2R10G10B10_UNORM_PACK32"),
+    ("r8g8b8a8_snorm", "R8G8B8A8_SNORM"),
+    ("r8g8b8a8_unorm", "R8G8B8A8_UNORM"),
+    ("r16g16_snorm", "R16G16_SNORM"),
+    ("r16g16_unorm", "R16G16_UNORM"),
+];
+
+/// Parse `#[vertex(format = "...")]` on a field, if present.
+fn format_override(field: &syn::Field) -> Result<Option<proc_macro2::TokenStream>, syn::Error> {
+    let Some(attr) = field.attrs.iter().find(|attr| attr.path().is_ident("vertex")) else {
+        return Ok(None);
+    };
+
+    let mut format = None;
+    attr.parse_nested_meta(|meta| {
+        if meta.path.is_ident("format") {
+            let lit: syn::LitStr = meta.value()?.parse()?;
+            format = Some(lit);
+            Ok(())
+        } else {
+            Err(meta.error("unsupported vertex attribute, expected `format = \"...\"`"))
+        }
+    })?;
+    let Some(format) = format else {
+        return Err(syn::Error::new(attr.span(), "expected `#[vertex(format = \"...\")]`"));
+    };
+
+    let is_u32 = matches!(&field.ty, Type::Path(TypePath { path, .. }) if path.is_ident("u32"));
+    if !is_u32 {
+        return Err(syn::Error::new(field.ty.span(), "vertex format overrides require a `u32` field"));
+    }
+
+    let name = format.value();
+    let Some((_, variant)) = PACKED_FORMAT_OVERRIDES.iter().find(|(key, _)| *key == name) else {
+        let supported = PACKED_FORMAT_OVERRIDES.iter().map(|(key, _)| *key).collect::<Vec<_>>().join(", ");
+        return Err(syn::Error::new(
+            format.span(),
+            format!("unsupported vertex format override `{name}` (supported: {supported})"),
+        ));
+    };
+
+    let variant = syn::Ident::new(variant, format.span());
+    Ok(Some(quote!(::zenith_rhi::vk::Format::#variant)))
+}
+
 fn vk_format_for_type(ty: &Type) -> Result<proc_macro2::TokenStream, syn::Error> {
     // Arrays like [f32; 3]
     if let Type::Array(TypeArray { elem, len, .. }) = ty {
@@ -194,6 +194,34 @@ impl BufferState {
         }
     }
 
+    /// Whether the buffer is only read in this state.
+    pub fn is_read(self) -> bool {
+        match self {
+            BufferState::TransferSrc |
+            BufferState::Uniform |
+            BufferState::Vertex |
+            BufferState::Index => true,
+            BufferState::Undefined |
+            BufferState::HostWrite |
+            BufferState::TransferDst |
+            BufferState::Storage => false,
+        }
+    }
+
+    /// Usage flag a buffer must have been created with to be accessed in this state, if any.
+    pub fn required_usage(self) -> vk::BufferUsageFlags {
+        match self {
+            BufferState::Undefined |
+            BufferState::HostWrite => vk::BufferUsageFlags::empty(),
+            BufferState::TransferSrc => vk::BufferUsageFlags::TRANSFER_SRC,
+            BufferState::TransferDst => vk::BufferUsageFlags::TRANSFER_DST,
+            BufferState::Uniform => vk::BufferUsageFlags::UNIFORM_BUFFER,
+            BufferState::Storage => vk::BufferUsageFlags::STORAGE_BUFFER,
+            BufferState::Vertex => vk::BufferUsageFlags::VERTEX_BUFFER,
+            BufferState::Index => vk::BufferUsageFlags::INDEX_BUFFER,
+        }
+    }
+
     pub fn into_access_flag(self, is_readonly: bool) -> vk::AccessFlags2 {
         match self {
             BufferState::Undefined => vk::AccessFlags2::NONE,
@@ -209,6 +237,16 @@ impl BufferState {
     }
 }
 
+/// Queue family indices of a barrier. Barriers within a single queue family must not name it,
+/// or they would be treated as an ownership transfer.
+fn queue_family_indices(src_queue: Queue, dst_queue: Queue) -> (u32, u32) {
+    if src_queue.family_index() == dst_queue.family_index() {
+        (vk::QUEUE_FAMILY_IGNORED, vk::QUEUE_FAMILY_IGNORED)
+    } else {
+        (src_queue.family_index(), dst_queue.family_index())
+    }
+}
+
 pub struct BufferBarrier<'a> {
     pub buffer: BufferRange<'a>,
     pub src_state: BufferState,
@@ -217,6 +255,7 @@ pub struct BufferBarrier<'a> {
     pub dst_stage: PipelineStages,
     pub src_queue: Queue,
     pub dst_queue: Queue,
+    /// The destination access only reads the buffer.
     pub readonly: bool,
     pub offset: usize,
     pub size: usize,
@@ -247,6 +286,37 @@ impl<'a> BufferBarrier<'a> {
         }
     }
 
+    /// Same-queue barrier with stages derived from the states.
+    ///
+    /// `Uniform`/`Storage` are accessed from shaders, set their stages with [`Self::with_shader_stages`],
+    /// otherwise all commands are waited on. The destination access is read-only if `dst_state` is a read state.
+    pub fn transition(buffer: BufferRange<'a>, src_state: BufferState, dst_state: BufferState) -> Self {
+        Self::new(
+            buffer,
+            src_state,
+            dst_state,
+            PipelineStage::AllCommands.into(),
+            PipelineStage::AllCommands.into(),
+            Queue::ignored(),
+            Queue::ignored(),
+            dst_state.is_read(),
+        )
+    }
+
+    /// Keep the buffer on `queue`, without a queue family ownership transfer.
+    pub fn same_queue(mut self, queue: Queue) -> Self {
+        self.src_queue = queue;
+        self.dst_queue = queue;
+        self
+    }
+
+    /// Shader stages using the buffer, only used by `Uniform`/`Storage` states.
+    pub fn with_shader_stages(mut self, src_stage: PipelineStages, dst_stage: PipelineStages) -> Self {
+        self.src_stage = src_stage;
+        self.dst_stage = dst_stage;
+        self
+    }
+
     pub fn with_range(mut self, offset: usize, size: usize) -> Self {
         self.offset = offset;
         self.size = size;
@@ -254,17 +324,29 @@ impl<'a> BufferBarrier<'a> {
     }
 
     pub fn to_vk(&self) -> vk::BufferMemoryBarrier2<'a> {
+        debug_assert!(
+            self.buffer.buffer().desc().usage.contains(self.dst_state.required_usage()),
+            "BufferBarrier: buffer '{}' transitions to {:?} but was created without {:?} usage (has {:?})",
+            self.buffer.buffer().name(),
+            self.dst_state,
+            self.dst_state.required_usage(),
+            self.buffer.buffer().desc().usage,
+        );
+
         // Ensure stage mask matches access mask expectations derived from BufferState.
         // This mirrors the old behavior of `buffer_barrier()` which derives stage from state.
         let src_stage_vk = self.src_state.into_pipeline_stage(self.src_stage.to_vk());
         let dst_stage_vk = self.dst_state.into_pipeline_stage(self.dst_stage.to_vk());
+        let (src_queue_family, dst_queue_family) = queue_family_indices(self.src_queue, self.dst_queue);
+        // `readonly` describes the destination access, writes in a writable source state must still be made available
+        let src_readonly = self.readonly && self.src_state.is_read();
         vk::BufferMemoryBarrier2::default()
             .src_stage_mask(src_stage_vk)
-            .src_access_mask(self.src_state.into_access_flag(self.readonly))
+            .src_access_mask(self.src_state.into_access_flag(src_readonly))
             .dst_stage_mask(dst_stage_vk)
             .dst_access_mask(self.dst_state.into_access_flag(self.readonly))
-            .src_queue_family_index(self.src_queue.family_index())
-            .dst_queue_family_index(self.dst_queue.family_index())
+            .src_queue_family_index(src_queue_family)
+            .dst_queue_family_index(dst_queue_family)
             .buffer(self.buffer.buffer().handle())
             .offset(self.offset as vk::DeviceSize)
             .size(self.size as vk::DeviceSize)
@@ -281,6 +363,9 @@ pub enum TextureState {
     General,
     Color,
     DepthStencil,
+    /// Target of a depth/stencil multisample resolve. Resolves write in the color attachment
+    /// output stage with color attachment access, even for depth/stencil.
+    DepthStencilResolve,
     Present,
 }
 
@@ -295,6 +380,7 @@ impl TextureState {
             TextureState::General => shader_used_stage,
             TextureState::Color => vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT,
             TextureState::DepthStencil => vk::PipelineStageFlags2::EARLY_FRAGMENT_TESTS | vk::PipelineStageFlags2::LATE_FRAGMENT_TESTS,
+            TextureState::DepthStencilResolve => vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT | vk::PipelineStageFlags2::LATE_FRAGMENT_TESTS,
             TextureState::Present => vk::PipelineStageFlags2::NONE,
         }
     }
@@ -310,6 +396,7 @@ impl TextureState {
             TextureState::General => if is_readonly { vk::AccessFlags2::MEMORY_READ } else { vk::AccessFlags2::MEMORY_READ | vk::AccessFlags2::MEMORY_WRITE },
             TextureState::Color => if is_readonly { vk::AccessFlags2::COLOR_ATTACHMENT_READ } else { vk::AccessFlags2::COLOR_ATTACHMENT_WRITE }
             TextureState::DepthStencil => if is_readonly { vk::AccessFlags2::DEPTH_STENCIL_ATTACHMENT_READ } else { vk::AccessFlags2::DEPTH_STENCIL_ATTACHMENT_WRITE }
+            TextureState::DepthStencilResolve => if is_readonly { vk::AccessFlags2::DEPTH_STENCIL_ATTACHMENT_READ } else { vk::AccessFlags2::COLOR_ATTACHMENT_WRITE | vk::AccessFlags2::DEPTH_STENCIL_ATTACHMENT_WRITE }
             TextureState::Present => vk::AccessFlags2::NONE
         }
     }
@@ -323,7 +410,8 @@ impl TextureState {
             TextureState::Storage => vk::ImageLayout::GENERAL,
             TextureState::General => vk::ImageLayout::GENERAL,
             TextureState::Color => vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
-            TextureState::DepthStencil => vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+            TextureState::DepthStencil |
+            TextureState::DepthStencilResolve => vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
             TextureState::Present => vk::ImageLayout::PRESENT_SRC_KHR,
         }
     }
@@ -339,7 +427,8 @@ impl From<TextureState> for TextureLayout {
             TextureState::Storage => TextureLayout::General,
             TextureState::General => TextureLayout::General,
             TextureState::Color => TextureLayout::Color,
-            TextureState::DepthStencil => TextureLayout::DepthStencil,
+            TextureState::DepthStencil |
+            TextureState::DepthStencilResolve => TextureLayout::DepthStencil,
             TextureState::Present => TextureLayout::Present,
         }
     }
@@ -388,6 +477,13 @@ impl<'a> TextureBarrier<'a> {
         }
     }
 
+    /// Keep the texture on `queue`, without a queue family ownership transfer.
+    pub fn same_queue(mut self, queue: Queue) -> Self {
+        self.src_queue = queue;
+        self.dst_queue = queue;
+        self
+    }
+
     pub fn with_layouts(mut self, old_layout: TextureLayout, new_layout: TextureLayout) -> Self {
         self.old_layout = old_layout;
         self.new_layout = new_layout;
@@ -403,24 +499,53 @@ impl<'a> TextureBarrier<'a> {
         // This mirrors the old behavior of `texture_barrier()` which derives stage from state.
         let src_stage_vk = self.src_state.into_pipeline_stage(self.src_stage.to_vk());
         let dst_stage_vk = self.dst_state.into_pipeline_stage(self.dst_stage.to_vk());
+        let (src_queue_family, dst_queue_family) = queue_family_indices(self.src_queue, self.dst_queue);
 
         vk::ImageMemoryBarrier2::default()
             .src_stage_mask(src_stage_vk)
             .src_access_mask(self.src_state.into_access_flag(self.readonly))
             .dst_stage_mask(dst_stage_vk)
             .dst_access_mask(self.dst_state.into_access_flag(self.readonly))
-            .src_queue_family_index(self.src_queue.family_index())
-            .dst_queue_family_index(self.dst_queue.family_index())
+            .src_queue_family_index(src_queue_family)
+            .dst_queue_family_index(dst_queue_family)
             .old_layout(old_layout)
             .new_layout(self.new_layout.to_vk())
             .image(self.texture.texture().handle())
-            .subresource_range(vk::ImageSubresourceRange {
-                aspect_mask: self.texture.texture().aspect(),
-                base_mip_level: 0,
-                level_count: vk::REMAINING_MIP_LEVELS,
-                base_array_layer: 0,
-                layer_count: vk::REMAINING_ARRAY_LAYERS,
-            })
+            .subresource_range(self.texture.subresource_range())
+    }
+}
+
+/// A buffer or texture barrier moving a resource between queue families,
+/// recorded in two halves with [`crate::CommandEncoder::queue_release`] and [`crate::CommandEncoder::queue_acquire`].
+pub enum QueueTransferBarrier<'a> {
+    Buffer(BufferBarrier<'a>),
+    Texture(TextureBarrier<'a>),
+}
+
+impl QueueTransferBarrier<'_> {
+    fn queues(&self) -> (Queue, Queue) {
+        match self {
+            QueueTransferBarrier::Buffer(b) => (b.src_queue, b.dst_queue),
+            QueueTransferBarrier::Texture(t) => (t.src_queue, t.dst_queue),
+        }
+    }
+
+    /// Whether the barrier names two different queue families.
+    pub fn is_ownership_transfer(&self) -> bool {
+        let (src_queue, dst_queue) = self.queues();
+        src_queue.family_index() != dst_queue.family_index()
+    }
+}
+
+impl<'a> From<BufferBarrier<'a>> for QueueTransferBarrier<'a> {
+    fn from(value: BufferBarrier<'a>) -> Self {
+        QueueTransferBarrier::Buffer(value)
+    }
+}
+
+impl<'a> From<TextureBarrier<'a>> for QueueTransferBarrier<'a> {
+    fn from(value: TextureBarrier<'a>) -> Self {
+        QueueTransferBarrier::Texture(value)
     }
 }
 
@@ -458,4 +583,26 @@ impl MemoryBarrier {
             .dst_stage_mask(self.dst_stage.to_vk())
             .dst_access_mask(self.dst_access)
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_family_barriers_ignore_queue_families() {
+        let graphics = Queue::new(vk::Queue::null(), 0);
+        let graphics_other = Queue::new(vk::Queue::null(), 0);
+        assert_eq!(
+            queue_family_indices(graphics, graphics_other),
+            (vk::QUEUE_FAMILY_IGNORED, vk::QUEUE_FAMILY_IGNORED)
+        );
+        assert_eq!(
+            queue_family_indices(Queue::ignored(), Queue::ignored()),
+            (vk::QUEUE_FAMILY_IGNORED, vk::QUEUE_FAMILY_IGNORED)
+        );
+
+        let transfer = Queue::new(vk::Queue::null(), 1);
+        assert_eq!(queue_family_indices(graphics, transfer), (0, 1));
+    }
+}
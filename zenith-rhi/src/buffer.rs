@@ -3,12 +3,16 @@
 use ash::{vk};
 use zenith_core::log;
 use std::hash::{Hash, Hasher};
-use std::ops::RangeBounds;
+use std::marker::PhantomData;
+use std::ops::{Deref, RangeBounds};
+use std::sync::atomic::{AtomicBool, Ordering};
+use bytemuck::{AnyBitPattern, NoUninit};
 use zenith_rhi_derive::DeviceObject;
 use crate::RenderDevice;
 use crate::device::{DebuggableObject};
 use crate::utility::{find_memory_type, normalize_range_u64};
 use crate::device::set_debug_name_handle;
+use crate::memory::MemoryAllocation;
 
 /// Buffer descriptor for creating GPU buffers.
 #[derive(Debug, Clone)]
@@ -43,7 +47,7 @@ impl BufferDesc {
         }
     }
 
-    /// Create a vertex buffer descriptor.
+    /// Create a vertex buffer descriptor, device local and uploadable (`TRANSFER_DST`).
     pub fn vertex(name: &str, size: vk::DeviceSize) -> Self {
         Self {
             name: name.to_owned(),
@@ -53,7 +57,7 @@ impl BufferDesc {
         }
     }
 
-    /// Create an index buffer descriptor.
+    /// Create an index buffer descriptor, device local and uploadable (`TRANSFER_DST`).
     pub fn index(name: &str, size: vk::DeviceSize) -> Self {
         Self {
             name: name.to_owned(),
@@ -63,7 +67,7 @@ impl BufferDesc {
         }
     }
 
-    /// Create a uniform buffer descriptor.
+    /// Create a uniform buffer descriptor, host visible and coherent so it can be written directly.
     pub fn uniform(name: &str, size: vk::DeviceSize) -> Self {
         Self {
             name: name.to_owned(),
@@ -73,12 +77,12 @@ impl BufferDesc {
         }
     }
 
-    /// Create a storage buffer descriptor.
+    /// Create a storage buffer descriptor, device local and uploadable (`TRANSFER_DST`).
     pub fn storage(name: &str, size: vk::DeviceSize) -> Self {
         Self {
             name: name.to_owned(),
             size,
-            usage: vk::BufferUsageFlags::STORAGE_BUFFER,
+            usage: vk::BufferUsageFlags::STORAGE_BUFFER | vk::BufferUsageFlags::TRANSFER_DST,
             memory_flags: vk::MemoryPropertyFlags::DEVICE_LOCAL,
         }
     }
@@ -93,6 +97,8 @@ impl BufferDesc {
         }
     }
 
+    /// Rename the buffer. Use [`Self::new`] with [`Self::with_usage`] and [`Self::with_memory_flags`]
+    /// for usage and memory combinations the presets above don't cover.
     pub fn with_name(mut self, name: impl Into<String>) -> Self {
         self.name = name.into();
         self
@@ -161,7 +167,12 @@ impl Hash for BufferDesc {
 pub struct Buffer {
     buffer: vk::Buffer,
     desc: BufferDesc,
-    memory: vk::DeviceMemory,
+    memory: MemoryAllocation,
+    /// Host writes and device writes are visible without explicit flush/invalidate.
+    coherent: bool,
+    non_coherent_atom_size: vk::DeviceSize,
+    /// The memory is currently mapped, by a [`MappedMemory`] guard or a transient read/write/flush/invalidate.
+    mapped: AtomicBool,
 }
 
 impl Buffer {
@@ -187,14 +198,19 @@ impl Buffer {
             .ok_or(vk::Result::ERROR_OUT_OF_DEVICE_MEMORY)?;
 
         // Allocate memory
-        let alloc_info = vk::MemoryAllocateInfo::default()
-            .allocation_size(mem_requirements.size)
-            .memory_type_index(memory_type_index);
-
-        let memory = unsafe { device.handle().allocate_memory(&alloc_info, None)? };
+        let memory = match MemoryAllocation::new(device, mem_requirements, memory_type_index, true) {
+            Ok(memory) => memory,
+            Err(err) => {
+                unsafe { device.handle().destroy_buffer(buffer, None) };
+                return Err(err);
+            }
+        };
+        let coherent = memory_properties.memory_types[memory_type_index as usize]
+            .property_flags
+            .contains(vk::MemoryPropertyFlags::HOST_COHERENT);
 
         // Bind memory to buffer
-        unsafe { device.handle().bind_buffer_memory(buffer, memory, 0)? };
+        unsafe { device.handle().bind_buffer_memory(buffer, memory.memory(), memory.offset())? };
 
         log::trace!("new buffer created.");
 
@@ -202,6 +218,9 @@ impl Buffer {
             buffer,
             desc: desc.clone(),
             memory,
+            coherent,
+            non_coherent_atom_size: device.limits().non_coherent_atom_size.max(1),
+            mapped: AtomicBool::new(false),
             device: device.handle().clone(),
         };
         device.set_debug_name(&buf);
@@ -247,13 +266,130 @@ impl Buffer {
     pub fn usage(&self) -> vk::BufferUsageFlags {
         self.desc.usage
     }
+
+    /// Check if the memory is host coherent, so flush/invalidate are no-ops.
+    #[inline]
+    pub fn is_coherent(&self) -> bool {
+        self.coherent
+    }
+
+    /// Map the whole buffer until the returned guard is dropped. The buffer must be `HOST_VISIBLE`.
+    ///
+    /// Fails with `ERROR_MEMORY_MAP_FAILED` while the buffer is already mapped, by another guard or a
+    /// [`BufferRange::write`]/[`BufferRange::read`] in progress. While the guard lives, flush and invalidate
+    /// through [`MappedMemory::flush`] and [`MappedMemory::invalidate`].
+    pub fn map(&self) -> Result<MappedMemory<'_>, vk::Result> {
+        if !self.desc.memory_flags.contains(vk::MemoryPropertyFlags::HOST_VISIBLE) {
+            log::error!("Buffer '{}': map requires HOST_VISIBLE memory, has {:?}.", self.name(), self.desc.memory_flags);
+            return Err(vk::Result::ERROR_MEMORY_MAP_FAILED);
+        }
+
+        // SAFETY: the whole buffer is in range, the guard unmaps it on drop.
+        let (ptr, _, _) = unsafe { self.map_range(0, self.desc.size)? };
+        Ok(MappedMemory {
+            buffer: self,
+            ptr,
+            len: self.desc.size as usize,
+        })
+    }
+
+    /// Make host writes to `range` visible to the device. No-op for coherent memory.
+    ///
+    /// Maps the range for the duration of the call, so it fails while the buffer is mapped.
+    pub fn flush<R: RangeBounds<u64>>(&self, range: R) -> Result<(), vk::Result> {
+        let (offset, size) = normalize_range_u64(range, self.desc.size)?;
+        self.sync_mapped_range(offset, size, MappedRangeSync::Flush)
+    }
+
+    /// Make device writes to `range` visible to the host. No-op for coherent memory.
+    ///
+    /// Maps the range for the duration of the call, so it fails while the buffer is mapped.
+    pub fn invalidate<R: RangeBounds<u64>>(&self, range: R) -> Result<(), vk::Result> {
+        let (offset, size) = normalize_range_u64(range, self.desc.size)?;
+        self.sync_mapped_range(offset, size, MappedRangeSync::Invalidate)
+    }
+
+    /// Expand a range to `nonCoherentAtomSize` boundaries, clamped to the allocation.
+    fn aligned_memory_range(&self, offset: u64, size: u64) -> (u64, u64) {
+        let atom = self.non_coherent_atom_size;
+        let start = offset / atom * atom;
+        let end = (offset + size).div_ceil(atom) * atom;
+        (start, end.min(self.memory.size()) - start)
+    }
+
+    fn sync_mapped_range(&self, offset: u64, size: u64, sync: MappedRangeSync) -> Result<(), vk::Result> {
+        if self.coherent || size == 0 {
+            return Ok(());
+        }
+
+        // SAFETY: range lies within the buffer, the memory is mapped for the duration of the call.
+        unsafe {
+            let (_, mapped_offset, mapped_size) = self.map_range(offset, size)?;
+            let result = self.sync_mapped_memory(mapped_offset, mapped_size, sync);
+            self.unmap_range();
+            result
+        }
+    }
+
+    /// Flush or invalidate an aligned range relative to the allocation, which must currently be mapped.
+    unsafe fn sync_mapped_memory(&self, offset: u64, size: u64, sync: MappedRangeSync) -> Result<(), vk::Result> {
+        if self.coherent {
+            return Ok(());
+        }
+
+        let range = vk::MappedMemoryRange::default()
+            .memory(self.memory.memory())
+            .offset(self.memory.offset() + offset)
+            .size(size);
+        unsafe {
+            match sync {
+                MappedRangeSync::Flush => self.device.flush_mapped_memory_ranges(&[range]),
+                MappedRangeSync::Invalidate => self.device.invalidate_mapped_memory_ranges(&[range]),
+            }
+        }
+    }
+
+    /// Map the atom aligned range covering `offset..offset + size`, returning the pointer to `offset`.
+    /// Must be paired with [`Self::unmap_range`], fails if the buffer is already mapped.
+    unsafe fn map_range(&self, offset: u64, size: u64) -> Result<(*mut u8, u64, u64), vk::Result> {
+        if self.mapped.swap(true, Ordering::Acquire) {
+            log::error!("Buffer '{}' is already mapped.", self.name());
+            return Err(vk::Result::ERROR_MEMORY_MAP_FAILED);
+        }
+
+        let (aligned_offset, aligned_size) = if self.coherent {
+            (offset, size)
+        } else {
+            self.aligned_memory_range(offset, size)
+        };
+        let ptr = match unsafe { self.memory.map(&self.device, aligned_offset, aligned_size) } {
+            Ok(ptr) => ptr,
+            Err(err) => {
+                self.mapped.store(false, Ordering::Release);
+                return Err(err);
+            }
+        };
+        let ptr = unsafe { ptr.add((offset - aligned_offset) as usize) };
+        Ok((ptr, aligned_offset, aligned_size))
+    }
+
+    unsafe fn unmap_range(&self) {
+        unsafe { self.memory.unmap(&self.device) };
+        self.mapped.store(false, Ordering::Release);
+    }
+}
+
+#[derive(Clone, Copy)]
+enum MappedRangeSync {
+    Flush,
+    Invalidate,
 }
 
 impl Drop for Buffer {
     fn drop(&mut self) {
         unsafe {
             self.device.destroy_buffer(self.buffer, None);
-            self.device.free_memory(self.memory, None);
+            self.memory.free(&self.device);
         }
 
         log::trace!("buffer destroyed.");
@@ -263,12 +399,15 @@ impl Drop for Buffer {
 impl DebuggableObject for Buffer {
     fn set_debug_name(&self, device: &RenderDevice) {
         set_debug_name_handle(device, self.buffer, vk::ObjectType::BUFFER, self.name());
-        set_debug_name_handle(
-            device,
-            self.memory,
-            vk::ObjectType::DEVICE_MEMORY,
-            &format!("{}.memory", self.name()),
-        );
+        // Suballocated memory is shared with other resources.
+        if self.memory.is_dedicated() {
+            set_debug_name_handle(
+                device,
+                self.memory.memory(),
+                vk::ObjectType::DEVICE_MEMORY,
+                &format!("{}.memory", self.name()),
+            );
+        }
     }
 }
 
@@ -305,18 +444,180 @@ impl<'a> BufferRange<'a> {
             return Err(vk::Result::ERROR_OUT_OF_DEVICE_MEMORY);
         }
 
-        // SAFETY: range is checked before constructing, and mapping is limited to `len`.
+        // SAFETY: range is checked before constructing, and mapping covers `len`.
+        unsafe {
+            let (ptr, mapped_offset, mapped_size) = self.buffer.map_range(self.offset, len)?;
+            std::ptr::copy_nonoverlapping(data.as_ptr(), ptr, data.len());
+            let result = self.buffer.sync_mapped_memory(mapped_offset, mapped_size, MappedRangeSync::Flush);
+            self.buffer.unmap_range();
+            result
+        }
+    }
+
+    /// Read the start of the range into `data`, the buffer must be host-visible.
+    pub fn read(&self, data: &mut [u8]) -> Result<(), vk::Result> {
+        let len = data.len() as u64;
+        if len == 0 {
+            return Ok(());
+        }
+        if len > self.size {
+            return Err(vk::Result::ERROR_OUT_OF_DEVICE_MEMORY);
+        }
+
+        // SAFETY: range is checked before constructing, and mapping covers `len`.
         unsafe {
-            let ptr = self.buffer.device.map_memory(
-                self.buffer.memory,
-                self.offset as vk::DeviceSize,
-                len as vk::DeviceSize,
-                vk::MemoryMapFlags::empty(),
-            )?;
-            std::ptr::copy_nonoverlapping(data.as_ptr(), ptr as *mut u8, data.len());
-            self.buffer.device.unmap_memory(self.buffer.memory);
+            let (ptr, mapped_offset, mapped_size) = self.buffer.map_range(self.offset, len)?;
+            let result = self.buffer.sync_mapped_memory(mapped_offset, mapped_size, MappedRangeSync::Invalidate);
+            if result.is_ok() {
+                std::ptr::copy_nonoverlapping(ptr as *const u8, data.as_mut_ptr(), data.len());
+            }
+            self.buffer.unmap_range();
+            result
         }
+    }
+}
+
+/// Host mapping of a whole [`Buffer`], unmapped on drop. Created by [`Buffer::map`].
+///
+/// For non-coherent memory, host writes must be made visible to the device with [`Self::flush`],
+/// and device writes to the host with [`Self::invalidate`] before reading them.
+pub struct MappedMemory<'a> {
+    buffer: &'a Buffer,
+    ptr: *mut u8,
+    len: usize,
+}
+
+impl MappedMemory<'_> {
+    #[inline]
+    pub fn len(&self) -> usize { self.len }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool { self.len == 0 }
+
+    /// View the mapped bytes as `T`s, panics if the size or alignment doesn't fit `T`.
+    pub fn as_slice<T: AnyBitPattern>(&self) -> &[T] {
+        // SAFETY: `ptr` is mapped for `len` bytes for the lifetime of the guard.
+        bytemuck::cast_slice(unsafe { std::slice::from_raw_parts(self.ptr, self.len) })
+    }
+
+    /// Mutably view the mapped bytes as `T`s, panics if the size or alignment doesn't fit `T`.
+    pub fn as_slice_mut<T: NoUninit + AnyBitPattern>(&mut self) -> &mut [T] {
+        // SAFETY: `ptr` is mapped for `len` bytes for the lifetime of the guard, which is borrowed mutably.
+        bytemuck::cast_slice_mut(unsafe { std::slice::from_raw_parts_mut(self.ptr, self.len) })
+    }
+
+    /// Make host writes visible to the device. No-op for coherent memory.
+    pub fn flush(&self) -> Result<(), vk::Result> {
+        self.sync(MappedRangeSync::Flush)
+    }
+
+    /// Make device writes visible to the host. No-op for coherent memory.
+    pub fn invalidate(&self) -> Result<(), vk::Result> {
+        self.sync(MappedRangeSync::Invalidate)
+    }
+
+    fn sync(&self, sync: MappedRangeSync) -> Result<(), vk::Result> {
+        let (offset, size) = self.buffer.aligned_memory_range(0, self.len as u64);
+        // SAFETY: the guard keeps the whole buffer mapped until it is dropped.
+        unsafe { self.buffer.sync_mapped_memory(offset, size, sync) }
+    }
+}
+
+impl Drop for MappedMemory<'_> {
+    fn drop(&mut self) {
+        // SAFETY: mapped by `Buffer::map`.
+        unsafe { self.buffer.unmap_range() };
+    }
+}
+
+/// Buffer viewed as an array of `T`, offsets are computed from `size_of::<T>()`.
+pub struct TypedBuffer<T> {
+    buffer: Buffer,
+    _marker: PhantomData<T>,
+}
+
+impl<T: NoUninit + AnyBitPattern> TypedBuffer<T> {
+    /// Wrap `buffer`, checking `T` has a valid array stride for the buffer usage:
+    /// a multiple of 16 bytes for uniform buffers (std140) and 4 bytes for storage buffers (std430).
+    pub fn new(buffer: Buffer) -> Self {
+        let stride = size_of::<T>() as u64;
+        assert!(stride > 0, "TypedBuffer '{}': zero sized element type", buffer.name());
+        if buffer.usage().contains(vk::BufferUsageFlags::UNIFORM_BUFFER) {
+            assert!(
+                stride.is_multiple_of(16),
+                "TypedBuffer '{}': element size {stride} is not a multiple of 16 required by uniform buffer arrays",
+                buffer.name()
+            );
+        }
+        if buffer.usage().contains(vk::BufferUsageFlags::STORAGE_BUFFER) {
+            assert!(
+                stride.is_multiple_of(4),
+                "TypedBuffer '{}': element size {stride} is not a multiple of 4 required by storage buffer arrays",
+                buffer.name()
+            );
+        }
+
+        Self {
+            buffer,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Create a buffer holding `len` elements of `T`, `desc.size` is overridden.
+    pub fn with_len(device: &RenderDevice, desc: &BufferDesc, len: usize) -> Result<Self, vk::Result> {
+        let desc = BufferDesc {
+            size: (len * size_of::<T>()) as vk::DeviceSize,
+            ..desc.clone()
+        };
+        Ok(Self::new(Buffer::new(device, &desc)?))
+    }
+
+    /// Number of whole elements in the buffer.
+    #[inline]
+    pub fn len(&self) -> usize {
+        (self.buffer.size() / size_of::<T>() as u64) as usize
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Range of the element at `index`.
+    pub fn element_range(&self, index: usize) -> Result<BufferRange<'_>, vk::Result> {
+        assert!(index < self.len(), "TypedBuffer '{}': index {index} out of bounds (len: {})", self.buffer.name(), self.len());
+        let stride = size_of::<T>() as u64;
+        let offset = index as u64 * stride;
+        self.buffer.as_range(offset..offset + stride)
+    }
+
+    /// Write the element at `index`, the buffer must be host-visible.
+    pub fn write_at(&self, index: usize, value: &T) -> Result<(), vk::Result> {
+        self.element_range(index)?.write(bytemuck::bytes_of(value))
+    }
+
+    /// Read the element at `index`, the buffer must be host-visible.
+    pub fn read_at(&self, index: usize) -> Result<T, vk::Result> {
+        debug_assert!(
+            self.buffer.desc().memory_flags.contains(vk::MemoryPropertyFlags::HOST_VISIBLE),
+            "TypedBuffer '{}': read_at requires a host-visible buffer",
+            self.buffer.name()
+        );
+        let mut value = T::zeroed();
+        self.element_range(index)?.read(bytemuck::bytes_of_mut(&mut value))?;
+        Ok(value)
+    }
+
+    /// Unwrap the underlying buffer.
+    pub fn into_inner(self) -> Buffer {
+        self.buffer
+    }
+}
+
+impl<T> Deref for TypedBuffer<T> {
+    type Target = Buffer;
 
-        Ok(())
+    fn deref(&self) -> &Buffer {
+        &self.buffer
     }
 }
@@ -2,10 +2,14 @@
 
 use std::cell::{Cell, RefCell};
 use ash::{vk};
+use bytemuck::NoUninit;
+use zenith_core::collections::SmallVec;
 use zenith_rhi_derive::DeviceObject;
-use crate::barrier::{BufferBarrier, TextureBarrier, MemoryBarrier};
-use crate::{Queue, RenderDevice};
+use crate::barrier::{BufferBarrier, TextureBarrier, MemoryBarrier, QueueTransferBarrier, TextureState};
+use crate::{Buffer, ColorAttachmentDesc, DepthStencilDesc, GraphicPipeline, QueryPool, Queue, RenderDevice};
+use crate::texture::TextureRange;
 use crate::synchronization::Fence;
+use zenith_core::log;
 use crate::device::DebuggableObject;
 use crate::device::set_debug_name_handle;
 
@@ -41,6 +45,16 @@ impl CommandPool {
         Ok(pool)
     }
 
+    /// Create a pool for short-lived command buffers that is recycled as a whole with [`Self::reset`].
+    pub fn new_transient(name: &str, device: &RenderDevice, queue_family: u32) -> Result<Self, vk::Result> {
+        Self::new(
+            name,
+            device,
+            queue_family,
+            vk::CommandPoolCreateFlags::TRANSIENT | vk::CommandPoolCreateFlags::RESET_COMMAND_BUFFER,
+        )
+    }
+
     pub fn allocate(&self) -> Result<vk::CommandBuffer, vk::Result> {
         let index = self.next_index.get();
         self.next_index.set(index + 1);
@@ -88,6 +102,90 @@ impl Drop for CommandPool {
     }
 }
 
+/// One transient command pool per frame in flight of the device, rotated by frame index.
+pub struct FramePools {
+    pools: Vec<CommandPool>,
+}
+
+impl FramePools {
+    pub fn new(name: &str, device: &RenderDevice, queue_family: u32) -> Result<Self, vk::Result> {
+        let pools = (0..device.num_frames())
+            .map(|idx| CommandPool::new_transient(&format!("{name}.f{idx}"), device, queue_family))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self { pools })
+    }
+
+    /// Reset the pool of `frame_index` and return it.
+    /// The caller must ensure the GPU has finished the commands previously recorded for this frame.
+    pub fn reset_frame(&self, frame_index: usize) -> Result<&CommandPool, vk::Result> {
+        let pool = self.pool(frame_index);
+        pool.reset()?;
+        Ok(pool)
+    }
+
+    /// Reset the pool of `frame_index` and hand out a fresh encoder from it.
+    pub fn begin_frame<'a>(&self, device: &'a RenderDevice, frame_index: usize) -> anyhow::Result<CommandEncoder<'a>> {
+        let pool = self.reset_frame(frame_index)?;
+        CommandEncoder::new(&format!("cmd.{}", pool.name()), device, pool)
+    }
+
+    #[inline]
+    pub fn pool(&self, frame_index: usize) -> &CommandPool {
+        &self.pools[frame_index]
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.pools.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.pools.is_empty()
+    }
+}
+
+/// A color target for [`CommandEncoder::begin_rendering_targets`].
+/// The texture (and the resolve texture, if any) must already be in `COLOR_ATTACHMENT_OPTIMAL` layout.
+pub struct ColorRenderTarget<'a> {
+    pub desc: &'a ColorAttachmentDesc,
+    pub target: TextureRange<'a>,
+    /// Single-sample texture the multisampled `target` is averaged into at the end of rendering.
+    pub resolve: Option<TextureRange<'a>>,
+}
+
+impl<'a> ColorRenderTarget<'a> {
+    pub fn new(desc: &'a ColorAttachmentDesc, target: TextureRange<'a>) -> Self {
+        Self { desc, target, resolve: None }
+    }
+
+    pub fn with_resolve(mut self, resolve: TextureRange<'a>) -> Self {
+        self.resolve = Some(resolve);
+        self
+    }
+}
+
+/// A depth/stencil target for [`CommandEncoder::begin_rendering_targets`].
+/// The texture (and the resolve texture, if any) must already be in `DEPTH_STENCIL_ATTACHMENT_OPTIMAL` layout.
+pub struct DepthRenderTarget<'a> {
+    pub desc: &'a DepthStencilDesc,
+    pub target: TextureRange<'a>,
+    /// Single-sample texture and mode (`MIN`, `MAX` or `SAMPLE_ZERO`) used to resolve the depth aspect.
+    /// The mode must be supported by the device, see [`crate::core::DepthStencilResolveSupport`].
+    pub resolve: Option<(TextureRange<'a>, vk::ResolveModeFlags)>,
+}
+
+impl<'a> DepthRenderTarget<'a> {
+    pub fn new(desc: &'a DepthStencilDesc, target: TextureRange<'a>) -> Self {
+        Self { desc, target, resolve: None }
+    }
+
+    pub fn with_resolve(mut self, resolve: TextureRange<'a>, mode: vk::ResolveModeFlags) -> Self {
+        self.resolve = Some((resolve, mode));
+        self
+    }
+}
+
 /// Command encoder wrapping a command buffer with common graphics commands.
 pub struct CommandEncoder<'a> {
     name: String,
@@ -137,6 +235,11 @@ impl<'a> CommandEncoder<'a> {
         descriptor_sets: &[vk::DescriptorSet],
         dynamic_offsets: &[u32],
     ) {
+        debug_assert!(
+            first_set as usize + descriptor_sets.len() <= self.device.limits().max_bound_descriptor_sets as usize,
+            "bind_descriptor_sets: binding sets {first_set}..{} exceeds maxBoundDescriptorSets",
+            first_set as usize + descriptor_sets.len(),
+        );
         unsafe {
             self.device.handle().cmd_bind_descriptor_sets(
                 self.cmd,
@@ -149,6 +252,25 @@ impl<'a> CommandEncoder<'a> {
         }
     }
 
+    /// Bind descriptor sets against the layout of `pipeline`,
+    /// checking they fit in the pipeline's descriptor set layouts.
+    pub fn bind_pipeline_descriptor_sets(
+        &self,
+        bind_point: vk::PipelineBindPoint,
+        pipeline: &GraphicPipeline,
+        first_set: u32,
+        descriptor_sets: &[vk::DescriptorSet],
+        dynamic_offsets: &[u32],
+    ) {
+        debug_assert!(
+            first_set as usize + descriptor_sets.len() <= pipeline.descriptor_set_count(),
+            "bind_pipeline_descriptor_sets: binding sets {first_set}..{} but the pipeline layout only has {} sets",
+            first_set as usize + descriptor_sets.len(),
+            pipeline.descriptor_set_count(),
+        );
+        self.bind_descriptor_sets(bind_point, pipeline.layout(), first_set, descriptor_sets, dynamic_offsets);
+    }
+
     // Vertex/Index buffer commands
     pub fn bind_vertex_buffers(&self, first_binding: u32, buffers: &[vk::Buffer], offsets: &[vk::DeviceSize]) {
         unsafe { self.device.handle().cmd_bind_vertex_buffers(self.cmd, first_binding, buffers, offsets) }
@@ -176,11 +298,29 @@ impl<'a> CommandEncoder<'a> {
         unsafe { self.device.handle().cmd_set_scissor(self.cmd, first, scissors) }
     }
 
+    /// Requires `DEPTH_BIAS` dynamic state on the bound pipeline.
+    pub fn set_depth_bias(&self, constant_factor: f32, clamp: f32, slope_factor: f32) {
+        unsafe { self.device.handle().cmd_set_depth_bias(self.cmd, constant_factor, clamp, slope_factor) }
+    }
+
+    /// Requires `STENCIL_REFERENCE` dynamic state on the bound pipeline.
+    pub fn set_stencil_reference(&self, face: vk::StencilFaceFlags, reference: u32) {
+        unsafe { self.device.handle().cmd_set_stencil_reference(self.cmd, face, reference) }
+    }
+
+    /// Requires `STENCIL_COMPARE_MASK` dynamic state on the bound pipeline.
+    pub fn set_stencil_compare_mask(&self, face: vk::StencilFaceFlags, compare_mask: u32) {
+        unsafe { self.device.handle().cmd_set_stencil_compare_mask(self.cmd, face, compare_mask) }
+    }
+
+    /// Requires `STENCIL_WRITE_MASK` dynamic state on the bound pipeline.
+    pub fn set_stencil_write_mask(&self, face: vk::StencilFaceFlags, write_mask: u32) {
+        unsafe { self.device.handle().cmd_set_stencil_write_mask(self.cmd, face, write_mask) }
+    }
+
     // Push constants
-    pub fn push_constants<T: Copy>(&self, layout: vk::PipelineLayout, stages: vk::ShaderStageFlags, offset: u32, data: &T) {
-        let bytes = unsafe {
-            std::slice::from_raw_parts(data as *const T as *const u8, std::mem::size_of::<T>())
-        };
+    pub fn push_constants<T: NoUninit>(&self, layout: vk::PipelineLayout, stages: vk::ShaderStageFlags, offset: u32, data: &T) {
+        let bytes = bytemuck::bytes_of(data);
         unsafe { self.device.handle().cmd_push_constants(self.cmd, layout, stages, offset, bytes) }
     }
 
@@ -189,10 +329,128 @@ impl<'a> CommandEncoder<'a> {
         unsafe { self.device.handle().cmd_begin_rendering(self.cmd, info) }
     }
 
+    /// Begin dynamic rendering using the load/store ops and clear values of the attachment descs.
+    /// No layout transitions are recorded; barriers stay the caller's job.
+    pub fn begin_rendering_targets(
+        &self,
+        extent: vk::Extent2D,
+        color_targets: &[ColorRenderTarget],
+        depth_target: Option<DepthRenderTarget>,
+    ) -> Result<(), vk::Result> {
+        let mut color_attachments = Vec::with_capacity(color_targets.len());
+        for color in color_targets {
+            let texture = color.target.texture();
+            debug_assert!(
+                texture.usage().contains(vk::ImageUsageFlags::COLOR_ATTACHMENT),
+                "begin_rendering_targets: '{}' lacks COLOR_ATTACHMENT usage",
+                texture.name()
+            );
+
+            let mut attachment = vk::RenderingAttachmentInfo::default()
+                .image_view(color.target.view()?)
+                .image_layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+                .load_op(color.desc.load_op)
+                .store_op(color.desc.store_op)
+                .clear_value(vk::ClearValue {
+                    color: vk::ClearColorValue {
+                        float32: color.desc.clear_value,
+                    },
+                });
+
+            if let Some(resolve) = &color.resolve {
+                debug_assert!(
+                    texture.desc().samples != vk::SampleCountFlags::TYPE_1
+                        && resolve.texture().desc().samples == vk::SampleCountFlags::TYPE_1,
+                    "begin_rendering_targets: resolving '{}' into '{}' requires a multisampled source and a single-sample destination",
+                    texture.name(),
+                    resolve.texture().name()
+                );
+
+                attachment = attachment
+                    .resolve_mode(vk::ResolveModeFlags::AVERAGE)
+                    .resolve_image_view(resolve.view()?)
+                    .resolve_image_layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL);
+            }
+
+            color_attachments.push(attachment);
+        }
+
+        let depth_attachment = match depth_target {
+            Some(depth) => {
+                let texture = depth.target.texture();
+                debug_assert!(
+                    texture.usage().contains(vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT),
+                    "begin_rendering_targets: '{}' lacks DEPTH_STENCIL_ATTACHMENT usage",
+                    texture.name()
+                );
+
+                let mut attachment = vk::RenderingAttachmentInfo::default()
+                    .image_view(depth.target.view()?)
+                    .image_layout(vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL)
+                    .load_op(depth.desc.depth_load_op)
+                    .store_op(depth.desc.depth_store_op)
+                    .clear_value(vk::ClearValue {
+                        depth_stencil: vk::ClearDepthStencilValue {
+                            depth: depth.desc.depth_clear_value,
+                            stencil: depth.desc.stencil_clear_value,
+                        },
+                    });
+
+                if let Some((resolve, mode)) = &depth.resolve {
+                    let support = self.device.parent_physical_device().depth_stencil_resolve();
+                    if !support.supports_depth_resolve(*mode, texture.format()) {
+                        log::error!(
+                            "begin_rendering_targets: depth resolve mode {:?} of '{}' is not supported by the device ({:?}).",
+                            mode,
+                            texture.name(),
+                            support,
+                        );
+                        return Err(vk::Result::ERROR_FEATURE_NOT_PRESENT);
+                    }
+
+                    attachment = attachment
+                        .resolve_mode(*mode)
+                        .resolve_image_view(resolve.view()?)
+                        .resolve_image_layout(vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL);
+                }
+
+                Some(attachment)
+            }
+            None => None,
+        };
+
+        let mut rendering_info = vk::RenderingInfo::default()
+            .render_area(vk::Rect2D { offset: vk::Offset2D { x: 0, y: 0 }, extent })
+            .layer_count(1)
+            .color_attachments(&color_attachments);
+
+        if let Some(ref depth) = depth_attachment {
+            rendering_info = rendering_info.depth_attachment(depth);
+        }
+
+        self.begin_rendering(&rendering_info);
+        Ok(())
+    }
+
     pub fn end_rendering(&self) {
         unsafe { self.device.handle().cmd_end_rendering(self.cmd) }
     }
     
+    /// Record all barriers with a single `cmd_pipeline_barrier2`, sharing one dependency scope.
+    pub fn pipeline_barrier<'b>(&self, memory_barriers: &[MemoryBarrier], buffer_barriers: &[BufferBarrier<'b>], texture_barriers: &[TextureBarrier<'b>]) {
+        if memory_barriers.is_empty() && buffer_barriers.is_empty() && texture_barriers.is_empty() {
+            return;
+        }
+        let vk_memory_barriers: SmallVec<[vk::MemoryBarrier2; 4]> = memory_barriers.iter().map(|b| b.to_vk()).collect();
+        let vk_buffer_barriers: SmallVec<[vk::BufferMemoryBarrier2; 8]> = buffer_barriers.iter().map(|b| b.to_vk()).collect();
+        let vk_image_barriers: SmallVec<[vk::ImageMemoryBarrier2; 8]> = texture_barriers.iter().map(|b| b.to_vk()).collect();
+        let dep = vk::DependencyInfo::default()
+            .memory_barriers(&vk_memory_barriers)
+            .buffer_memory_barriers(&vk_buffer_barriers)
+            .image_memory_barriers(&vk_image_barriers);
+        unsafe { self.device.handle().cmd_pipeline_barrier2(self.cmd, &dep) }
+    }
+
     pub fn buffer_barriers<'b>(&self, barriers: &[BufferBarrier<'b>]) {
         if barriers.is_empty() {
             return;
@@ -211,6 +469,61 @@ impl<'a> CommandEncoder<'a> {
         unsafe { self.device.handle().cmd_pipeline_barrier2(self.cmd, &dep) }
     }
 
+    /// Record the release half of a queue family ownership transfer.
+    /// Must be recorded on a command buffer submitted to `src_queue`.
+    ///
+    /// The matching [`Self::queue_acquire`] on `dst_queue` must use a barrier with the same
+    /// subresource range, states and queues, and be ordered after this one with a semaphore.
+    pub fn queue_release<'b>(&self, barrier: impl Into<QueueTransferBarrier<'b>>) {
+        self.queue_transfer(barrier.into(), true);
+    }
+
+    /// Record the acquire half of a queue family ownership transfer.
+    /// Must be recorded on a command buffer submitted to `dst_queue`, see [`Self::queue_release`].
+    pub fn queue_acquire<'b>(&self, barrier: impl Into<QueueTransferBarrier<'b>>) {
+        self.queue_transfer(barrier.into(), false);
+    }
+
+    fn queue_transfer(&self, barrier: QueueTransferBarrier, release: bool) {
+        debug_assert!(
+            barrier.is_ownership_transfer(),
+            "queue_transfer: src and dst queue share a family, use a plain barrier instead"
+        );
+
+        // The release half only makes the source accesses available, the acquire half only
+        // makes them visible to the destination, the other scope is ignored by the driver.
+        match barrier {
+            QueueTransferBarrier::Buffer(barrier) => {
+                let mut vk_barrier = barrier.to_vk();
+                if release {
+                    vk_barrier = vk_barrier
+                        .dst_stage_mask(vk::PipelineStageFlags2::NONE)
+                        .dst_access_mask(vk::AccessFlags2::NONE);
+                } else {
+                    vk_barrier = vk_barrier
+                        .src_stage_mask(vk::PipelineStageFlags2::NONE)
+                        .src_access_mask(vk::AccessFlags2::NONE);
+                }
+                let dep = vk::DependencyInfo::default().buffer_memory_barriers(std::slice::from_ref(&vk_barrier));
+                unsafe { self.device.handle().cmd_pipeline_barrier2(self.cmd, &dep) }
+            }
+            QueueTransferBarrier::Texture(barrier) => {
+                let mut vk_barrier = barrier.to_vk();
+                if release {
+                    vk_barrier = vk_barrier
+                        .dst_stage_mask(vk::PipelineStageFlags2::NONE)
+                        .dst_access_mask(vk::AccessFlags2::NONE);
+                } else {
+                    vk_barrier = vk_barrier
+                        .src_stage_mask(vk::PipelineStageFlags2::NONE)
+                        .src_access_mask(vk::AccessFlags2::NONE);
+                }
+                let dep = vk::DependencyInfo::default().image_memory_barriers(std::slice::from_ref(&vk_barrier));
+                unsafe { self.device.handle().cmd_pipeline_barrier2(self.cmd, &dep) }
+            }
+        }
+    }
+
     pub fn memory_barrier(&self, barriers: &[MemoryBarrier]) {
         if barriers.is_empty() {
             return;
@@ -220,18 +533,177 @@ impl<'a> CommandEncoder<'a> {
         unsafe { self.device.handle().cmd_pipeline_barrier2(self.cmd, &dep) }
     }
 
+    // Query commands
+    pub fn reset_query_pool(&self, pool: &QueryPool, first: u32, count: u32) {
+        unsafe { self.device.handle().cmd_reset_query_pool(self.cmd, pool.handle(), first, count) }
+    }
+
+    pub fn write_timestamp(&self, pool: &QueryPool, stage: vk::PipelineStageFlags2, query: u32) {
+        unsafe { self.device.handle().cmd_write_timestamp2(self.cmd, stage, pool.handle(), query) }
+    }
+
     // Copy commands
-    pub fn copy_buffer(&self, src: vk::Buffer, dst: vk::Buffer, regions: &[vk::BufferCopy]) {
-        unsafe { self.device.handle().cmd_copy_buffer(self.cmd, src, dst, regions) }
+    pub fn copy_buffer(&self, src: &Buffer, dst: &Buffer, regions: &[vk::BufferCopy]) {
+        debug_assert!(src.usage().contains(vk::BufferUsageFlags::TRANSFER_SRC), "copy_buffer: '{}' lacks TRANSFER_SRC usage", src.name());
+        debug_assert!(dst.usage().contains(vk::BufferUsageFlags::TRANSFER_DST), "copy_buffer: '{}' lacks TRANSFER_DST usage", dst.name());
+        debug_assert!(
+            regions.iter().all(|r| r.src_offset + r.size <= src.size() && r.dst_offset + r.size <= dst.size()),
+            "copy_buffer: region out of bounds ('{}' -> '{}')",
+            src.name(),
+            dst.name()
+        );
+
+        unsafe { self.device.handle().cmd_copy_buffer(self.cmd, src.handle(), dst.handle(), regions) }
     }
 
     pub fn copy_buffer_to_image(&self, src: vk::Buffer, dst: vk::Image, layout: vk::ImageLayout, regions: &[vk::BufferImageCopy]) {
         unsafe { self.device.handle().cmd_copy_buffer_to_image(self.cmd, src, dst, layout, regions) }
     }
 
+    pub fn copy_image_to_buffer(&self, src: vk::Image, layout: vk::ImageLayout, dst: vk::Buffer, regions: &[vk::BufferImageCopy]) {
+        unsafe { self.device.handle().cmd_copy_image_to_buffer(self.cmd, src, layout, dst, regions) }
+    }
+
+    /// Copy every mip level of `src` into the matching mip level of `dst`.
+    /// Both textures must be in their transfer layouts (`TRANSFER_SRC_OPTIMAL` / `TRANSFER_DST_OPTIMAL`).
+    pub fn copy_image(&self, src: &TextureRange, dst: &TextureRange) {
+        debug_assert!(
+            src.texture().usage().contains(vk::ImageUsageFlags::TRANSFER_SRC),
+            "copy_image: '{}' lacks TRANSFER_SRC usage",
+            src.texture().name()
+        );
+        debug_assert!(
+            dst.texture().usage().contains(vk::ImageUsageFlags::TRANSFER_DST),
+            "copy_image: '{}' lacks TRANSFER_DST usage",
+            dst.texture().name()
+        );
+        debug_assert!(
+            src.num_mips() == dst.num_mips() && src.num_layers() == dst.num_layers(),
+            "copy_image: subresource count mismatch ('{}' -> '{}')",
+            src.texture().name(),
+            dst.texture().name()
+        );
+
+        let regions = (0..src.num_mips())
+            .map(|mip| {
+                debug_assert_eq!(src.mip_extent(mip), dst.mip_extent(mip), "copy_image: extent mismatch at mip {}", mip);
+                vk::ImageCopy::default()
+                    .src_subresource(src.subresource_layers(mip))
+                    .dst_subresource(dst.subresource_layers(mip))
+                    .extent(src.mip_extent(mip))
+            })
+            .collect::<Vec<_>>();
+
+        unsafe {
+            self.device.handle().cmd_copy_image(
+                self.cmd,
+                src.texture().handle(),
+                vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                dst.texture().handle(),
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                &regions,
+            )
+        }
+    }
+
     // Blit
-    pub fn blit_image(&self, src: vk::Image, src_layout: vk::ImageLayout, dst: vk::Image, dst_layout: vk::ImageLayout, regions: &[vk::ImageBlit], filter: vk::Filter) {
-        unsafe { self.device.handle().cmd_blit_image(self.cmd, src, src_layout, dst, dst_layout, regions, filter) }
+    /// Scale the first mip level of `src` into the first mip level of `dst`, e.g. for mip generation.
+    /// Both textures must be in their transfer layouts (`TRANSFER_SRC_OPTIMAL` / `TRANSFER_DST_OPTIMAL`).
+    pub fn blit_image(&self, src: &TextureRange, dst: &TextureRange, filter: vk::Filter) {
+        debug_assert!(
+            src.texture().usage().contains(vk::ImageUsageFlags::TRANSFER_SRC),
+            "blit_image: '{}' lacks TRANSFER_SRC usage",
+            src.texture().name()
+        );
+        debug_assert!(
+            dst.texture().usage().contains(vk::ImageUsageFlags::TRANSFER_DST),
+            "blit_image: '{}' lacks TRANSFER_DST usage",
+            dst.texture().name()
+        );
+
+        let to_offset = |extent: vk::Extent3D| vk::Offset3D {
+            x: extent.width as i32,
+            y: extent.height as i32,
+            z: extent.depth as i32,
+        };
+        let region = vk::ImageBlit::default()
+            .src_subresource(src.subresource_layers(0))
+            .src_offsets([vk::Offset3D::default(), to_offset(src.mip_extent(0))])
+            .dst_subresource(dst.subresource_layers(0))
+            .dst_offsets([vk::Offset3D::default(), to_offset(dst.mip_extent(0))]);
+
+        unsafe {
+            self.device.handle().cmd_blit_image(
+                self.cmd,
+                src.texture().handle(),
+                vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                dst.texture().handle(),
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                std::slice::from_ref(&region),
+                filter,
+            )
+        }
+    }
+
+    // Clear commands (outside of a rendering scope)
+    /// Clear a color texture. `state` must be `TransferDst` or `General`.
+    pub fn clear_color(&self, range: &TextureRange, state: TextureState, color: [f32; 4]) {
+        let texture = range.texture();
+        debug_assert!(
+            texture.usage().contains(vk::ImageUsageFlags::TRANSFER_DST),
+            "clear_color: '{}' lacks TRANSFER_DST usage",
+            texture.name()
+        );
+        debug_assert!(
+            matches!(state, TextureState::TransferDst | TextureState::General),
+            "clear_color: '{}' must be in TransferDst or General state, got {:?}",
+            texture.name(),
+            state
+        );
+        debug_assert_eq!(texture.aspect(), vk::ImageAspectFlags::COLOR, "clear_color: '{}' is not a color texture", texture.name());
+
+        let value = vk::ClearColorValue { float32: color };
+        unsafe {
+            self.device.handle().cmd_clear_color_image(
+                self.cmd,
+                texture.handle(),
+                state.into_image_layout(),
+                &value,
+                &[range.subresource_range()],
+            )
+        }
+    }
+
+    /// Clear a depth/stencil texture. `state` must be `TransferDst` or `General`.
+    pub fn clear_depth_stencil(&self, range: &TextureRange, state: TextureState, depth: f32, stencil: u32) {
+        let texture = range.texture();
+        debug_assert!(
+            texture.usage().contains(vk::ImageUsageFlags::TRANSFER_DST),
+            "clear_depth_stencil: '{}' lacks TRANSFER_DST usage",
+            texture.name()
+        );
+        debug_assert!(
+            matches!(state, TextureState::TransferDst | TextureState::General),
+            "clear_depth_stencil: '{}' must be in TransferDst or General state, got {:?}",
+            texture.name(),
+            state
+        );
+        debug_assert!(
+            !texture.aspect().contains(vk::ImageAspectFlags::COLOR),
+            "clear_depth_stencil: '{}' is not a depth/stencil texture",
+            texture.name()
+        );
+
+        let value = vk::ClearDepthStencilValue { depth, stencil };
+        unsafe {
+            self.device.handle().cmd_clear_depth_stencil_image(
+                self.cmd,
+                texture.handle(),
+                state.into_image_layout(),
+                &value,
+                &[range.subresource_range()],
+            )
+        }
     }
 
     pub fn custom<F>(&self, func: F)
@@ -254,19 +726,20 @@ pub struct ImmediateCommandEncoder<'a> {
     device: &'a RenderDevice,
     queue: Queue,
     pool: CommandPool,
-    fence: Fence,
+    /// Taken from the device fence pool, `None` only while dropping.
+    fence: Option<Fence>,
 }
 
 impl<'a> ImmediateCommandEncoder<'a> {
     pub fn new(device: &'a RenderDevice, queue: Queue) -> Result<Self, vk::Result> {
         let pool = CommandPool::new("command_pool.immediate", device, queue.family_index(), vk::CommandPoolCreateFlags::empty())?;
-        let fence = Fence::new("fence.immediate", device, false)?;
+        let fence = device.fence_pool().acquire(device)?;
 
         Ok(Self {
             device,
             queue,
             pool,
-            fence,
+            fence: Some(fence),
         })
     }
 
@@ -284,17 +757,45 @@ impl<'a> ImmediateCommandEncoder<'a> {
         record(&encoder);
         encoder.end()?;
 
-        let cmd_info = vk::CommandBufferSubmitInfo::default().command_buffer(encoder.handle());
-        let submit_info = vk::SubmitInfo2::default()
-            .command_buffer_infos(std::slice::from_ref(&cmd_info));
+        submit_and_wait(self.device, self.queue, encoder.handle(), self.fence.as_ref().unwrap())
+    }
+
+    /// Record commands and submit immediately on `queue`, blocking until the GPU finishes.
+    ///
+    /// Unlike [`Self::submit_and_wait`] this needs no encoder instance: the command buffer and fence
+    /// are cached per queue family on the [`RenderDevice`] and reset (not freed) for the next call,
+    /// which makes it cheap for repeated uploads during loading.
+    ///
+    /// # Thread safety
+    ///
+    /// The cache lives on the `RenderDevice`, which is `!Sync`, so it is only reachable from the thread
+    /// owning the device. Other threads must create their own `ImmediateCommandEncoder`, as command pools
+    /// are externally synchronized. Calling `run` from inside `record` creates a second context instead
+    /// of reusing the one being recorded.
+    pub fn run<F>(device: &RenderDevice, queue: Queue, record: F) -> Result<(), vk::Result>
+    where
+        F: FnOnce(&CommandEncoder),
+    {
+        let context = match device.take_immediate_context(queue.family_index()) {
+            Some(context) => context,
+            None => ImmediateContext::new(device, queue.family_index())?,
+        };
 
-        unsafe {
-            let fence = self.fence.handle();
-            self.device.handle().queue_submit2(self.queue.handle(), &[submit_info], fence)?;
-            self.device.handle().wait_for_fences(&[fence], true, u64::MAX)?;
-            self.device.handle().reset_fences(&[fence])?;
-        }
+        unsafe { device.handle().reset_command_buffer(context.cmd, vk::CommandBufferResetFlags::empty())?; }
+
+        let encoder = CommandEncoder {
+            name: "cmd.immediate".to_owned(),
+            device,
+            cmd: context.cmd,
+        };
+        device.set_debug_name(&encoder);
+
+        encoder.begin(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT)?;
+        record(&encoder);
+        encoder.end()?;
 
+        submit_and_wait(device, queue, context.cmd, &context.fence)?;
+        device.return_immediate_context(context);
         Ok(())
     }
 
@@ -302,3 +803,75 @@ impl<'a> ImmediateCommandEncoder<'a> {
 
     pub fn queue(&self) -> Queue { self.queue }
 }
+
+impl Drop for ImmediateCommandEncoder<'_> {
+    fn drop(&mut self) {
+        // The fence is waited on by every submit, so it is never in flight here.
+        if let Some(fence) = self.fence.take()
+            && let Err(err) = self.device.fence_pool().release(fence)
+        {
+            log::error!("ImmediateCommandEncoder: failed to release fence: {:?}", err);
+        }
+    }
+}
+
+/// Command buffer and fence reused by [`ImmediateCommandEncoder::run`], cached on the [`RenderDevice`].
+pub(crate) struct ImmediateContext {
+    pub(crate) queue_family: u32,
+    // Keep the pool alive as long as the command buffer allocated from it.
+    _pool: CommandPool,
+    cmd: vk::CommandBuffer,
+    fence: Fence,
+}
+
+impl ImmediateContext {
+    fn new(device: &RenderDevice, queue_family: u32) -> Result<Self, vk::Result> {
+        let pool = CommandPool::new("command_pool.immediate", device, queue_family, vk::CommandPoolCreateFlags::RESET_COMMAND_BUFFER)?;
+        let cmd = pool.allocate()?;
+        let fence = device.fence_pool().acquire(device)?;
+
+        Ok(Self {
+            queue_family,
+            _pool: pool,
+            cmd,
+            fence,
+        })
+    }
+}
+
+fn submit_and_wait(device: &RenderDevice, queue: Queue, cmd: vk::CommandBuffer, fence: &Fence) -> Result<(), vk::Result> {
+    let cmd_info = vk::CommandBufferSubmitInfo::default().command_buffer(cmd);
+    let submit_info = vk::SubmitInfo2::default()
+        .command_buffer_infos(std::slice::from_ref(&cmd_info));
+
+    unsafe {
+        let fence = fence.handle();
+        device.handle().queue_submit2(queue.handle(), &[submit_info], fence)?;
+        device.handle().wait_for_fences(&[fence], true, u64::MAX)?;
+        device.handle().reset_fences(&[fence])?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::TestDevice;
+
+    #[test]
+    #[ignore = "requires a Vulkan 1.3 device"]
+    fn immediate_submits_reuse_pooled_fences() {
+        let device = TestDevice::new().expect("no Vulkan 1.3 device with a graphics queue");
+
+        for _ in 0..500 {
+            ImmediateCommandEncoder::run(&device, device.graphics_queue(), |_| {}).unwrap();
+        }
+        for _ in 0..500 {
+            let encoder = ImmediateCommandEncoder::new(&device, device.graphics_queue()).unwrap();
+            encoder.submit_and_wait(|_| {}).unwrap();
+        }
+        // One fence cached by the `run` context and one cycled through the pool by the encoders.
+        assert!(device.fence_pool().created_count() <= 2, "created {} fences", device.fence_pool().created_count());
+    }
+}
@@ -3,7 +3,7 @@
 use ash::{vk, Entry, Instance};
 use raw_window_handle::{HasDisplayHandle, RawDisplayHandle};
 use std::ffi::{CStr, CString};
-use anyhow::anyhow;
+use std::sync::{Arc, RwLock};
 use winit::window::Window;
 use zenith_core::log;
 
@@ -23,11 +23,43 @@ const SCORE_VULKAN_1_4: u32 = 600;
 const SCORE_VULKAN_1_3: u32 = 400;
 const SCORE_VULKAN_1_2: u32 = 200;
 
+/// Depth/stencil resolve support, from `VkPhysicalDeviceDepthStencilResolveProperties`.
+#[derive(Debug, Clone, Copy)]
+pub struct DepthStencilResolveSupport {
+    /// `supportedDepthResolveModes`, always includes `SAMPLE_ZERO`.
+    pub depth_modes: vk::ResolveModeFlags,
+    /// `supportedStencilResolveModes`, always includes `SAMPLE_ZERO`.
+    pub stencil_modes: vk::ResolveModeFlags,
+    /// `independentResolveNone`: one aspect can be resolved while the other is not.
+    pub independent_resolve_none: bool,
+    /// `independentResolve`: depth and stencil can be resolved with different modes.
+    pub independent_resolve: bool,
+}
+
+impl DepthStencilResolveSupport {
+    /// Check if the depth aspect of a `format` attachment can be resolved with `mode`, leaving
+    /// its stencil aspect (if any) unresolved.
+    pub fn supports_depth_resolve(&self, mode: vk::ResolveModeFlags, format: vk::Format) -> bool {
+        if mode == vk::ResolveModeFlags::NONE || !self.depth_modes.contains(mode) {
+            return false;
+        }
+        let has_stencil = crate::texture::format_to_aspect_mask(format).contains(vk::ImageAspectFlags::STENCIL);
+        !has_stencil || self.independent_resolve_none || self.independent_resolve
+    }
+}
+
 #[derive(Clone)]
 pub struct PhysicalDevice {
     handle: vk::PhysicalDevice,
     properties: vk::PhysicalDeviceProperties,
     memory_properties: vk::PhysicalDeviceMemoryProperties,
+    /// Supports the descriptor indexing features needed for runtime sized descriptor arrays.
+    supports_descriptor_indexing: bool,
+    /// Supports `VK_EXT_memory_budget`.
+    supports_memory_budget: bool,
+    /// Supports the `depthBounds` feature.
+    supports_depth_bounds: bool,
+    depth_stencil_resolve: DepthStencilResolveSupport,
 
     graphics_queue_family: u32,
     present_queue_family: u32,
@@ -49,15 +81,84 @@ impl PhysicalDevice {
         &self.memory_properties
     }
 
+    /// Get the device name reported by the driver.
+    pub fn name(&self) -> String {
+        unsafe { CStr::from_ptr(self.properties.device_name.as_ptr()) }.to_string_lossy().into_owned()
+    }
+
+    /// Check if runtime sized descriptor arrays with variable descriptor count are supported.
+    pub fn supports_descriptor_indexing(&self) -> bool { self.supports_descriptor_indexing }
+
+    /// Check if per-heap budget and usage can be queried with `VK_EXT_memory_budget`.
+    pub fn supports_memory_budget(&self) -> bool { self.supports_memory_budget }
+
+    /// Check if the depth bounds test can be enabled.
+    pub fn supports_depth_bounds(&self) -> bool { self.supports_depth_bounds }
+
+    /// Resolve modes usable for depth/stencil attachments.
+    pub fn depth_stencil_resolve(&self) -> &DepthStencilResolveSupport { &self.depth_stencil_resolve }
+
     pub fn graphics_queue_family(&self) -> u32 { self.graphics_queue_family }
 
     pub fn present_queue_family(&self) -> u32 { self.present_queue_family }
 }
 
+/// Runtime configuration of the Vulkan instance.
+///
+/// Validation options only take effect when the `validation` cargo feature is enabled.
+#[derive(Debug, Clone, Copy)]
+pub struct RhiCoreConfig {
+    /// Enable the Khronos validation layer and debug messenger.
+    pub validation: bool,
+    /// Enable GPU-assisted validation, requires `validation`.
+    pub gpu_assisted: bool,
+    /// Enable synchronization validation, requires `validation`.
+    pub sync_validation: bool,
+    /// Panic on validation errors when no validation callback is installed.
+    /// The panic can't unwind out of the Vulkan callback, so it aborts the process.
+    pub validation_panic: bool,
+    /// Frames in flight of the render device, each with its own per-frame resources.
+    /// Independent of the swapchain image count, see `SwapchainConfig::num_back_buffers`.
+    pub num_frames: u32,
+}
+
+impl Default for RhiCoreConfig {
+    fn default() -> Self {
+        Self {
+            validation: cfg!(feature = "validation"),
+            gpu_assisted: false,
+            sync_validation: false,
+            validation_panic: false,
+            num_frames: NUM_BACK_BUFFERS,
+        }
+    }
+}
+
+/// Severity of a validation message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+    Info,
+    Verbose,
+}
+
+pub type ValidationCallback = Arc<dyn Fn(Severity, &str) + Send + Sync>;
+
+/// State shared with the debug messenger through its user data pointer.
+#[cfg_attr(not(feature = "validation"), allow(dead_code))]
+struct ValidationHandler {
+    callback: RwLock<Option<ValidationCallback>>,
+    panic_on_error: bool,
+}
+
 /// This is the global entry point for Vulkan initialization.
 pub struct RhiCore {
     entry: Entry,
     instance: Instance,
+    config: RhiCoreConfig,
+    /// Boxed so its address stays stable for the debug messenger.
+    validation_handler: Box<ValidationHandler>,
 
     /// Debug messenger (only in debug builds with validation).
     #[cfg(feature = "validation")]
@@ -69,7 +170,12 @@ pub struct RhiCore {
 impl RhiCore {
     /// Create a new Vulkan core with instance and physical device.
     #[profiling::function]
-    pub fn new(window: &Window) -> Result<Self, anyhow::Error> {
+    pub fn new(window: &Window, config: RhiCoreConfig) -> Result<Self, anyhow::Error> {
+        let config = RhiCoreConfig {
+            validation: config.validation && cfg!(feature = "validation"),
+            ..config
+        };
+
         // Load Vulkan dynamically
         let entry = unsafe { Entry::load()? };
 
@@ -77,15 +183,26 @@ impl RhiCore {
         let display_handle = window.display_handle()?.as_raw();
 
         // Create instance
-        let instance = create_instance(&entry, display_handle)?;
+        let instance = create_instance(&entry, display_handle, &config)?;
+
+        let validation_handler = Box::new(ValidationHandler {
+            callback: RwLock::new(None),
+            panic_on_error: config.validation_panic,
+        });
 
         // Setup debug messenger (validation only)
         #[cfg(feature = "validation")]
-        let (debug_utils, debug_messenger) = setup_debug_messenger(&entry, &instance)?;
+        let (debug_utils, debug_messenger) = if config.validation {
+            setup_debug_messenger(&entry, &instance, &validation_handler)?
+        } else {
+            (None, None)
+        };
 
         Ok(Self {
             entry,
             instance,
+            config,
+            validation_handler,
             #[cfg(feature = "validation")]
             debug_messenger,
             #[cfg(feature = "validation")]
@@ -98,10 +215,22 @@ impl RhiCore {
         RenderDevice::new(
             &self.instance,
             physical_device,
-            NUM_BACK_BUFFERS,
+            self.config.num_frames.max(1),
+            self.config.validation,
         )
     }
 
+    /// Install a callback receiving every validation message, replacing the previous one.
+    /// Messages are still logged.
+    pub fn set_validation_callback(&self, callback: Option<ValidationCallback>) {
+        *self.validation_handler.callback.write().unwrap() = callback;
+    }
+
+    /// Get the config the core was created with, validation is off if unavailable at compile time.
+    pub fn config(&self) -> &RhiCoreConfig {
+        &self.config
+    }
+
     /// Get the entry point.
     pub fn entry(&self) -> &Entry {
         &self.entry
@@ -127,7 +256,7 @@ impl Drop for RhiCore {
 }
 
 /// Get required instance extensions based on platform.
-fn get_required_instance_extensions(display_handle: RawDisplayHandle) -> Vec<*const i8> {
+fn get_required_instance_extensions(display_handle: RawDisplayHandle, validation: bool) -> Vec<*const i8> {
     let mut extensions = vec![
         // Surface extension (always needed)
         ash::khr::surface::NAME.as_ptr(),
@@ -157,14 +286,15 @@ fn get_required_instance_extensions(display_handle: RawDisplayHandle) -> Vec<*co
     }
 
     // Debug utils (for validation layers)
-    #[cfg(feature = "validation")]
-    extensions.push(ash::ext::debug_utils::NAME.as_ptr());
+    if validation {
+        extensions.push(ash::ext::debug_utils::NAME.as_ptr());
+    }
 
     extensions
 }
 
 /// Create Vulkan instance with required extensions and validation layers.
-fn create_instance(entry: &Entry, display_handle: RawDisplayHandle) -> Result<Instance, vk::Result> {
+fn create_instance(entry: &Entry, display_handle: RawDisplayHandle, config: &RhiCoreConfig) -> Result<Instance, vk::Result> {
     let app_name = CString::new("Zenith Engine").unwrap();
     let engine_name = CString::new("Zenith").unwrap();
 
@@ -175,7 +305,8 @@ fn create_instance(entry: &Entry, display_handle: RawDisplayHandle) -> Result<In
         .engine_version(vk::make_api_version(0, 1, 0, 0))
         .api_version(vk::API_VERSION_1_3);
 
-    let extensions = get_required_instance_extensions(display_handle);
+    #[cfg_attr(not(feature = "validation"), allow(unused_mut))]
+    let mut extensions = get_required_instance_extensions(display_handle, config.validation);
 
     #[cfg(feature = "validation")]
     let layer_names: Vec<CString> = VALIDATION_LAYERS
@@ -186,23 +317,62 @@ fn create_instance(entry: &Entry, display_handle: RawDisplayHandle) -> Result<In
     #[cfg(feature = "validation")]
     let layer_pointers: Vec<*const i8> = layer_names.iter().map(|s| s.as_ptr()).collect();
 
+    #[cfg(feature = "validation")]
+    let mut enabled_validation_features = Vec::new();
+    #[cfg(feature = "validation")]
+    if config.validation {
+        if config.gpu_assisted {
+            enabled_validation_features.push(vk::ValidationFeatureEnableEXT::GPU_ASSISTED);
+            enabled_validation_features.push(vk::ValidationFeatureEnableEXT::GPU_ASSISTED_RESERVE_BINDING_SLOT);
+        }
+        if config.sync_validation {
+            enabled_validation_features.push(vk::ValidationFeatureEnableEXT::SYNCHRONIZATION_VALIDATION);
+        }
+
+        if !enabled_validation_features.is_empty() {
+            if supports_validation_features(entry, &layer_names) {
+                extensions.push(ash::ext::validation_features::NAME.as_ptr());
+            } else {
+                log::warn!("VK_EXT_validation_features is unavailable, GPU-assisted and synchronization validation are disabled.");
+                enabled_validation_features.clear();
+            }
+        }
+    }
+    #[cfg(feature = "validation")]
+    let mut validation_features = vk::ValidationFeaturesEXT::default()
+        .enabled_validation_features(&enabled_validation_features);
+
     let mut create_info = vk::InstanceCreateInfo::default()
         .application_info(&app_info)
         .enabled_extension_names(&extensions);
 
     #[cfg(feature = "validation")]
-    {
+    if config.validation {
         create_info = create_info.enabled_layer_names(&layer_pointers);
+        if !enabled_validation_features.is_empty() {
+            create_info = create_info.push_next(&mut validation_features);
+        }
     }
 
     unsafe { entry.create_instance(&create_info, None) }
 }
 
+/// Check whether `VK_EXT_validation_features` is exposed by the loader or one of the `layers`.
+#[cfg(feature = "validation")]
+fn supports_validation_features(entry: &Entry, layers: &[CString]) -> bool {
+    std::iter::once(None)
+        .chain(layers.iter().map(|layer| Some(layer.as_c_str())))
+        .filter_map(|layer| unsafe { entry.enumerate_instance_extension_properties(layer) }.ok())
+        .flatten()
+        .any(|ext| ext.extension_name_as_c_str() == Ok(ash::ext::validation_features::NAME))
+}
+
 /// Setup debug messenger for validation layers.
 #[cfg(feature = "validation")]
 fn setup_debug_messenger(
     entry: &Entry,
     instance: &Instance,
+    handler: &ValidationHandler,
 ) -> Result<(Option<ash::ext::debug_utils::Instance>, Option<vk::DebugUtilsMessengerEXT>), vk::Result> {
     let debug_utils = ash::ext::debug_utils::Instance::new(entry, instance);
 
@@ -217,7 +387,8 @@ fn setup_debug_messenger(
                 | vk::DebugUtilsMessageTypeFlagsEXT::VALIDATION
                 | vk::DebugUtilsMessageTypeFlagsEXT::PERFORMANCE,
         )
-        .pfn_user_callback(Some(vulkan_debug_callback));
+        .pfn_user_callback(Some(vulkan_debug_callback))
+        .user_data(handler as *const ValidationHandler as *mut std::ffi::c_void);
 
     let messenger = unsafe { debug_utils.create_debug_utils_messenger(&create_info, None)? };
 
@@ -230,7 +401,7 @@ unsafe extern "system" fn vulkan_debug_callback(
     message_severity: vk::DebugUtilsMessageSeverityFlagsEXT,
     message_type: vk::DebugUtilsMessageTypeFlagsEXT,
     p_callback_data: *const vk::DebugUtilsMessengerCallbackDataEXT<'_>,
-    _user_data: *mut std::ffi::c_void,
+    user_data: *mut std::ffi::c_void,
 ) -> vk::Bool32 {
     let callback_data = unsafe { *p_callback_data };
     let message = unsafe { CStr::from_ptr(callback_data.p_message) }.to_string_lossy();
@@ -245,7 +416,6 @@ unsafe extern "system" fn vulkan_debug_callback(
     match message_severity {
         vk::DebugUtilsMessageSeverityFlagsEXT::ERROR => {
             log::error!("Vulkan {}: {}", type_str, message);
-            // TODO: break point
         }
         vk::DebugUtilsMessageSeverityFlagsEXT::WARNING => {
             log::warn!("Vulkan {}: {}", type_str, message);
@@ -258,6 +428,28 @@ unsafe extern "system" fn vulkan_debug_callback(
         }
     }
 
+    // SAFETY: user data points to the boxed handler owned by RhiCore, which outlives the messenger.
+    let Some(handler) = (unsafe { (user_data as *const ValidationHandler).as_ref() }) else {
+        return vk::FALSE;
+    };
+
+    let severity = match message_severity {
+        vk::DebugUtilsMessageSeverityFlagsEXT::ERROR => Severity::Error,
+        vk::DebugUtilsMessageSeverityFlagsEXT::WARNING => Severity::Warning,
+        vk::DebugUtilsMessageSeverityFlagsEXT::INFO => Severity::Info,
+        _ => Severity::Verbose,
+    };
+
+    // Cloned out of the lock, so the callback can replace itself without deadlocking.
+    let callback = handler.callback.read().unwrap().clone();
+    match callback {
+        Some(callback) => callback(severity, &message),
+        None if severity == Severity::Error && handler.panic_on_error => {
+            panic!("Vulkan validation error: {}", message);
+        }
+        None => {}
+    }
+
     vk::FALSE
 }
 
@@ -346,51 +538,241 @@ fn score_physical_device(
     score
 }
 
-/// Select the best physical device.
-pub fn select_physical_device(
+/// How to pick the physical device.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DeviceSelector {
+    /// Highest scoring device.
+    Auto,
+    /// Device at this index of the enumeration order.
+    Index(usize),
+    /// First device whose name contains this string (case insensitive).
+    NameContains(String),
+}
+
+impl DeviceSelector {
+    /// Environment variable overriding the device selection, either an index or part of the device name.
+    pub const ENV_VAR: &'static str = "ZENITH_GPU";
+
+    /// Read the selector from [`Self::ENV_VAR`], `Auto` if unset or empty.
+    pub fn from_env() -> Self {
+        match std::env::var(Self::ENV_VAR) {
+            Ok(value) if !value.trim().is_empty() => {
+                let value = value.trim();
+                value.parse::<usize>()
+                    .map(DeviceSelector::Index)
+                    .unwrap_or_else(|_| DeviceSelector::NameContains(value.to_owned()))
+            }
+            _ => DeviceSelector::Auto,
+        }
+    }
+}
+
+/// Physical device with its selection score, a score of zero means unusable.
+#[derive(Clone)]
+pub struct ScoredPhysicalDevice {
+    pub device: PhysicalDevice,
+    pub score: u32,
+}
+
+/// Query the properties and features of `device` used by the render device.
+fn describe_physical_device(
+    instance: &Instance,
+    device: vk::PhysicalDevice,
+    graphics_queue_family: u32,
+    present_queue_family: u32,
+) -> Result<PhysicalDevice, vk::Result> {
+    let properties = unsafe { instance.get_physical_device_properties(device) };
+    let memory_properties =
+        unsafe { instance.get_physical_device_memory_properties(device) };
+
+    let mut vulkan_12_features = vk::PhysicalDeviceVulkan12Features::default();
+    let mut features = vk::PhysicalDeviceFeatures2::default().push_next(&mut vulkan_12_features);
+    unsafe { instance.get_physical_device_features2(device, &mut features) };
+    let supports_depth_bounds = features.features.depth_bounds == vk::TRUE;
+    let supports_descriptor_indexing = vulkan_12_features.runtime_descriptor_array == vk::TRUE
+        && vulkan_12_features.descriptor_binding_variable_descriptor_count == vk::TRUE
+        && vulkan_12_features.descriptor_binding_partially_bound == vk::TRUE;
+
+    let mut resolve_properties = vk::PhysicalDeviceDepthStencilResolveProperties::default();
+    let mut properties2 = vk::PhysicalDeviceProperties2::default().push_next(&mut resolve_properties);
+    unsafe { instance.get_physical_device_properties2(device, &mut properties2) };
+    let depth_stencil_resolve = DepthStencilResolveSupport {
+        depth_modes: resolve_properties.supported_depth_resolve_modes,
+        stencil_modes: resolve_properties.supported_stencil_resolve_modes,
+        independent_resolve_none: resolve_properties.independent_resolve_none == vk::TRUE,
+        independent_resolve: resolve_properties.independent_resolve == vk::TRUE,
+    };
+
+    let supports_memory_budget = unsafe { instance.enumerate_device_extension_properties(device)? }
+        .iter()
+        .any(|ext| ext.extension_name_as_c_str() == Ok(ash::ext::memory_budget::NAME));
+
+    Ok(PhysicalDevice {
+        handle: device,
+        properties,
+        memory_properties,
+        supports_descriptor_indexing,
+        supports_memory_budget,
+        supports_depth_bounds,
+        depth_stencil_resolve,
+        graphics_queue_family,
+        present_queue_family,
+    })
+}
+
+/// Enumerate all physical devices with their selection scores, in enumeration order.
+pub fn scored_physical_devices(
     instance: &Instance,
     swapchain_window: &SwapchainWindow,
-) -> Result<PhysicalDevice, anyhow::Error> {
+) -> Result<Vec<ScoredPhysicalDevice>, anyhow::Error> {
     let physical_devices = unsafe { instance.enumerate_physical_devices()? };
 
     if physical_devices.is_empty() {
         return Err(anyhow::anyhow!("No Vulkan-capable GPU found"));
     }
 
-    let mut best_device = None;
-    let mut best_device_score = 0u32;
+    let mut scored = Vec::with_capacity(physical_devices.len());
 
     for device in physical_devices {
-        let properties = unsafe { instance.get_physical_device_properties(device) };
-        let memory_properties =
-            unsafe { instance.get_physical_device_memory_properties(device) };
-
         let (graphics_family, present_family) = find_queue_families(instance, device, swapchain_window);
-        let graphics_queue_family = graphics_family.ok_or(anyhow!("Invalid graphic queue family."))?;
-        let present_queue_family = present_family.ok_or(anyhow!("Invalid graphic queue family."))?;
-
         let has_required_queues = graphics_family.is_some() && present_family.is_some();
-        let score = score_physical_device(&properties, &memory_properties, has_required_queues);
 
-        let device_name = unsafe { CStr::from_ptr(properties.device_name.as_ptr()).to_string_lossy() };
+        // Devices without graphics or present queues stay listed with score 0 so they are never selected.
+        let device = describe_physical_device(instance, device, graphics_family.unwrap_or(0), present_family.unwrap_or(0))?;
+        if !has_required_queues {
+            log::warn!(
+                "GPU {} lacks a {} queue family.",
+                device.name(),
+                if graphics_family.is_none() { "graphics" } else { "present" }
+            );
+        }
+
+        let score = score_physical_device(&device.properties, &device.memory_properties, has_required_queues);
 
         log::info!(
             "Found GPU: {} (score: {}, type: {:?})",
-            device_name, score, properties.device_type
+            device.name(), score, device.properties.device_type
         );
 
-        if score > best_device_score {
-            best_device = Some(PhysicalDevice {
-                handle: device,
-                properties,
-                memory_properties,
-                graphics_queue_family,
-                present_queue_family,
+        scored.push(ScoredPhysicalDevice { device, score });
+    }
+
+    Ok(scored)
+}
+
+/// Select the best physical device, unless overridden by the `ZENITH_GPU` environment variable.
+pub fn select_physical_device(
+    instance: &Instance,
+    swapchain_window: &SwapchainWindow,
+) -> Result<PhysicalDevice, anyhow::Error> {
+    select_physical_device_by(instance, swapchain_window, DeviceSelector::from_env())
+}
+
+/// Select a physical device with `selector`, falling back to the best scoring device if nothing matches.
+pub fn select_physical_device_by(
+    instance: &Instance,
+    swapchain_window: &SwapchainWindow,
+    selector: DeviceSelector,
+) -> Result<PhysicalDevice, anyhow::Error> {
+    let scored = scored_physical_devices(instance, swapchain_window)?;
+
+    let selected = match &selector {
+        DeviceSelector::Auto => None,
+        DeviceSelector::Index(index) => scored.get(*index),
+        DeviceSelector::NameContains(name) => {
+            let name = name.to_lowercase();
+            scored.iter().find(|scored| scored.device.name().to_lowercase().contains(&name))
+        }
+    };
+
+    match selected {
+        Some(selected) if selected.score > 0 => return Ok(selected.device.clone()),
+        Some(selected) => log::warn!("GPU override {:?} selects unusable device {}, falling back to auto selection.", selector, selected.device.name()),
+        None if selector != DeviceSelector::Auto => log::warn!("GPU override {:?} matches no device, falling back to auto selection.", selector),
+        None => {}
+    }
+
+    scored
+        .into_iter()
+        .filter(|scored| scored.score > 0)
+        // Reversed so ties keep the first enumerated device.
+        .rev()
+        .max_by_key(|scored| scored.score)
+        .map(|scored| scored.device)
+        .ok_or_else(|| anyhow::anyhow!("No suitable GPU found"))
+}
+
+
+/// Headless render device for tests that need a GPU.
+///
+/// Such tests are `#[ignore]`d, run them on a machine with a GPU via `cargo test -- --ignored`.
+#[cfg(test)]
+pub(crate) struct TestDevice {
+    device: std::mem::ManuallyDrop<RenderDevice>,
+    instance: Instance,
+    _entry: Entry,
+}
+
+#[cfg(test)]
+impl TestDevice {
+    /// Create a device on the first Vulkan 1.3 GPU with a graphics queue, `None` if there is none.
+    pub(crate) fn new() -> Option<Self> {
+        let entry = unsafe { Entry::load() }.ok()?;
+        let app_info = vk::ApplicationInfo::default().api_version(vk::API_VERSION_1_3);
+        let create_info = vk::InstanceCreateInfo::default().application_info(&app_info);
+        let instance = unsafe { entry.create_instance(&create_info, None) }.ok()?;
+
+        let physical_device = unsafe { instance.enumerate_physical_devices() }
+            .unwrap_or_default()
+            .into_iter()
+            .find_map(|device| {
+                let properties = unsafe { instance.get_physical_device_properties(device) };
+                if properties.api_version < vk::API_VERSION_1_3 {
+                    return None;
+                }
+                let graphics_family = unsafe { instance.get_physical_device_queue_family_properties(device) }
+                    .iter()
+                    .position(|family| family.queue_flags.contains(vk::QueueFlags::GRAPHICS))? as u32;
+                describe_physical_device(&instance, device, graphics_family, graphics_family).ok()
             });
-            best_device_score = score;
+
+        match physical_device.map(|physical_device| RenderDevice::new(&instance, &physical_device, 1, false)) {
+            Some(Ok(device)) => Some(Self {
+                device: std::mem::ManuallyDrop::new(device),
+                instance,
+                _entry: entry,
+            }),
+            _ => {
+                unsafe { instance.destroy_instance(None) };
+                None
+            }
         }
     }
+}
+
+#[cfg(test)]
+impl std::ops::Deref for TestDevice {
+    type Target = RenderDevice;
+
+    fn deref(&self) -> &RenderDevice {
+        &self.device
+    }
+}
 
-    best_device.ok_or_else(|| anyhow::anyhow!("No suitable GPU found"))
+#[cfg(test)]
+impl std::ops::DerefMut for TestDevice {
+    fn deref_mut(&mut self) -> &mut RenderDevice {
+        &mut self.device
+    }
 }
 
+#[cfg(test)]
+impl Drop for TestDevice {
+    fn drop(&mut self) {
+        unsafe {
+            // The device must go before the instance it was created from.
+            std::mem::ManuallyDrop::drop(&mut self.device);
+            self.instance.destroy_instance(None);
+        }
+    }
+}
@@ -4,13 +4,14 @@ use ash::{vk};
 use std::collections::HashMap;
 use std::default::Default;
 use zenith_core::collections::SmallVec;
+use zenith_core::log;
 use zenith_rhi_derive::DeviceObject;
 use crate::buffer::BufferRange;
 use crate::{GraphicPipeline, RenderDevice, Sampler};
 use crate::device::DebuggableObject;
 use crate::device::set_debug_name_handle;
 use crate::shader::{ShaderBinding, ShaderReflection};
-use crate::texture::TextureRange;
+use crate::texture::{Texture, TextureRange};
 
 /// Descriptor binding validation error.
 #[derive(Debug)]
@@ -58,6 +59,9 @@ pub struct LayoutBinding {
     pub descriptor_type: vk::DescriptorType,
     pub count: u32,
     pub stage_flags: vk::ShaderStageFlags,
+    /// Runtime sized array, `count` is the upper bound and sets are allocated with that many descriptors.
+    /// Must be the highest binding of the layout.
+    pub variable_count: bool,
 }
 
 /// Descriptor set layout with binding metadata for validation.
@@ -83,7 +87,26 @@ impl DescriptorSetLayout {
             })
             .collect();
 
-        let create_info = vk::DescriptorSetLayoutCreateInfo::default().bindings(&vk_bindings);
+        let binding_flags: Vec<vk::DescriptorBindingFlags> = bindings
+            .iter()
+            .map(|b| if b.variable_count {
+                vk::DescriptorBindingFlags::VARIABLE_DESCRIPTOR_COUNT | vk::DescriptorBindingFlags::PARTIALLY_BOUND
+            } else {
+                vk::DescriptorBindingFlags::empty()
+            })
+            .collect();
+        let mut binding_flags_info = vk::DescriptorSetLayoutBindingFlagsCreateInfo::default()
+            .binding_flags(&binding_flags);
+
+        let mut create_info = vk::DescriptorSetLayoutCreateInfo::default().bindings(&vk_bindings);
+        if let Some(variable) = bindings.iter().find(|b| b.variable_count) {
+            debug_assert!(
+                bindings.iter().all(|b| b.binding <= variable.binding),
+                "DescriptorSetLayout '{name}': runtime sized binding {} must be the highest binding",
+                variable.binding,
+            );
+            create_info = create_info.push_next(&mut binding_flags_info);
+        }
 
         let layout = unsafe { device.handle().create_descriptor_set_layout(&create_info, None)? };
 
@@ -113,13 +136,26 @@ impl DescriptorSetLayout {
         let bindings: Vec<LayoutBinding> = shader_bindings
             .iter()
             .filter(|b| b.set == set_index)
-            .map(|b| LayoutBinding {
-                binding: b.binding,
-                descriptor_type: b.descriptor_type,
-                count: b.count,
-                stage_flags: b.stage_flags,
+            .map(|b| {
+                // Unbounded arrays are reflected with a count of u32::MAX.
+                let variable_count = b.count == u32::MAX;
+                if variable_count && !device.descriptor_indexing_enabled() {
+                    log::error!(
+                        "Binding '{}' (set {}, binding {}) is a runtime sized array, but descriptor indexing is not enabled on this device.",
+                        b.name, b.set, b.binding
+                    );
+                    return Err(vk::Result::ERROR_FEATURE_NOT_PRESENT);
+                }
+
+                Ok(LayoutBinding {
+                    binding: b.binding,
+                    descriptor_type: b.descriptor_type,
+                    count: if variable_count { device.max_variable_descriptor_count(b.descriptor_type) } else { b.count },
+                    stage_flags: b.stage_flags,
+                    variable_count,
+                })
             })
-            .collect();
+            .collect::<Result<_, _>>()?;
 
         let layout = Self::new(name, device, &bindings)?;
         device.set_debug_name(&layout);
@@ -143,6 +179,11 @@ impl DescriptorSetLayout {
     pub fn bindings(&self) -> &[LayoutBinding] {
         &self.bindings
     }
+
+    /// Get the runtime sized binding of this layout, if any.
+    pub fn variable_binding(&self) -> Option<&LayoutBinding> {
+        self.bindings.iter().find(|b| b.variable_count)
+    }
 }
 
 impl Drop for DescriptorSetLayout {
@@ -203,12 +244,7 @@ impl DescriptorPool {
 
     /// Allocate a single descriptor set.
     pub fn allocate(&self, layout: &DescriptorSetLayout) -> Result<vk::DescriptorSet, vk::Result> {
-        let layouts = [layout.handle()];
-        let alloc_info = vk::DescriptorSetAllocateInfo::default()
-            .descriptor_pool(self.pool)
-            .set_layouts(&layouts);
-
-        let sets = unsafe { self.device.allocate_descriptor_sets(&alloc_info)? };
+        let sets = self.allocate_many(layout, 1)?;
         Ok(sets[0])
     }
 
@@ -219,9 +255,18 @@ impl DescriptorPool {
         count: u32,
     ) -> Result<Vec<vk::DescriptorSet>, vk::Result> {
         let layouts: Vec<vk::DescriptorSetLayout> = (0..count).map(|_| layout.handle()).collect();
-        let alloc_info = vk::DescriptorSetAllocateInfo::default()
+        let variable_counts: Vec<u32> = layout.variable_binding()
+            .map(|b| vec![b.count; count as usize])
+            .unwrap_or_default();
+        let mut variable_count_info = vk::DescriptorSetVariableDescriptorCountAllocateInfo::default()
+            .descriptor_counts(&variable_counts);
+
+        let mut alloc_info = vk::DescriptorSetAllocateInfo::default()
             .descriptor_pool(self.pool)
             .set_layouts(&layouts);
+        if !variable_counts.is_empty() {
+            alloc_info = alloc_info.push_next(&mut variable_count_info);
+        }
 
         unsafe { self.device.allocate_descriptor_sets(&alloc_info) }
     }
@@ -264,12 +309,36 @@ impl DebuggableObject for DescriptorPool {
     }
 }
 
+/// Minimum offset alignment of a buffer bound as `descriptor_type`.
+fn buffer_offset_alignment(device: &RenderDevice, descriptor_type: vk::DescriptorType) -> vk::DeviceSize {
+    let limits = device.limits();
+    match descriptor_type {
+        vk::DescriptorType::UNIFORM_BUFFER | vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC => limits.min_uniform_buffer_offset_alignment,
+        vk::DescriptorType::STORAGE_BUFFER | vk::DescriptorType::STORAGE_BUFFER_DYNAMIC => limits.min_storage_buffer_offset_alignment,
+        _ => 1,
+    }
+}
+
+/// Round `offset` up so a buffer range starting there can be bound as both a uniform and a storage buffer.
+///
+/// Use it when sub-allocating several bindings from one buffer: both limits are powers of two,
+/// so the larger one satisfies either descriptor type.
+pub fn align_offset(offset: vk::DeviceSize, device: &RenderDevice) -> vk::DeviceSize {
+    let limits = device.limits();
+    let alignment = limits.min_uniform_buffer_offset_alignment
+        .max(limits.min_storage_buffer_offset_alignment)
+        .max(1);
+    offset.next_multiple_of(alignment)
+}
+
 /// Error type for shader resource binding.
 #[derive(Debug)]
 pub enum ShaderBindingError {
     BindingNotFound(String),
     TypeMismatch { name: String, expected: vk::DescriptorType, got: vk::DescriptorType },
+    ArrayIndexOutOfBounds { name: String, index: u32, count: u32 },
     AllocationFailed(vk::Result),
+    ViewCreationFailed(vk::Result),
 }
 
 impl std::fmt::Display for ShaderBindingError {
@@ -279,7 +348,11 @@ impl std::fmt::Display for ShaderBindingError {
             ShaderBindingError::TypeMismatch { name, expected, got } => {
                 write!(f, "Type mismatch for '{}': expected {:?}, got {:?}", name, expected, got)
             }
+            ShaderBindingError::ArrayIndexOutOfBounds { name, index, count } => {
+                write!(f, "Array index {} out of bounds for '{}' (count: {})", index, name, count)
+            }
             ShaderBindingError::AllocationFailed(e) => write!(f, "Descriptor set allocation failed: {:?}", e),
+            ShaderBindingError::ViewCreationFailed(e) => write!(f, "Image view creation failed: {:?}", e),
         }
     }
 }
@@ -289,18 +362,32 @@ impl std::error::Error for ShaderBindingError {}
 struct PendingWrite {
     set_index: u32,
     binding: u32,
+    array_element: u32,
     descriptor_type: vk::DescriptorType,
     buffer_info: Option<vk::DescriptorBufferInfo>,
     image_info: Option<vk::DescriptorImageInfo>,
 }
 
+/// [`DescriptorSetBinder`] state of a binder from [`DescriptorSetBinder::new`], whose sets are
+/// allocated from a pool sized for the bound resources on finish.
+pub struct AllocateOnFinish;
+
+/// [`DescriptorSetBinder`] state of a binder from [`DescriptorSetBinder::for_pipeline`],
+/// holding the sets allocated up front, one per pipeline descriptor set layout.
+pub struct Preallocated(Vec<vk::DescriptorSet>);
+
 /// Shader resource binder that binds resources by name using shader reflection.
-pub struct DescriptorSetBinder<'a> {
+///
+/// Created with [`Self::new`] the sets are allocated from a pool sized for the bound resources
+/// on finish. Created with [`Self::for_pipeline`] the sets are allocated up front from the given
+/// pool, and finish only writes and returns them.
+pub struct DescriptorSetBinder<'a, S = AllocateOnFinish> {
     device: &'a RenderDevice,
     pipeline: &'a GraphicPipeline,
     reflection: &'a ShaderReflection,
     resource_ty_sizes: HashMap<vk::DescriptorType, u32>,
     pending_writes: Vec<PendingWrite>,
+    sets: S,
 }
 
 impl<'a> DescriptorSetBinder<'a> {
@@ -316,9 +403,68 @@ impl<'a> DescriptorSetBinder<'a> {
             reflection,
             resource_ty_sizes: Default::default(),
             pending_writes: Vec::new(),
+            sets: AllocateOnFinish,
         })
     }
 
+    /// Create a binder allocating one set per descriptor set layout of `pipeline` from `pool`,
+    /// resolving names with the pipeline's merged reflection.
+    pub fn for_pipeline(
+        device: &'a RenderDevice,
+        pipeline: &'a GraphicPipeline,
+        pool: &DescriptorPool,
+    ) -> Result<DescriptorSetBinder<'a, Preallocated>, ShaderBindingError> {
+        let sets = pipeline.descriptor_layouts.iter()
+            .map(|layout| pool.allocate(layout))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(ShaderBindingError::AllocationFailed)?;
+
+        Ok(DescriptorSetBinder {
+            device,
+            pipeline,
+            reflection: pipeline.reflection(),
+            resource_ty_sizes: Default::default(),
+            pending_writes: Vec::new(),
+            sets: Preallocated(sets),
+        })
+    }
+
+    /// Finish binding and return the descriptor sets for binding to the pipeline.
+    pub fn finish(mut self) -> (DescriptorPool, Vec<vk::DescriptorSet>) {
+        // Runtime sized arrays are allocated with their full count.
+        for binding in self.pipeline.descriptor_layouts.iter().filter_map(|layout| layout.variable_binding()) {
+            *self.resource_ty_sizes.entry(binding.descriptor_type).or_insert(0) += binding.count;
+        }
+
+        let pool_sizes = self.resource_ty_sizes.iter()
+            .map(|(&ty, &descriptor_count)| vk::DescriptorPoolSize {
+                ty,
+                descriptor_count,
+            })
+            .collect::<Vec<_>>();
+
+        let pool = DescriptorPool::new("descriptor_pool", self.device, self.pipeline.descriptor_layouts.len() as _, &pool_sizes).unwrap();
+        let descriptor_sets = self.pipeline.descriptor_layouts.iter()
+            .map(|layout| {
+                pool.allocate(layout).map_err(ShaderBindingError::AllocationFailed).unwrap()
+            })
+            .collect::<Vec<_>>();
+
+        self.write_sets(&descriptor_sets);
+        (pool, descriptor_sets)
+    }
+}
+
+impl DescriptorSetBinder<'_, Preallocated> {
+    /// Write the bound resources into the sets allocated by [`DescriptorSetBinder::for_pipeline`]
+    /// and return them, indexed by set, for binding to the pipeline.
+    pub fn finish(self) -> Vec<vk::DescriptorSet> {
+        self.write_sets(&self.sets.0);
+        self.sets.0
+    }
+}
+
+impl<'a, S> DescriptorSetBinder<'a, S> {
     /// Bind a buffer by shader name.
     pub fn bind_buffer(
         &mut self,
@@ -327,7 +473,98 @@ impl<'a> DescriptorSetBinder<'a> {
     ) -> Result<&mut Self, ShaderBindingError> {
         let binding = self.reflection.find_binding(name)
             .ok_or_else(|| ShaderBindingError::BindingNotFound(name.to_string()))?;
+        self.push_buffer(binding, 0, buffer)
+    }
+
+    /// Bind a buffer to element `index` of a buffer array by shader name.
+    pub fn bind_buffer_element(
+        &mut self,
+        name: &str,
+        index: u32,
+        buffer: BufferRange,
+    ) -> Result<&mut Self, ShaderBindingError> {
+        let binding = self.reflection.find_binding(name)
+            .ok_or_else(|| ShaderBindingError::BindingNotFound(name.to_string()))?;
+        self.push_buffer(binding, index, buffer)
+    }
+
+    /// Bind a buffer by set and binding index.
+    pub fn bind_buffer_at(
+        &mut self,
+        set: u32,
+        binding: u32,
+        buffer: BufferRange,
+    ) -> Result<&mut Self, ShaderBindingError> {
+        let binding = self.reflection.find_binding_at(set, binding)
+            .ok_or_else(|| ShaderBindingError::BindingNotFound(format!("(set {}, binding {})", set, binding)))?;
+        self.push_buffer(binding, 0, buffer)
+    }
+
+    /// Bind a combined image sampler by name.
+    pub fn bind_texture(
+        &mut self,
+        name: &str,
+        texture: TextureRange<'a>,
+        sampler: &'a Sampler,
+        layout: vk::ImageLayout,
+    ) -> Result<&mut Self, ShaderBindingError> {
+        let binding = self.reflection.find_binding(name)
+            .ok_or_else(|| ShaderBindingError::BindingNotFound(name.to_string()))?;
+        self.push_texture(binding, 0, texture, sampler, layout)
+    }
 
+    /// Bind a combined image sampler to element `index` of a texture array by name.
+    pub fn bind_texture_element(
+        &mut self,
+        name: &str,
+        index: u32,
+        texture: TextureRange<'a>,
+        sampler: &'a Sampler,
+        layout: vk::ImageLayout,
+    ) -> Result<&mut Self, ShaderBindingError> {
+        let binding = self.reflection.find_binding(name)
+            .ok_or_else(|| ShaderBindingError::BindingNotFound(name.to_string()))?;
+        self.push_texture(binding, index, texture, sampler, layout)
+    }
+
+    /// Bind a combined image sampler by set and binding index.
+    pub fn bind_texture_at(
+        &mut self,
+        set: u32,
+        binding: u32,
+        texture: TextureRange<'a>,
+        sampler: &'a Sampler,
+        layout: vk::ImageLayout,
+    ) -> Result<&mut Self, ShaderBindingError> {
+        let binding = self.reflection.find_binding_at(set, binding)
+            .ok_or_else(|| ShaderBindingError::BindingNotFound(format!("(set {}, binding {})", set, binding)))?;
+        self.push_texture(binding, 0, texture, sampler, layout)
+    }
+
+    /// Check `index` against the descriptor count of the binding, taken from the pipeline layout
+    /// so runtime sized arrays use their allocated count.
+    fn check_array_index(&self, binding: &ShaderBinding, index: u32) -> Result<(), ShaderBindingError> {
+        let count = self.pipeline.descriptor_layouts
+            .get(binding.set as usize)
+            .and_then(|layout| layout.get_binding(binding.binding))
+            .map_or(binding.count, |layout_binding| layout_binding.count);
+
+        if index >= count {
+            return Err(ShaderBindingError::ArrayIndexOutOfBounds {
+                name: binding.name.clone(),
+                index,
+                count,
+            });
+        }
+        Ok(())
+    }
+
+    fn push_buffer(
+        &mut self,
+        binding: &ShaderBinding,
+        array_element: u32,
+        buffer: BufferRange,
+    ) -> Result<&mut Self, ShaderBindingError> {
         let is_buffer_type = matches!(
             binding.descriptor_type,
             vk::DescriptorType::UNIFORM_BUFFER
@@ -338,17 +575,29 @@ impl<'a> DescriptorSetBinder<'a> {
 
         if !is_buffer_type {
             return Err(ShaderBindingError::TypeMismatch {
-                name: name.to_string(),
+                name: binding.name.clone(),
                 expected: binding.descriptor_type,
                 got: vk::DescriptorType::UNIFORM_BUFFER,
             });
         }
+        self.check_array_index(binding, array_element)?;
+
+        let buffer_info = buffer.to_binding();
+        debug_assert!(
+            buffer_info.offset.is_multiple_of(buffer_offset_alignment(self.device, binding.descriptor_type)),
+            "bind_buffer: offset {} of '{}' is not aligned to the device's min {:?} offset alignment {}, use align_offset",
+            buffer_info.offset,
+            binding.name,
+            binding.descriptor_type,
+            buffer_offset_alignment(self.device, binding.descriptor_type),
+        );
 
         self.pending_writes.push(PendingWrite {
             set_index: binding.set,
             binding: binding.binding,
+            array_element,
             descriptor_type: binding.descriptor_type,
-            buffer_info: Some(buffer.to_binding()),
+            buffer_info: Some(buffer_info),
             image_info: None,
         });
 
@@ -356,17 +605,14 @@ impl<'a> DescriptorSetBinder<'a> {
         Ok(self)
     }
 
-    /// Bind a combined image sampler by name.
-    pub fn bind_texture(
+    fn push_texture(
         &mut self,
-        name: &str,
+        binding: &ShaderBinding,
+        array_element: u32,
         texture: TextureRange<'a>,
         sampler: &'a Sampler,
         layout: vk::ImageLayout,
     ) -> Result<&mut Self, ShaderBindingError> {
-        let binding = self.reflection.find_binding(name)
-            .ok_or_else(|| ShaderBindingError::BindingNotFound(name.to_string()))?;
-
         let is_image_type = matches!(
             binding.descriptor_type,
             vk::DescriptorType::COMBINED_IMAGE_SAMPLER
@@ -376,39 +622,136 @@ impl<'a> DescriptorSetBinder<'a> {
 
         if !is_image_type {
             return Err(ShaderBindingError::TypeMismatch {
-                name: name.to_string(),
+                name: binding.name.clone(),
                 expected: binding.descriptor_type,
                 got: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
             });
         }
+        self.check_array_index(binding, array_element)?;
 
         self.pending_writes.push(PendingWrite {
             set_index: binding.set,
             binding: binding.binding,
+            array_element,
             descriptor_type: binding.descriptor_type,
             buffer_info: None,
             image_info: Some(texture.to_binding(sampler, layout)),
         });
 
         *self.resource_ty_sizes.entry(binding.descriptor_type).or_insert(0) += 1;
+
+        // A separate sampled image ignores the sampler, write it to the paired sampler binding instead.
+        // Without one, bind_sampler must be called, which `write_sets` checks in debug builds.
+        if let Some(sampler_binding) = self.reflection.paired_sampler(binding) {
+            self.push_sampler(sampler_binding, array_element, sampler)?;
+        }
         Ok(self)
     }
 
-    /// Finish binding and return the descriptor sets for binding to the pipeline.
-    pub fn finish(self) -> (DescriptorPool, Vec<vk::DescriptorSet>) {
-        let pool_sizes = self.resource_ty_sizes.into_iter()
-            .map(|(ty, descriptor_count)| vk::DescriptorPoolSize {
-                ty,
-                descriptor_count,
-            })
-            .collect::<Vec<_>>();
+    /// Bind a separate sampler (`SamplerState` in Slang) by name.
+    ///
+    /// Samplers paired with a sampled image are bound by [`Self::bind_texture`] already,
+    /// see [`ShaderReflection::paired_sampler`]. A separate sampled image without a paired sampler
+    /// requires its sampler to be bound here.
+    pub fn bind_sampler(
+        &mut self,
+        name: &str,
+        sampler: &'a Sampler,
+    ) -> Result<&mut Self, ShaderBindingError> {
+        let binding = self.reflection.find_binding(name)
+            .ok_or_else(|| ShaderBindingError::BindingNotFound(name.to_string()))?;
+        self.push_sampler(binding, 0, sampler)
+    }
 
-        let pool = DescriptorPool::new("descriptor_pool", self.device, self.pipeline.descriptor_layouts.len() as _, &pool_sizes).unwrap();
-        let descriptor_sets = self.pipeline.descriptor_layouts.iter()
-            .map(|layout| {
-                pool.allocate(layout).map_err(ShaderBindingError::AllocationFailed).unwrap()
-            })
-            .collect::<Vec<_>>();
+    fn push_sampler(
+        &mut self,
+        binding: &ShaderBinding,
+        array_element: u32,
+        sampler: &'a Sampler,
+    ) -> Result<&mut Self, ShaderBindingError> {
+        if binding.descriptor_type != vk::DescriptorType::SAMPLER {
+            return Err(ShaderBindingError::TypeMismatch {
+                name: binding.name.clone(),
+                expected: binding.descriptor_type,
+                got: vk::DescriptorType::SAMPLER,
+            });
+        }
+        self.check_array_index(binding, array_element)?;
+
+        self.pending_writes.push(PendingWrite {
+            set_index: binding.set,
+            binding: binding.binding,
+            array_element,
+            descriptor_type: binding.descriptor_type,
+            buffer_info: None,
+            image_info: Some(vk::DescriptorImageInfo::default().sampler(sampler.handle())),
+        });
+
+        *self.resource_ty_sizes.entry(binding.descriptor_type).or_insert(0) += 1;
+        Ok(self)
+    }
+
+    /// Bind a single mip level of a texture as a storage image by name.
+    /// The image is expected to be in `GENERAL` layout when accessed.
+    pub fn bind_storage_image(
+        &mut self,
+        name: &str,
+        texture: &'a Texture,
+        mip_level: u32,
+    ) -> Result<&mut Self, ShaderBindingError> {
+        let binding = self.reflection.find_binding(name)
+            .ok_or_else(|| ShaderBindingError::BindingNotFound(name.to_string()))?;
+
+        if binding.descriptor_type != vk::DescriptorType::STORAGE_IMAGE {
+            return Err(ShaderBindingError::TypeMismatch {
+                name: name.to_string(),
+                expected: binding.descriptor_type,
+                got: vk::DescriptorType::STORAGE_IMAGE,
+            });
+        }
+
+        let image_view = texture
+            .as_range(mip_level..mip_level + 1, 0..texture.desc().array_layers)
+            .and_then(|range| range.view())
+            .map_err(ShaderBindingError::ViewCreationFailed)?;
+
+        self.pending_writes.push(PendingWrite {
+            set_index: binding.set,
+            binding: binding.binding,
+            array_element: 0,
+            descriptor_type: binding.descriptor_type,
+            buffer_info: None,
+            image_info: Some(
+                vk::DescriptorImageInfo::default()
+                    .image_view(image_view)
+                    .sampler(vk::Sampler::null())
+                    .image_layout(vk::ImageLayout::GENERAL),
+            ),
+        });
+
+        *self.resource_ty_sizes.entry(binding.descriptor_type).or_insert(0) += 1;
+        Ok(self)
+    }
+
+    /// Check that every bound separate sampled image has a sampler to be sampled with.
+    #[cfg(debug_assertions)]
+    fn check_samplers_bound(&self) {
+        let has_sampler = self.pending_writes.iter()
+            .any(|write| write.descriptor_type == vk::DescriptorType::SAMPLER);
+        if has_sampler {
+            return;
+        }
+
+        if let Some(image) = self.pending_writes.iter().find(|write| write.descriptor_type == vk::DescriptorType::SAMPLED_IMAGE) {
+            let name = self.reflection.find_binding_at(image.set_index, image.binding)
+                .map_or("<unknown>", |binding| binding.name.as_str());
+            panic!("DescriptorSetBinder: separate sampled image '{}' is bound without a sampler, bind one with bind_sampler", name);
+        }
+    }
+
+    fn write_sets(&self, descriptor_sets: &[vk::DescriptorSet]) {
+        #[cfg(debug_assertions)]
+        self.check_samplers_bound();
 
         let mut buffer_infos: SmallVec<[vk::DescriptorBufferInfo; 8]> = SmallVec::new();
         let mut image_infos: SmallVec<[vk::DescriptorImageInfo; 8]> = SmallVec::new();
@@ -430,7 +773,7 @@ impl<'a> DescriptorSetBinder<'a> {
             let mut write = vk::WriteDescriptorSet::default()
                 .dst_set(descriptor_sets[pending.set_index as usize])
                 .dst_binding(pending.binding)
-                .dst_array_element(0)
+                .dst_array_element(pending.array_element)
                 .descriptor_type(pending.descriptor_type);
 
             if pending.buffer_info.is_some() {
@@ -450,7 +793,5 @@ impl<'a> DescriptorSetBinder<'a> {
                 self.device.handle().update_descriptor_sets(&writes, &[]);
             }
         }
-
-        (pool, descriptor_sets)
     }
 }
@@ -1,17 +1,23 @@
 //! Vulkan Device - logical device and queue management.
 
+use zenith_core::log;
 use crate::core::PhysicalDevice;
 use crate::defer_release::{DeferRelease, DeferReleaseQueue};
+use crate::memory::{HeapBudget, MemoryUsageTracker, TrackedAllocation};
+#[cfg(feature = "allocator")]
+use crate::memory::{AllocatorStats, MemoryAllocator};
 use crate::resource_cache::ResourceCache;
 use crate::queue::Queue;
-use crate::synchronization::{Fence, Semaphore};
+use crate::synchronization::{Fence, FencePool, Semaphore};
 use ash::{vk, Device, Instance};
 use std::cell::RefCell;
+use std::sync::Arc;
 #[cfg(feature = "validation")]
 use std::ffi::CString;
 use std::default::Default;
-use zenith_core::collections::{SmallVec, hashset::HashSet};
+use zenith_core::collections::{SmallVec, hashmap::HashMap, hashset::HashSet};
 use crate::CommandEncoder;
+use crate::command::ImmediateContext;
 
 #[cfg(feature = "validation")]
 fn set_debug_name_raw(
@@ -59,7 +65,9 @@ pub(crate) fn set_debug_name_handle<T: vk::Handle>(
 ) {
     #[cfg(feature = "validation")]
     {
-        set_debug_name_raw(&device.debug_utils, object.as_raw(), object_type, name);
+        if let Some(debug_utils) = &device.debug_utils {
+            set_debug_name_raw(debug_utils, object.as_raw(), object_type, name);
+        }
     }
     #[cfg(not(feature = "validation"))]
     {
@@ -67,25 +75,61 @@ pub(crate) fn set_debug_name_handle<T: vk::Handle>(
     }
 }
 
+/// Default upper bound of runtime sized descriptor arrays.
+const DEFAULT_MAX_BINDLESS_DESCRIPTORS: u32 = 4096;
+
 /// Get required device extensions.
 fn get_required_device_extensions() -> Vec<*const i8> {
     vec![ash::khr::swapchain::NAME.as_ptr()]
 }
 
+/// Features enabled on the logical device.
+#[derive(Debug, Clone, Copy)]
+pub struct EnabledFeatures {
+    /// Core Vulkan 1.0 features.
+    pub core: vk::PhysicalDeviceFeatures,
+    /// Runtime sized descriptor arrays with variable descriptor count and partially bound bindings.
+    pub descriptor_indexing: bool,
+    pub dynamic_rendering: bool,
+    pub synchronization2: bool,
+    /// `VK_EXT_memory_budget`, see [`RenderDevice::memory_budget`].
+    pub memory_budget: bool,
+}
+
 /// Vulkan logical device with queues.
 pub struct RenderDevice {
+    instance: Instance,
     parent_physical_device: PhysicalDevice,
+    format_properties: RefCell<HashMap<vk::Format, vk::FormatProperties>>,
+    properties: vk::PhysicalDeviceProperties,
+    enabled_features: EnabledFeatures,
     device: Device,
+    /// Only loaded when validation is enabled at runtime.
     #[cfg(feature = "validation")]
-    debug_utils: ash::ext::debug_utils::Device,
+    debug_utils: Option<ash::ext::debug_utils::Device>,
     graphics_queue: vk::Queue,
     present_queue: vk::Queue,
 
     frame_resource_fences: Vec<Fence>,
     defer_release_queues: RefCell<Vec<DeferReleaseQueue>>,
     resource_caches: Vec<ResourceCache>,
+    immediate_contexts: RefCell<Vec<ImmediateContext>>,
+    /// Fences of immediate submits.
+    fence_pool: FencePool,
+
+    /// Upper bound of runtime sized descriptor arrays, further clamped by the device limits.
+    max_bindless_descriptors: u32,
+    /// Bytes allocated by buffers and textures, per heap.
+    memory_usage: Arc<MemoryUsageTracker>,
+    /// Suballocator shared by buffers and textures.
+    #[cfg(feature = "allocator")]
+    allocator: Arc<MemoryAllocator>,
 
     current_frame: u8,
+    /// Monotonic number of the current frame, advanced by `end_frame`.
+    frame_number: u64,
+    /// Frame number last begun in each frame slot.
+    frame_slot_numbers: Vec<u64>,
 }
 
 impl RenderDevice {
@@ -94,6 +138,7 @@ impl RenderDevice {
         instance: &Instance,
         physical_device: &PhysicalDevice,
         num_frames: u32,
+        validation: bool,
     ) -> Result<Self, vk::Result> {
         // Collect unique queue families
         let unique_families: HashSet<u32> = [physical_device.graphics_queue_family(), physical_device.present_queue_family()]
@@ -111,18 +156,26 @@ impl RenderDevice {
             })
             .collect();
 
-        let extensions = get_required_device_extensions();
+        let mut extensions = get_required_device_extensions();
+        let memory_budget = physical_device.supports_memory_budget();
+        if memory_budget {
+            extensions.push(ash::ext::memory_budget::NAME.as_ptr());
+        }
 
         // Enable features
-        let features = vk::PhysicalDeviceFeatures::default();
+        let features = vk::PhysicalDeviceFeatures::default()
+            .depth_bounds(physical_device.supports_depth_bounds());
             // .sampler_anisotropy(true)
             // .fill_mode_non_solid(true);
 
         // Vulkan 1.2 features
-        // let mut vulkan_12_features = vk::PhysicalDeviceVulkan12Features::default()
-        //     .descriptor_indexing(true)
-        //     .buffer_device_address(true)
-        //     .timeline_semaphore(true);
+        let descriptor_indexing = physical_device.supports_descriptor_indexing();
+        let mut vulkan_12_features = vk::PhysicalDeviceVulkan12Features::default()
+            .runtime_descriptor_array(descriptor_indexing)
+            .descriptor_binding_variable_descriptor_count(descriptor_indexing)
+            .descriptor_binding_partially_bound(descriptor_indexing);
+            // .buffer_device_address(true)
+            // .timeline_semaphore(true);
 
         // Vulkan 1.3 features
         let mut vulkan_13_features = vk::PhysicalDeviceVulkan13Features::default()
@@ -133,12 +186,14 @@ impl RenderDevice {
             .queue_create_infos(&queue_create_infos)
             .enabled_extension_names(&extensions)
             .enabled_features(&features)
-            // .push_next(&mut vulkan_12_features)
+            .push_next(&mut vulkan_12_features)
             .push_next(&mut vulkan_13_features);
 
         let device = unsafe { instance.create_device(physical_device.handle(), &create_info, None)? };
         #[cfg(feature = "validation")]
-        let debug_utils = ash::ext::debug_utils::Device::new(instance, &device);
+        let debug_utils = validation.then(|| ash::ext::debug_utils::Device::new(instance, &device));
+        #[cfg(not(feature = "validation"))]
+        let _ = validation;
 
         let graphics_queue = unsafe { device.get_device_queue(physical_device.graphics_queue_family(), 0) };
         let present_queue = unsafe { device.get_device_queue(physical_device.present_queue_family(), 0) };
@@ -146,8 +201,25 @@ impl RenderDevice {
         let resource_caches: Vec<ResourceCache> =
             (0..num_frames as usize).map(|_| ResourceCache::default()).collect();
 
+        #[cfg(feature = "allocator")]
+        let allocator = Arc::new(MemoryAllocator::new(
+            device.clone(),
+            *physical_device.memory_properties(),
+            &physical_device.properties().limits,
+        ));
+
         let mut device = Self {
+            instance: instance.clone(),
+            format_properties: RefCell::new(HashMap::new()),
             parent_physical_device: physical_device.clone(),
+            properties: *physical_device.properties(),
+            enabled_features: EnabledFeatures {
+                core: features,
+                descriptor_indexing,
+                dynamic_rendering: true,
+                synchronization2: true,
+                memory_budget,
+            },
             device,
             #[cfg(feature = "validation")]
             debug_utils,
@@ -156,7 +228,15 @@ impl RenderDevice {
             frame_resource_fences: Vec::with_capacity(num_frames as usize),
             defer_release_queues: RefCell::new(Vec::with_capacity(num_frames as usize)),
             resource_caches,
+            immediate_contexts: RefCell::new(Vec::new()),
+            fence_pool: FencePool::new("fence.immediate"),
+            max_bindless_descriptors: DEFAULT_MAX_BINDLESS_DESCRIPTORS,
+            memory_usage: Arc::default(),
+            #[cfg(feature = "allocator")]
+            allocator,
             current_frame: 0,
+            frame_number: 0,
+            frame_slot_numbers: vec![0; num_frames as usize],
         };
 
         for _ in 0..num_frames {
@@ -176,12 +256,53 @@ impl RenderDevice {
         &self.device
     }
 
+    /// Check if descriptor indexing (runtime sized descriptor arrays) is enabled.
+    #[inline]
+    pub fn descriptor_indexing_enabled(&self) -> bool { self.enabled_features.descriptor_indexing }
+
+    /// Set the upper bound used for runtime sized descriptor arrays of layouts created afterwards.
+    #[inline]
+    pub fn set_max_bindless_descriptors(&mut self, max: u32) { self.max_bindless_descriptors = max; }
+
+    /// Descriptor count to use for a runtime sized array of `descriptor_type`.
+    pub fn max_variable_descriptor_count(&self, descriptor_type: vk::DescriptorType) -> u32 {
+        let limits = self.limits();
+        let limit = match descriptor_type {
+            vk::DescriptorType::SAMPLER => limits.max_per_stage_descriptor_samplers,
+            vk::DescriptorType::COMBINED_IMAGE_SAMPLER => {
+                limits.max_per_stage_descriptor_samplers.min(limits.max_per_stage_descriptor_sampled_images)
+            }
+            vk::DescriptorType::SAMPLED_IMAGE | vk::DescriptorType::UNIFORM_TEXEL_BUFFER => {
+                limits.max_per_stage_descriptor_sampled_images
+            }
+            vk::DescriptorType::STORAGE_IMAGE | vk::DescriptorType::STORAGE_TEXEL_BUFFER => {
+                limits.max_per_stage_descriptor_storage_images
+            }
+            vk::DescriptorType::UNIFORM_BUFFER | vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC => {
+                limits.max_per_stage_descriptor_uniform_buffers
+            }
+            vk::DescriptorType::STORAGE_BUFFER | vk::DescriptorType::STORAGE_BUFFER_DYNAMIC => {
+                limits.max_per_stage_descriptor_storage_buffers
+            }
+            vk::DescriptorType::INPUT_ATTACHMENT => limits.max_per_stage_descriptor_input_attachments,
+            _ => limits.max_per_stage_resources,
+        };
+        limit.min(self.max_bindless_descriptors)
+    }
+
     /// Set debug name for a zenith-rhi wrapper object (best-effort, no-op without validation).
     #[inline]
     pub(crate) fn set_debug_name<T: DebuggableObject>(&self, obj: &T) {
         obj.set_debug_name(self)
     }
 
+    /// Name a raw Vulkan handle created outside the wrapper types (e.g. a descriptor set
+    /// allocated by [`crate::DescriptorSetBinder`]) for capture tools.
+    /// No-op when debug utils are unavailable.
+    pub fn name_handle<H: vk::Handle>(&self, handle: H, object_type: vk::ObjectType, name: &str) {
+        set_debug_name_handle(self, handle, object_type, name);
+    }
+
     pub fn begin_frame(&mut self) -> usize {
         // wait and reset until execution of current frame completes on GPU side
         unsafe {
@@ -189,6 +310,7 @@ impl RenderDevice {
             self.device.wait_for_fences(&[fence], true, u64::MAX).unwrap();
             self.device.reset_fences(&[fence]).unwrap();
         }
+        self.frame_slot_numbers[self.current_frame as usize] = self.frame_number;
         self.current_frame as _
     }
 
@@ -212,11 +334,36 @@ impl RenderDevice {
     #[inline]
     pub fn end_frame(&mut self) {
         self.current_frame = (self.current_frame + 1) % (self.defer_release_queues.borrow().len() as u8);
+        self.frame_number += 1;
     }
 
     #[inline]
     pub fn current_frame_index(&self) -> usize { self.current_frame as _ }
 
+    /// Monotonic number of the current frame, unlike [`Self::current_frame_index`] it never wraps.
+    #[inline]
+    pub fn current_frame_number(&self) -> u64 { self.frame_number }
+
+    /// Number of the latest frame whose GPU work has completed, or `None` if no frame has completed yet.
+    ///
+    /// Polls the per-frame fences without waiting, so it can be used to release resources precisely
+    /// instead of calling `wait_until_idle`. This relies on the last submission of every frame signaling
+    /// [`Self::frame_resource_fence`], as the render graph does.
+    pub fn completed_frame_number(&self) -> Option<u64> {
+        let oldest_in_flight = self.frame_resource_fences
+            .iter()
+            .zip(&self.frame_slot_numbers)
+            .filter(|(fence, _)| !unsafe { self.device.get_fence_status(fence.handle()) }.unwrap_or(false))
+            .map(|(_, &number)| number)
+            .min();
+
+        match oldest_in_flight {
+            Some(number) => number.checked_sub(1),
+            // Nothing in flight, every frame begun so far has completed.
+            None => self.frame_number.checked_sub(1),
+        }
+    }
+
     #[inline]
     pub fn num_frames(&self) -> usize { self.defer_release_queues.borrow().len() as _ }
 
@@ -264,13 +411,66 @@ impl RenderDevice {
         &mut self.resource_caches[self.current_frame as usize]
     }
 
+    /// Drop cached render targets of every frame sized to `old_extent`, see [`ResourceCache::invalidate_size_dependent`].
+    pub fn invalidate_size_dependent(&mut self, old_extent: vk::Extent2D) {
+        for cache in &mut self.resource_caches {
+            cache.invalidate_size_dependent(old_extent);
+        }
+    }
+
     pub fn frame_resource_fence(&self) -> &Fence {
         &self.frame_resource_fences[self.current_frame as usize]
     }
 
     /// Get the physical device properties.
     pub fn properties(&self) -> &vk::PhysicalDeviceProperties {
-        &self.parent_physical_device.properties()
+        &self.properties
+    }
+
+    /// Get the properties of `format` on the physical device, cached after the first query.
+    pub fn format_properties(&self, format: vk::Format) -> vk::FormatProperties {
+        *self.format_properties.borrow_mut().entry(format).or_insert_with(|| unsafe {
+            self.instance.get_physical_device_format_properties(self.parent_physical_device.handle(), format)
+        })
+    }
+
+    /// Check if `format` supports all of `features` with `tiling`.
+    pub fn supports_format(&self, format: vk::Format, tiling: vk::ImageTiling, features: vk::FormatFeatureFlags) -> bool {
+        let properties = self.format_properties(format);
+        let supported = match tiling {
+            vk::ImageTiling::LINEAR => properties.linear_tiling_features,
+            vk::ImageTiling::OPTIMAL => properties.optimal_tiling_features,
+            _ => vk::FormatFeatureFlags::empty(),
+        };
+        supported.contains(features)
+    }
+
+    /// Get the first of `candidates` supporting all of `features` with `tiling`.
+    pub fn first_supported_format(
+        &self,
+        candidates: &[vk::Format],
+        tiling: vk::ImageTiling,
+        features: vk::FormatFeatureFlags,
+    ) -> Option<vk::Format> {
+        candidates.iter().copied().find(|&format| self.supports_format(format, tiling, features))
+    }
+
+    /// Get the physical device limits.
+    #[inline]
+    pub fn limits(&self) -> &vk::PhysicalDeviceLimits {
+        &self.properties.limits
+    }
+
+    /// Get the features enabled on this device.
+    #[inline]
+    pub fn enabled_features(&self) -> &EnabledFeatures {
+        &self.enabled_features
+    }
+
+    /// Nanoseconds per timestamp query tick.
+    #[inline]
+    pub fn timestamp_period(&self) -> f32 {
+        self.limits().timestamp_period
     }
 
     /// Get the physical device memory properties.
@@ -278,6 +478,95 @@ impl RenderDevice {
         &self.parent_physical_device.memory_properties()
     }
 
+    /// Budget and usage of every memory heap.
+    ///
+    /// Reported by the driver with `VK_EXT_memory_budget`, which includes other processes and
+    /// allocations made outside the engine. Without the extension the budget is the heap size and
+    /// the usage only sums the buffers and textures allocated through the engine, or the memory
+    /// blocks of the suballocator with the `allocator` feature.
+    pub fn memory_budget(&self) -> Vec<HeapBudget> {
+        let mut budget_properties = vk::PhysicalDeviceMemoryBudgetPropertiesEXT::default();
+        let memory_properties = if self.enabled_features.memory_budget {
+            let mut properties = vk::PhysicalDeviceMemoryProperties2::default().push_next(&mut budget_properties);
+            unsafe {
+                self.instance.get_physical_device_memory_properties2(self.parent_physical_device.handle(), &mut properties);
+            }
+            properties.memory_properties
+        } else {
+            *self.memory_properties()
+        };
+
+        memory_properties.memory_heaps_as_slice()
+            .iter()
+            .enumerate()
+            .map(|(index, heap)| {
+                let (budget, usage) = if self.enabled_features.memory_budget {
+                    (budget_properties.heap_budget[index], budget_properties.heap_usage[index])
+                } else {
+                    #[cfg(feature = "allocator")]
+                    let usage = self.allocator.heap_block_bytes(index as u32);
+                    #[cfg(not(feature = "allocator"))]
+                    let usage = self.memory_usage.heap_usage(index as u32);
+                    (heap.size, usage)
+                };
+                HeapBudget {
+                    heap_index: index as u32,
+                    flags: heap.flags,
+                    size: heap.size,
+                    budget,
+                    usage,
+                }
+            })
+            .collect()
+    }
+
+    /// Total device memory currently allocated for buffers and textures, in bytes.
+    ///
+    /// With the `allocator` feature these are the bytes of the allocator's blocks, including the
+    /// space not handed out to resources yet, see [`Self::allocator_stats`].
+    #[inline]
+    pub fn allocated_bytes(&self) -> vk::DeviceSize {
+        #[cfg(feature = "allocator")]
+        {
+            self.allocator.stats().block_bytes
+        }
+        #[cfg(not(feature = "allocator"))]
+        {
+            self.memory_usage.total_usage()
+        }
+    }
+
+    /// Number of live `VkDeviceMemory` allocations backing buffers and textures.
+    /// Swapchain images are not counted.
+    ///
+    /// With the `allocator` feature this is the number of the allocator's blocks, which are shared
+    /// by many resources, see [`Self::allocator_stats`].
+    #[inline]
+    pub fn allocation_count(&self) -> usize {
+        #[cfg(feature = "allocator")]
+        {
+            self.allocator.stats().block_count
+        }
+        #[cfg(not(feature = "allocator"))]
+        {
+            self.memory_usage.allocation_count()
+        }
+    }
+
+    /// Block and suballocation statistics of the memory allocator.
+    #[cfg(feature = "allocator")]
+    pub fn allocator_stats(&self) -> AllocatorStats { self.allocator.stats() }
+
+    #[cfg(feature = "allocator")]
+    #[inline]
+    pub(crate) fn allocator(&self) -> &Arc<MemoryAllocator> { &self.allocator }
+
+    /// Count an allocation of `size` bytes from `memory_type_index` until the returned guard drops.
+    pub(crate) fn track_allocation(&self, memory_type_index: u32, size: vk::DeviceSize) -> TrackedAllocation {
+        let heap_index = self.memory_properties().memory_types[memory_type_index as usize].heap_index;
+        TrackedAllocation::new(self.memory_usage.clone(), heap_index, size)
+    }
+
     pub fn graphics_queue(&self) -> Queue {
         Queue::new(self.graphics_queue, self.parent_physical_device.graphics_queue_family())
     }
@@ -286,6 +575,7 @@ impl RenderDevice {
         Queue::new(self.present_queue, self.parent_physical_device.present_queue_family())
     }
 
+    /// Wait until all queues are idle. Prefer [`Queue::wait_idle`] to stall on a single queue.
     pub fn wait_until_idle(&self) -> Result<(), vk::Result> {
         unsafe { self.device.device_wait_idle() }
     }
@@ -294,6 +584,21 @@ impl RenderDevice {
         &self.parent_physical_device
     }
 
+    /// Take the cached immediate context of `queue_family` out of the device, if any.
+    pub(crate) fn take_immediate_context(&self, queue_family: u32) -> Option<ImmediateContext> {
+        let mut contexts = self.immediate_contexts.borrow_mut();
+        let index = contexts.iter().position(|context| context.queue_family == queue_family)?;
+        Some(contexts.swap_remove(index))
+    }
+
+    pub(crate) fn return_immediate_context(&self, context: ImmediateContext) {
+        self.immediate_contexts.borrow_mut().push(context);
+    }
+
+    /// Pool of reusable fences for short lived submits, e.g. immediate command encoders.
+    #[inline]
+    pub fn fence_pool(&self) -> &FencePool { &self.fence_pool }
+
     pub fn submit_commands<'a>(
         &self,
         encoder: CommandEncoder<'a>,
@@ -338,9 +643,19 @@ impl RenderDevice {
     }
 }
 
+/// Wait until `device` is idle before tearing down the objects of `owner`.
+///
+/// Used by `Drop` impls: a failure (usually device lost) is logged instead of panicking,
+/// since there's nothing left to do but release the objects anyway.
+pub(crate) fn wait_idle_for_teardown(device: &ash::Device, owner: &str) {
+    if let Err(err) = unsafe { device.device_wait_idle() } {
+        log::error!("{owner}: failed to wait for the device to be idle before teardown: {err:?}");
+    }
+}
+
 impl Drop for RenderDevice {
     fn drop(&mut self) {
-        unsafe { self.device.device_wait_idle().unwrap(); }
+        wait_idle_for_teardown(&self.device, "render device");
 
         for queue in self.defer_release_queues.get_mut() {
             queue.release_all();
@@ -350,6 +665,8 @@ impl Drop for RenderDevice {
             cache.clear();
         }
         self.resource_caches.clear();
+        self.immediate_contexts.get_mut().clear();
+        self.fence_pool.clear();
         self.frame_resource_fences.clear();
 
         unsafe {
@@ -9,6 +9,7 @@ pub mod descriptor;
 pub mod device;
 pub mod pipeline;
 pub mod pipeline_cache;
+pub mod query;
 pub mod resource_cache;
 pub mod sampler;
 pub mod shader;
@@ -17,51 +18,62 @@ pub mod texture;
 pub mod upload;
 pub mod queue;
 mod defer_release;
+mod memory;
 mod barrier;
 mod synchronization;
 mod utility;
 
 pub(crate) use paste::paste;
 
+// Lets `#[derive(VertexLayout)]` resolve `::zenith_rhi` inside this crate's tests.
+#[cfg(test)]
+extern crate self as zenith_rhi;
+
 pub(crate) const NUM_BACK_BUFFERS: u32 = 3;
 
 pub use memoffset;
 pub use zenith_rhi_derive::VertexLayout;
 
 pub use ash::{vk, Device};
-pub use buffer::{Buffer, BufferDesc};
-pub use command::{CommandPool, CommandEncoder, ImmediateCommandEncoder};
-pub use core::RhiCore;
+pub use buffer::{Buffer, BufferDesc, MappedMemory, TypedBuffer};
+pub use command::{ColorRenderTarget, CommandPool, CommandEncoder, DepthRenderTarget, FramePools, ImmediateCommandEncoder};
+pub use core::{RhiCore, RhiCoreConfig, Severity, ValidationCallback};
 pub use queue::Queue;
 pub use descriptor::{
     BindingError, DescriptorPool, DescriptorSetLayout, LayoutBinding,
-    ShaderBindingError, DescriptorSetBinder,
+    ShaderBindingError, DescriptorSetBinder, AllocateOnFinish, Preallocated, align_offset,
 };
-pub use device::RenderDevice;
+pub use device::{EnabledFeatures, RenderDevice};
 pub use pipeline::{
-    ColorAttachmentDesc, ColorAttachmentDescBuilder, ColorAttachmentDescBuilderError,
+    ColorAttachmentDesc, ColorAttachmentDescBuilder, ColorAttachmentDescBuilderError, ColorInfo,
     DepthStencilDesc, DepthStencilDescBuilder, DepthStencilDescBuilderError,
     GraphicPipeline, GraphicPipelineDesc, GraphicPipelineState, GraphicPipelineStateBuilder,
     GraphicShaderInput, GraphicShaderInputBuilder, GraphicShaderInputBuildError,
     GraphicPipelineAttachments,
     InputAssemblyState, RasterizationState, MultisampleState, ColorBlendState,
-    VertexAttribute, VertexBinding, VertexLayout,
+    VertexAttribute, VertexBinding, VertexLayout, formats_compatible,
 };
-pub use pipeline_cache::{PipelineCache, PipelineCacheStats};
+pub use pipeline_cache::{PipelineCache, PipelineCacheStats, PrecompileHandle};
+pub use query::QueryPool;
+pub use memory::HeapBudget;
+#[cfg(feature = "allocator")]
+pub use memory::AllocatorStats;
 pub use resource_cache::ResourceCache;
-pub use sampler::{Sampler, SamplerConfig};
+pub use sampler::{Sampler, SamplerConfig, SamplerError};
 pub use shader::{
-    reflect_spirv, Shader, ShaderBinding, ShaderError, ShaderReflection, ShaderStage,
+    list_entry_points, reflect_spirv, reflect_spirv_entry, Shader, ShaderBinding, ShaderCompileOptions, ShaderError, ShaderReflection, ShaderStage,
 };
-pub use swapchain::{FrameSync, SwapchainConfig, Swapchain};
+#[cfg(feature = "hlsl")]
+pub use shader::compile_hlsl;
+pub use swapchain::{ColorSpace, FrameCapture, FrameSync, PendingCapture, SwapchainConfig, Swapchain};
 pub use texture::{Texture, TextureDesc};
 pub use barrier::{
     BufferState, TextureState,
     global_memory_barrier, flush_all_memory_writes,
     PipelineStage, PipelineStages, TextureLayout,
-    BufferBarrier, TextureBarrier, MemoryBarrier,
+    BufferBarrier, TextureBarrier, MemoryBarrier, QueueTransferBarrier,
 };
-pub use synchronization::{Semaphore, Fence};
+pub use synchronization::{Semaphore, Fence, FencePool};
 pub use upload::UploadPool;
 
 pub use defer_release::{DeferRelease, LastFreedStats};
\ No newline at end of file
@@ -0,0 +1,518 @@
+//! Device memory budget and allocation tracking.
+
+use ash::vk;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use crate::RenderDevice;
+
+/// Budget and usage of a single memory heap, in bytes.
+#[derive(Debug, Clone, Copy)]
+pub struct HeapBudget {
+    pub heap_index: u32,
+    pub flags: vk::MemoryHeapFlags,
+    /// Total size of the heap.
+    pub size: vk::DeviceSize,
+    /// Amount the process can allocate from the heap without degrading performance.
+    /// The heap size when `VK_EXT_memory_budget` is unavailable.
+    pub budget: vk::DeviceSize,
+    /// Amount currently in use. Only counts engine allocations when `VK_EXT_memory_budget` is unavailable.
+    pub usage: vk::DeviceSize,
+}
+
+impl HeapBudget {
+    #[inline]
+    pub fn is_device_local(&self) -> bool {
+        self.flags.contains(vk::MemoryHeapFlags::DEVICE_LOCAL)
+    }
+
+    /// Bytes left before reaching the budget.
+    #[inline]
+    pub fn available(&self) -> vk::DeviceSize {
+        self.budget.saturating_sub(self.usage)
+    }
+}
+
+/// Bytes allocated by the engine per memory heap, and the number of live allocations.
+#[derive(Default)]
+pub(crate) struct MemoryUsageTracker {
+    heaps: [AtomicU64; vk::MAX_MEMORY_HEAPS],
+    count: AtomicUsize,
+}
+
+impl MemoryUsageTracker {
+    #[cfg(not(feature = "allocator"))]
+    pub(crate) fn heap_usage(&self, heap_index: u32) -> vk::DeviceSize {
+        self.heaps[heap_index as usize].load(Ordering::Relaxed)
+    }
+
+    #[cfg(not(feature = "allocator"))]
+    pub(crate) fn total_usage(&self) -> vk::DeviceSize {
+        self.heaps.iter().map(|heap| heap.load(Ordering::Relaxed)).sum()
+    }
+
+    #[cfg(not(feature = "allocator"))]
+    pub(crate) fn allocation_count(&self) -> usize {
+        self.count.load(Ordering::Relaxed)
+    }
+}
+
+/// An allocation counted by a [`MemoryUsageTracker`], released on drop.
+pub(crate) struct TrackedAllocation {
+    tracker: Arc<MemoryUsageTracker>,
+    heap_index: u32,
+    size: vk::DeviceSize,
+}
+
+impl TrackedAllocation {
+    pub(crate) fn new(tracker: Arc<MemoryUsageTracker>, heap_index: u32, size: vk::DeviceSize) -> Self {
+        tracker.heaps[heap_index as usize].fetch_add(size, Ordering::Relaxed);
+        tracker.count.fetch_add(1, Ordering::Relaxed);
+        Self { tracker, heap_index, size }
+    }
+}
+
+impl Drop for TrackedAllocation {
+    fn drop(&mut self) {
+        self.tracker.heaps[self.heap_index as usize].fetch_sub(self.size, Ordering::Relaxed);
+        self.tracker.count.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Device memory backing a buffer or texture: either a dedicated `vkAllocateMemory`,
+/// or a range of a shared block when the `allocator` feature is on.
+pub(crate) struct MemoryAllocation {
+    memory: vk::DeviceMemory,
+    offset: vk::DeviceSize,
+    size: vk::DeviceSize,
+    /// Owning allocator and block, `None` for a dedicated allocation.
+    #[cfg(feature = "allocator")]
+    block: Option<(Arc<MemoryAllocator>, u64)>,
+    /// Host pointer to `offset` if the block is persistently mapped.
+    #[cfg(feature = "allocator")]
+    mapped: Option<usize>,
+    _tracked: TrackedAllocation,
+}
+
+impl MemoryAllocation {
+    /// Allocate memory satisfying `requirements` from `memory_type_index`.
+    ///
+    /// `linear` resources (buffers) and optimal tiled images never share a block,
+    /// so `bufferImageGranularity` doesn't need to be respected between suballocations.
+    pub(crate) fn new(
+        device: &RenderDevice,
+        requirements: vk::MemoryRequirements,
+        memory_type_index: u32,
+        linear: bool,
+    ) -> Result<Self, vk::Result> {
+        let tracked = device.track_allocation(memory_type_index, requirements.size);
+
+        #[cfg(feature = "allocator")]
+        {
+            let allocator = device.allocator();
+            let suballocation = allocator.allocate(requirements, memory_type_index, linear)?;
+            Ok(Self {
+                memory: suballocation.memory,
+                offset: suballocation.offset,
+                size: suballocation.size,
+                block: Some((allocator.clone(), suballocation.block_id)),
+                mapped: suballocation.mapped,
+                _tracked: tracked,
+            })
+        }
+        #[cfg(not(feature = "allocator"))]
+        {
+            let _ = linear;
+            let alloc_info = vk::MemoryAllocateInfo::default()
+                .allocation_size(requirements.size)
+                .memory_type_index(memory_type_index);
+            let memory = unsafe { device.handle().allocate_memory(&alloc_info, None)? };
+            Ok(Self {
+                memory,
+                offset: 0,
+                size: requirements.size,
+                _tracked: tracked,
+            })
+        }
+    }
+
+    /// The `VkDeviceMemory`, shared with other resources for suballocations.
+    #[inline]
+    pub(crate) fn memory(&self) -> vk::DeviceMemory { self.memory }
+
+    /// Offset of the allocation in [`Self::memory`].
+    #[inline]
+    pub(crate) fn offset(&self) -> vk::DeviceSize { self.offset }
+
+    #[inline]
+    pub(crate) fn size(&self) -> vk::DeviceSize { self.size }
+
+    /// Whether the allocation owns its `VkDeviceMemory`.
+    #[inline]
+    pub(crate) fn is_dedicated(&self) -> bool {
+        #[cfg(feature = "allocator")]
+        {
+            self.block.is_none()
+        }
+        #[cfg(not(feature = "allocator"))]
+        {
+            true
+        }
+    }
+
+    /// Map `offset..offset + size` (relative to the allocation), must be paired with [`Self::unmap`].
+    pub(crate) unsafe fn map(&self, device: &ash::Device, offset: vk::DeviceSize, size: vk::DeviceSize) -> Result<*mut u8, vk::Result> {
+        #[cfg(feature = "allocator")]
+        if let Some(mapped) = self.mapped {
+            return Ok((mapped + offset as usize) as *mut u8);
+        }
+
+        let ptr = unsafe { device.map_memory(self.memory, self.offset + offset, size, vk::MemoryMapFlags::empty())? };
+        Ok(ptr as *mut u8)
+    }
+
+    pub(crate) unsafe fn unmap(&self, device: &ash::Device) {
+        #[cfg(feature = "allocator")]
+        if self.mapped.is_some() {
+            return;
+        }
+
+        unsafe { device.unmap_memory(self.memory) }
+    }
+
+    /// Return the memory to the device or allocator. The resource bound to it must be destroyed first.
+    pub(crate) unsafe fn free(&mut self, device: &ash::Device) {
+        #[cfg(feature = "allocator")]
+        if let Some((allocator, block_id)) = self.block.take() {
+            allocator.free(block_id, self.offset, self.size);
+            return;
+        }
+
+        unsafe { device.free_memory(self.memory, None) }
+    }
+}
+
+/// Statistics of the suballocating allocator.
+#[cfg(feature = "allocator")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AllocatorStats {
+    /// Number of `VkDeviceMemory` blocks.
+    pub block_count: usize,
+    /// Bytes of all blocks, i.e. the device memory actually allocated.
+    pub block_bytes: vk::DeviceSize,
+    /// Number of live suballocations.
+    pub allocation_count: usize,
+    /// Bytes handed out to resources, excluding alignment padding and free space.
+    pub allocated_bytes: vk::DeviceSize,
+}
+
+#[cfg(feature = "allocator")]
+pub(crate) use allocator::MemoryAllocator;
+
+#[cfg(feature = "allocator")]
+mod allocator {
+    use super::AllocatorStats;
+    use ash::vk;
+    use std::ops::Range;
+    use std::sync::Mutex;
+    use zenith_core::log;
+
+    /// Default size of a memory block, larger allocations get a block of their own.
+    const DEFAULT_BLOCK_SIZE: vk::DeviceSize = 64 * 1024 * 1024;
+
+    pub(crate) struct Suballocation {
+        pub(crate) block_id: u64,
+        pub(crate) memory: vk::DeviceMemory,
+        pub(crate) offset: vk::DeviceSize,
+        /// Requested size, padded to `nonCoherentAtomSize` for non-coherent host-visible memory.
+        pub(crate) size: vk::DeviceSize,
+        pub(crate) mapped: Option<usize>,
+    }
+
+    struct MemoryBlock {
+        id: u64,
+        memory_type_index: u32,
+        linear: bool,
+        memory: vk::DeviceMemory,
+        size: vk::DeviceSize,
+        /// Host address of the persistently mapped block, for host-visible memory types.
+        mapped: Option<usize>,
+        /// Free ranges sorted by offset, never adjacent.
+        free: Vec<Range<vk::DeviceSize>>,
+        allocation_count: usize,
+        allocated_bytes: vk::DeviceSize,
+    }
+
+    impl MemoryBlock {
+        fn new(id: u64, memory_type_index: u32, linear: bool, memory: vk::DeviceMemory, size: vk::DeviceSize, mapped: Option<usize>) -> Self {
+            Self {
+                id,
+                memory_type_index,
+                linear,
+                memory,
+                size,
+                mapped,
+                free: vec![Range { start: 0, end: size }],
+                allocation_count: 0,
+                allocated_bytes: 0,
+            }
+        }
+
+        fn try_allocate(&mut self, size: vk::DeviceSize, alignment: vk::DeviceSize) -> Option<vk::DeviceSize> {
+            let (index, offset) = self.free.iter().enumerate().find_map(|(index, range)| {
+                let offset = range.start.next_multiple_of(alignment);
+                (offset + size <= range.end).then_some((index, offset))
+            })?;
+
+            let range = self.free.remove(index);
+            if offset + size < range.end {
+                self.free.insert(index, offset + size..range.end);
+            }
+            if range.start < offset {
+                self.free.insert(index, range.start..offset);
+            }
+            self.allocation_count += 1;
+            self.allocated_bytes += size;
+            Some(offset)
+        }
+
+        fn free(&mut self, offset: vk::DeviceSize, size: vk::DeviceSize) {
+            let index = self.free.partition_point(|range| range.start < offset);
+            self.free.insert(index, offset..offset + size);
+
+            // Merge with the following and preceding ranges.
+            if index + 1 < self.free.len() && self.free[index].end == self.free[index + 1].start {
+                let next = self.free.remove(index + 1);
+                self.free[index].end = next.end;
+            }
+            if index > 0 && self.free[index - 1].end == self.free[index].start {
+                let current = self.free.remove(index);
+                self.free[index - 1].end = current.end;
+            }
+            self.allocation_count -= 1;
+            self.allocated_bytes -= size;
+        }
+    }
+
+    /// First-fit suballocator handing out ranges of large `VkDeviceMemory` blocks,
+    /// keeping the allocation count far below `maxMemoryAllocationCount`.
+    pub(crate) struct MemoryAllocator {
+        device: ash::Device,
+        memory_properties: vk::PhysicalDeviceMemoryProperties,
+        non_coherent_atom_size: vk::DeviceSize,
+        blocks: Mutex<(u64, Vec<MemoryBlock>)>,
+    }
+
+    impl MemoryAllocator {
+        pub(crate) fn new(device: ash::Device, memory_properties: vk::PhysicalDeviceMemoryProperties, limits: &vk::PhysicalDeviceLimits) -> Self {
+            Self {
+                device,
+                memory_properties,
+                non_coherent_atom_size: limits.non_coherent_atom_size.max(1),
+                blocks: Mutex::new((0, Vec::new())),
+            }
+        }
+
+        pub(crate) fn allocate(
+            &self,
+            requirements: vk::MemoryRequirements,
+            memory_type_index: u32,
+            linear: bool,
+        ) -> Result<Suballocation, vk::Result> {
+            let flags = self.memory_properties.memory_types[memory_type_index as usize].property_flags;
+            let (alignment, size) = padded_requirements(requirements, flags, self.non_coherent_atom_size);
+
+            let mut guard = self.blocks.lock().unwrap();
+            let (next_id, blocks) = &mut *guard;
+
+            for block in blocks.iter_mut().filter(|b| b.memory_type_index == memory_type_index && b.linear == linear) {
+                if let Some(offset) = block.try_allocate(size, alignment) {
+                    return Ok(Suballocation {
+                        block_id: block.id,
+                        memory: block.memory,
+                        offset,
+                        size,
+                        mapped: block.mapped.map(|ptr| ptr + offset as usize),
+                    });
+                }
+            }
+
+            let block_size = size.max(DEFAULT_BLOCK_SIZE);
+            let alloc_info = vk::MemoryAllocateInfo::default()
+                .allocation_size(block_size)
+                .memory_type_index(memory_type_index);
+            let memory = unsafe { self.device.allocate_memory(&alloc_info, None)? };
+            let mapped = if flags.contains(vk::MemoryPropertyFlags::HOST_VISIBLE) {
+                match unsafe { self.device.map_memory(memory, 0, vk::WHOLE_SIZE, vk::MemoryMapFlags::empty()) } {
+                    Ok(ptr) => Some(ptr as usize),
+                    Err(err) => {
+                        unsafe { self.device.free_memory(memory, None) };
+                        return Err(err);
+                    }
+                }
+            } else {
+                None
+            };
+
+            log::trace!("memory block of {} bytes allocated from type {}.", block_size, memory_type_index);
+
+            let mut block = MemoryBlock::new(*next_id, memory_type_index, linear, memory, block_size, mapped);
+            *next_id += 1;
+
+            let offset = block.try_allocate(size, alignment).expect("fresh block must fit the allocation");
+            let suballocation = Suballocation {
+                block_id: block.id,
+                memory,
+                offset,
+                size,
+                mapped: mapped.map(|ptr| ptr + offset as usize),
+            };
+            blocks.push(block);
+            Ok(suballocation)
+        }
+
+        /// Return a range, freeing its block once empty.
+        pub(crate) fn free(&self, block_id: u64, offset: vk::DeviceSize, size: vk::DeviceSize) {
+            let mut guard = self.blocks.lock().unwrap();
+            let blocks = &mut guard.1;
+            let Some(index) = blocks.iter().position(|b| b.id == block_id) else {
+                log::error!("MemoryAllocator::free: unknown memory block {}", block_id);
+                return;
+            };
+
+            blocks[index].free(offset, size);
+
+            if blocks[index].allocation_count == 0 {
+                let block = blocks.swap_remove(index);
+                self.free_block(&block);
+            }
+        }
+
+        fn free_block(&self, block: &MemoryBlock) {
+            unsafe {
+                if block.mapped.is_some() {
+                    self.device.unmap_memory(block.memory);
+                }
+                self.device.free_memory(block.memory, None);
+            }
+        }
+
+        pub(crate) fn stats(&self) -> AllocatorStats {
+            let guard = self.blocks.lock().unwrap();
+            guard.1.iter().fold(AllocatorStats::default(), |mut stats, block| {
+                stats.block_count += 1;
+                stats.block_bytes += block.size;
+                stats.allocation_count += block.allocation_count;
+                stats.allocated_bytes += block.allocated_bytes;
+                stats
+            })
+        }
+
+        /// Bytes of the blocks allocated from `heap_index`.
+        pub(crate) fn heap_block_bytes(&self, heap_index: u32) -> vk::DeviceSize {
+            let guard = self.blocks.lock().unwrap();
+            guard.1.iter()
+                .filter(|block| self.memory_properties.memory_types[block.memory_type_index as usize].heap_index == heap_index)
+                .map(|block| block.size)
+                .sum()
+        }
+    }
+
+    impl Drop for MemoryAllocator {
+        fn drop(&mut self) {
+            let blocks = std::mem::take(&mut self.blocks.get_mut().unwrap().1);
+            if !blocks.is_empty() {
+                log::warn!("MemoryAllocator dropped with {} memory blocks still in use.", blocks.len());
+            }
+            for block in &blocks {
+                self.free_block(block);
+            }
+        }
+    }
+
+    /// Alignment and size of a suballocation for `requirements` in a memory type with `flags`.
+    ///
+    /// Host ranges of non-coherent memory are kept atom aligned, so flushes never touch a neighbour.
+    fn padded_requirements(
+        requirements: vk::MemoryRequirements,
+        flags: vk::MemoryPropertyFlags,
+        non_coherent_atom_size: vk::DeviceSize,
+    ) -> (vk::DeviceSize, vk::DeviceSize) {
+        if flags.contains(vk::MemoryPropertyFlags::HOST_VISIBLE) && !flags.contains(vk::MemoryPropertyFlags::HOST_COHERENT) {
+            let atom = non_coherent_atom_size;
+            (requirements.alignment.max(atom), requirements.size.next_multiple_of(atom))
+        } else {
+            (requirements.alignment.max(1), requirements.size)
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn block(size: vk::DeviceSize) -> MemoryBlock {
+            MemoryBlock::new(0, 0, true, vk::DeviceMemory::null(), size, None)
+        }
+
+        #[test]
+        fn allocations_are_aligned() {
+            let mut block = block(1024);
+            assert_eq!(block.try_allocate(10, 1), Some(0));
+            assert_eq!(block.try_allocate(16, 64), Some(64));
+            assert_eq!(block.try_allocate(8, 4), Some(12));
+            assert_eq!(block.free, vec![10..12, 20..64, 80..1024]);
+        }
+
+        #[test]
+        fn allocation_splits_free_range() {
+            let mut block = block(256);
+            assert_eq!(block.try_allocate(64, 1), Some(0));
+            assert_eq!(block.free, vec![64..256]);
+            assert_eq!(block.try_allocate(256, 1), None);
+            assert_eq!(block.try_allocate(192, 1), Some(64));
+            assert!(block.free.is_empty());
+            assert_eq!(block.allocation_count, 2);
+            assert_eq!(block.allocated_bytes, 256);
+        }
+
+        #[test]
+        fn free_merges_adjacent_ranges() {
+            let mut block = block(300);
+            let offsets = [100, 100, 100].map(|size| block.try_allocate(size, 1).unwrap());
+            assert_eq!(offsets, [0, 100, 200]);
+
+            block.free(0, 100);
+            block.free(200, 100);
+            assert_eq!(block.free, vec![0..100, 200..300]);
+
+            block.free(100, 100);
+            assert_eq!(block.free, vec![0..300]);
+            assert_eq!(block.allocation_count, 0);
+            assert_eq!(block.allocated_bytes, 0);
+        }
+
+        #[test]
+        fn freed_range_is_reused() {
+            let mut block = block(300);
+            let first = block.try_allocate(100, 1).unwrap();
+            let second = block.try_allocate(100, 1).unwrap();
+            block.try_allocate(100, 1).unwrap();
+
+            block.free(second, 100);
+            assert_eq!(block.try_allocate(64, 32), Some(second.next_multiple_of(32)));
+            assert_eq!(block.try_allocate(200, 1), None);
+
+            block.free(first, 100);
+            assert_eq!(block.try_allocate(100, 1), Some(first));
+        }
+
+        #[test]
+        fn non_coherent_memory_is_atom_padded() {
+            let requirements = vk::MemoryRequirements { size: 100, alignment: 16, memory_type_bits: 0 };
+            let host = vk::MemoryPropertyFlags::HOST_VISIBLE;
+
+            assert_eq!(padded_requirements(requirements, host, 64), (64, 128));
+            assert_eq!(padded_requirements(requirements, host | vk::MemoryPropertyFlags::HOST_COHERENT, 64), (16, 100));
+            assert_eq!(padded_requirements(requirements, vk::MemoryPropertyFlags::DEVICE_LOCAL, 64), (16, 100));
+        }
+    }
+}
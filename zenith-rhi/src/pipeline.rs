@@ -3,6 +3,7 @@
 use zenith_core::log;
 use crate::descriptor::DescriptorSetLayout;
 use crate::shader::{Shader, ShaderReflection};
+use crate::texture::{format_to_aspect_mask, Texture};
 use derive_builder::Builder;
 use ash::{vk};
 use ash::vk::Handle;
@@ -14,6 +15,7 @@ use zenith_rhi_derive::DeviceObject;
 use crate::{RenderDevice};
 use crate::device::DebuggableObject;
 use crate::device::set_debug_name_handle;
+use crate::utility::srgb_to_linear;
 
 /// Dynamic rendering attachment formats for a graphics pipeline.
 ///
@@ -21,11 +23,17 @@ use crate::device::set_debug_name_handle;
 /// - `color_formats[0..]` (in-order)
 /// - `depth_format` (optional)
 /// - `stencil_format` (optional)
+///
+/// A non-zero `view_mask` enables multiview rendering: every draw is broadcast to each layer whose
+/// bit is set, and shaders select per-view data with `gl_ViewIndex` (`SV_ViewID` in Slang/HLSL).
+/// This requires the `multiview` device feature and array attachments with enough layers.
+/// Dynamic rendering has no view correlation mask, unlike render passes.
 #[derive(Clone, Debug, Default)]
 pub struct GraphicPipelineAttachments {
     pub color_formats: Vec<vk::Format>,
     pub depth_format: Option<vk::Format>,
     pub stencil_format: Option<vk::Format>,
+    pub view_mask: u32,
 }
 
 impl Hash for GraphicPipelineAttachments {
@@ -35,6 +43,7 @@ impl Hash for GraphicPipelineAttachments {
         }
         self.depth_format.map(|f| f.as_raw() as i32).hash(state);
         self.stencil_format.map(|f| f.as_raw() as i32).hash(state);
+        self.view_mask.hash(state);
     }
 }
 
@@ -43,16 +52,49 @@ impl PartialEq for GraphicPipelineAttachments {
         self.color_formats == other.color_formats
             && self.depth_format == other.depth_format
             && self.stencil_format == other.stencil_format
+            && self.view_mask == other.view_mask
     }
 }
 
 impl Eq for GraphicPipelineAttachments {}
 
 impl GraphicPipelineAttachments {
+    /// Derive the attachment formats from the textures rendered into.
+    /// Depth and stencil formats are taken from the aspects of `depth`'s format.
+    pub fn from_textures(color: &[&Texture], depth: Option<&Texture>) -> Self {
+        let attachments = color
+            .iter()
+            .fold(Self::default(), |attachments, texture| attachments.with_color_format(texture.format()));
+        match depth {
+            Some(depth) => attachments.with_depth_stencil_format(depth.format()),
+            None => attachments,
+        }
+    }
+
+    /// Append a color attachment format.
+    pub fn with_color_format(mut self, format: vk::Format) -> Self {
+        self.color_formats.push(format);
+        self
+    }
+
+    /// Set the depth and/or stencil format, depending on the aspects of `format`.
+    pub fn with_depth_stencil_format(mut self, format: vk::Format) -> Self {
+        let aspect = format_to_aspect_mask(format);
+        self.depth_format = aspect.contains(vk::ImageAspectFlags::DEPTH).then_some(format);
+        self.stencil_format = aspect.contains(vk::ImageAspectFlags::STENCIL).then_some(format);
+        self
+    }
+
+    pub fn with_view_mask(mut self, view_mask: u32) -> Self {
+        self.view_mask = view_mask;
+        self
+    }
+
     #[inline]
     pub fn to_vk_rendering_info(&self) -> vk::PipelineRenderingCreateInfo<'_> {
-        let mut info =
-            vk::PipelineRenderingCreateInfo::default().color_attachment_formats(&self.color_formats);
+        let mut info = vk::PipelineRenderingCreateInfo::default()
+            .color_attachment_formats(&self.color_formats)
+            .view_mask(self.view_mask);
 
         if let Some(depth) = self.depth_format {
             info = info.depth_attachment_format(depth);
@@ -75,17 +117,32 @@ pub struct GraphicShaderInput {
 
     pub merged_reflection: ShaderReflection,
     // pub descriptor_set_layouts: Vec<Arc<DescriptorSetLayout>>,
+    pub push_constant_offset: u32,
     pub push_constant_size: u32,
 }
 
 impl GraphicShaderInput {
+    /// Vertex attribute formats only need to be [compatible](formats_compatible) with the shader inputs.
     pub fn new(
         vertex_shader: Arc<Shader>,
         fragment_shader: Option<Arc<Shader>>,
         vertex_bindings: Vec<VertexBinding>,
         vertex_attributes: Vec<VertexAttribute>,
     ) -> Result<Self, GraphicShaderInputBuildError> {
-        validate_vertex_inputs(vertex_shader.reflection(), &vertex_attributes)?;
+        Self::new_impl(vertex_shader, fragment_shader, vertex_bindings, vertex_attributes, Some(false))
+    }
+
+    /// `strict_formats` of `None` skips vertex input validation entirely.
+    fn new_impl(
+        vertex_shader: Arc<Shader>,
+        fragment_shader: Option<Arc<Shader>>,
+        vertex_bindings: Vec<VertexBinding>,
+        vertex_attributes: Vec<VertexAttribute>,
+        strict_formats: Option<bool>,
+    ) -> Result<Self, GraphicShaderInputBuildError> {
+        if let Some(strict_formats) = strict_formats {
+            validate_vertex_inputs(vertex_shader.reflection(), &vertex_attributes, strict_formats)?;
+        }
 
         let mut reflections: Vec<&ShaderReflection> = Vec::new();
         reflections.push(vertex_shader.reflection());
@@ -102,6 +159,7 @@ impl GraphicShaderInput {
             fragment_shader,
             vertex_bindings,
             vertex_attributes,
+            push_constant_offset: merged_reflection.push_constant_offset,
             push_constant_size: merged_reflection.push_constant_size,
             merged_reflection,
             // descriptor_set_layouts,
@@ -112,7 +170,7 @@ impl GraphicShaderInput {
         let push_constant_ranges = if self.push_constant_size > 0 {
             vec![vk::PushConstantRange {
                 stage_flags: vk::ShaderStageFlags::ALL_GRAPHICS,
-                offset: 0,
+                offset: self.push_constant_offset,
                 size: self.push_constant_size,
             }]
         } else {
@@ -139,6 +197,7 @@ pub enum GraphicShaderInputBuildError {
     MissingVertexAttribute { location: u32, expected: vk::Format },
     VertexAttributeFormatMismatch { location: u32, expected: vk::Format, provided: vk::Format },
     UnexpectedVertexAttribute { location: u32, provided: vk::Format },
+    UnsupportedAutoVertexFormat { location: u32, format: vk::Format },
     DescriptorLayoutCreationFailed(vk::Result),
 }
 
@@ -167,6 +226,11 @@ impl std::fmt::Display for GraphicShaderInputBuildError {
                 "unexpected vertex attribute at location {} (provided {:?}, but shader has no input at this location)",
                 location, provided
             ),
+            GraphicShaderInputBuildError::UnsupportedAutoVertexFormat { location, format } => write!(
+                f,
+                "cannot synthesize vertex attribute at location {} (format {:?} has no known size)",
+                location, format
+            ),
             GraphicShaderInputBuildError::DescriptorLayoutCreationFailed(e) => {
                 write!(f, "failed to create merged descriptor set layouts: {:?}", e)
             }
@@ -182,6 +246,9 @@ pub struct GraphicShaderInputBuilder {
     fragment_shader: Option<Arc<Shader>>,
     vertex_bindings: Vec<VertexBinding>,
     vertex_attributes: Vec<VertexAttribute>,
+    auto_vertex_layout: bool,
+    strict_vertex_formats: bool,
+    no_vertex_input: bool,
 }
 
 impl GraphicShaderInputBuilder {
@@ -212,22 +279,145 @@ impl GraphicShaderInputBuilder {
         self
     }
 
-    pub fn build(self) -> Result<GraphicShaderInput, GraphicShaderInputBuildError> {
+    /// Append `T`'s layout as a per-instance stream at `binding`.
+    ///
+    /// The binding is stepped at `VertexInputRate::INSTANCE`, and `T`'s attribute locations are shifted
+    /// past the highest location already declared, so a per-vertex [`Self::vertex_layout`] must be added first.
+    /// The vertex shader's instance inputs have to use those shifted locations.
+    pub fn instance_layout<T: VertexLayout>(mut self, binding: u32) -> Self {
+        let (mut instance_binding, attributes) = T::vertex_layout();
+        instance_binding.binding = binding;
+        instance_binding.input_rate = vk::VertexInputRate::INSTANCE;
+
+        let base_location = self.vertex_attributes.iter().map(|a| a.location + 1).max().unwrap_or(0);
+        self.vertex_bindings.push(instance_binding);
+        self.vertex_attributes.extend(attributes.into_iter().map(|a| VertexAttribute {
+            location: base_location + a.location,
+            binding,
+            ..a
+        }));
+        self
+    }
+
+    /// Derive the vertex layout from the vertex shader reflection when no explicit layout is given.
+    ///
+    /// Synthesizes a single tightly packed binding 0 with attributes in location order.
+    /// Offsets assume no padding, so this won't match a `#[repr(C)]` struct with alignment gaps;
+    /// use [`Self::vertex_layout`] for those.
+    pub fn auto_vertex_layout(mut self) -> Self {
+        self.auto_vertex_layout = true;
+        self
+    }
+
+    /// Require vertex attribute formats to equal the reflected shader input formats,
+    /// instead of only being [compatible](formats_compatible).
+    pub fn strict_vertex_formats(mut self) -> Self {
+        self.strict_vertex_formats = true;
+        self
+    }
+
+    /// Declare that the vertex shader reads no vertex attributes, e.g. a fullscreen triangle
+    /// generating its positions from `SV_VertexID`.
+    ///
+    /// Vertex input validation is skipped, since system value inputs may still show up in the
+    /// reflection. The vertex shader must not read any vertex attribute: none are bound, and
+    /// [`Self::build`] panics if a binding or attribute was added.
+    pub fn no_vertex_input(mut self) -> Self {
+        self.no_vertex_input = true;
+        self
+    }
+
+    pub fn build(mut self) -> Result<GraphicShaderInput, GraphicShaderInputBuildError> {
         let Some(vs) = self.vertex_shader else {
             return Err(GraphicShaderInputBuildError::MissingVertexShader);
         };
-        GraphicShaderInput::new(
+
+        if self.no_vertex_input {
+            assert!(
+                self.vertex_bindings.is_empty() && self.vertex_attributes.is_empty(),
+                "GraphicShaderInputBuilder: '{}' declared no_vertex_input but has {} vertex bindings and {} attributes",
+                vs.name(),
+                self.vertex_bindings.len(),
+                self.vertex_attributes.len(),
+            );
+            return GraphicShaderInput::new_impl(vs, self.fragment_shader, Vec::new(), Vec::new(), None);
+        }
+
+        if self.auto_vertex_layout && self.vertex_bindings.is_empty() && self.vertex_attributes.is_empty() {
+            let (binding, attributes) = synthesize_vertex_layout(vs.reflection())?;
+            if let Some(binding) = binding {
+                log::warn!(
+                    "Auto vertex layout for '{}': offsets assume tightly packed attributes (stride {}), \
+                     this won't match a vertex struct with alignment padding.",
+                    vs.name(),
+                    binding.stride
+                );
+                self.vertex_bindings.push(binding);
+                self.vertex_attributes = attributes;
+            }
+        }
+
+        GraphicShaderInput::new_impl(
             vs,
             self.fragment_shader,
             self.vertex_bindings,
             self.vertex_attributes,
+            Some(self.strict_vertex_formats),
         )
     }
 }
 
+fn synthesize_vertex_layout(
+    vs_reflection: &ShaderReflection,
+) -> Result<(Option<VertexBinding>, Vec<VertexAttribute>), GraphicShaderInputBuildError> {
+    if vs_reflection.vertex_inputs.is_empty() {
+        return Ok((None, Vec::new()));
+    }
+
+    let mut inputs = vs_reflection.vertex_inputs.iter().collect::<Vec<_>>();
+    inputs.sort_by_key(|vi| vi.location);
+
+    let mut offset = 0;
+    let mut attributes = Vec::with_capacity(inputs.len());
+    for vi in inputs {
+        let Some(size) = vertex_format_size(vi.format) else {
+            return Err(GraphicShaderInputBuildError::UnsupportedAutoVertexFormat {
+                location: vi.location,
+                format: vi.format,
+            });
+        };
+
+        attributes.push(VertexAttribute {
+            location: vi.location,
+            binding: 0,
+            format: vi.format,
+            offset,
+        });
+        offset += size;
+    }
+
+    let binding = VertexBinding {
+        binding: 0,
+        stride: offset,
+        input_rate: vk::VertexInputRate::VERTEX,
+    };
+    Ok((Some(binding), attributes))
+}
+
+fn vertex_format_size(format: vk::Format) -> Option<u32> {
+    match format {
+        vk::Format::R32_SFLOAT | vk::Format::R32_SINT | vk::Format::R32_UINT => Some(4),
+        vk::Format::R32G32_SFLOAT | vk::Format::R32G32_SINT | vk::Format::R32G32_UINT => Some(8),
+        vk::Format::R32G32B32_SFLOAT | vk::Format::R32G32B32_SINT | vk::Format::R32G32B32_UINT => Some(12),
+        vk::Format::R32G32B32A32_SFLOAT | vk::Format::R32G32B32A32_SINT | vk::Format::R32G32B32A32_UINT => Some(16),
+        _ => None,
+    }
+}
+
 fn validate_vertex_inputs(
     vs_reflection: &ShaderReflection,
     vertex_attributes: &[VertexAttribute],
+    strict_formats: bool,
 ) -> Result<(), GraphicShaderInputBuildError> {
     // If shader reflection doesn't provide inputs, accept only empty attributes.
     if vs_reflection.vertex_inputs.is_empty() {
@@ -262,7 +452,7 @@ fn validate_vertex_inputs(
                     expected: *exp_fmt,
                 })
             }
-            Some(got_fmt) if got_fmt != exp_fmt => {
+            Some(got_fmt) if got_fmt != exp_fmt && (strict_formats || !formats_compatible(*exp_fmt, *got_fmt)) => {
                 return Err(GraphicShaderInputBuildError::VertexAttributeFormatMismatch {
                     location: *loc,
                     expected: *exp_fmt,
@@ -286,6 +476,81 @@ fn validate_vertex_inputs(
     Ok(())
 }
 
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum VertexNumericType {
+    Float,
+    SInt,
+    UInt,
+}
+
+/// Numeric type the shader reads and component count of a vertex format, `None` if unknown.
+fn vertex_format_class(format: vk::Format) -> Option<(VertexNumericType, u32)> {
+    use VertexNumericType::*;
+    let class = match format {
+        vk::Format::R8_UNORM | vk::Format::R8_SNORM | vk::Format::R16_UNORM | vk::Format::R16_SNORM
+        | vk::Format::R16_SFLOAT | vk::Format::R32_SFLOAT => (Float, 1),
+        vk::Format::R8G8_UNORM | vk::Format::R8G8_SNORM | vk::Format::R16G16_UNORM | vk::Format::R16G16_SNORM
+        | vk::Format::R16G16_SFLOAT | vk::Format::R32G32_SFLOAT => (Float, 2),
+        vk::Format::R8G8B8_UNORM | vk::Format::R8G8B8_SNORM | vk::Format::R16G16B16_UNORM
+        | vk::Format::R16G16B16_SNORM | vk::Format::R16G16B16_SFLOAT | vk::Format::R32G32B32_SFLOAT
+        | vk::Format::B10G11R11_UFLOAT_PACK32 => (Float, 3),
+        vk::Format::R8G8B8A8_UNORM | vk::Format::R8G8B8A8_SNORM | vk::Format::B8G8R8A8_UNORM
+        | vk::Format::R16G16B16A16_UNORM | vk::Format::R16G16B16A16_SNORM | vk::Format::R16G16B16A16_SFLOAT
+        | vk::Format::R32G32B32A32_SFLOAT | vk::Format::A2B10G10R10_UNORM_PACK32
+        | vk::Format::A2B10G10R10_SNORM_PACK32 | vk::Format::A2R10G10B10_UNORM_PACK32
+        | vk::Format::A2R10G10B10_SNORM_PACK32 => (Float, 4),
+
+        vk::Format::R8_SINT | vk::Format::R16_SINT | vk::Format::R32_SINT => (SInt, 1),
+        vk::Format::R8G8_SINT | vk::Format::R16G16_SINT | vk::Format::R32G32_SINT => (SInt, 2),
+        vk::Format::R8G8B8_SINT | vk::Format::R16G16B16_SINT | vk::Format::R32G32B32_SINT => (SInt, 3),
+        vk::Format::R8G8B8A8_SINT | vk::Format::R16G16B16A16_SINT | vk::Format::R32G32B32A32_SINT => (SInt, 4),
+
+        vk::Format::R8_UINT | vk::Format::R16_UINT | vk::Format::R32_UINT => (UInt, 1),
+        vk::Format::R8G8_UINT | vk::Format::R16G16_UINT | vk::Format::R32G32_UINT => (UInt, 2),
+        vk::Format::R8G8B8_UINT | vk::Format::R16G16B16_UINT | vk::Format::R32G32B32_UINT => (UInt, 3),
+        vk::Format::R8G8B8A8_UINT | vk::Format::R16G16B16A16_UINT | vk::Format::R32G32B32A32_UINT => (UInt, 4),
+        _ => return None,
+    };
+    Some(class)
+}
+
+/// Whether a vertex attribute in `provided_fmt` can feed a shader input reflected as `shader_fmt`.
+///
+/// The vertex fetch converts the attribute to the type the shader declares, so the formats only need
+/// the same numeric type family (float for `*FLOAT`/`*UNORM`/`*SNORM`, signed or unsigned integer) and
+/// the same component count. The one exception is the packed `A2*10*10*10` formats (e.g. normals from a
+/// `#[vertex(format = "...")]` override), which may also feed a three component float input, the
+/// 2-bit alpha is dropped.
+///
+/// ```
+/// use zenith_rhi::{formats_compatible, vk};
+///
+/// assert!(formats_compatible(vk::Format::R32G32B32A32_SFLOAT, vk::Format::R8G8B8A8_UNORM));
+/// assert!(formats_compatible(vk::Format::R32G32B32_SFLOAT, vk::Format::A2B10G10R10_SNORM_PACK32));
+/// assert!(!formats_compatible(vk::Format::R32G32B32A32_SFLOAT, vk::Format::R8G8B8A8_UINT));
+/// assert!(!formats_compatible(vk::Format::R32G32_SFLOAT, vk::Format::R32G32B32_SFLOAT));
+/// ```
+pub fn formats_compatible(shader_fmt: vk::Format, provided_fmt: vk::Format) -> bool {
+    if shader_fmt == provided_fmt {
+        return true;
+    }
+
+    let (Some((shader_ty, shader_count)), Some((provided_ty, provided_count))) =
+        (vertex_format_class(shader_fmt), vertex_format_class(provided_fmt))
+    else {
+        return false;
+    };
+
+    let packed_normal = matches!(
+        provided_fmt,
+        vk::Format::A2B10G10R10_UNORM_PACK32
+            | vk::Format::A2B10G10R10_SNORM_PACK32
+            | vk::Format::A2R10G10B10_UNORM_PACK32
+            | vk::Format::A2R10G10B10_SNORM_PACK32
+    );
+    shader_ty == provided_ty && (shader_count == provided_count || (packed_normal && shader_count == 3))
+}
+
 impl Hash for GraphicShaderInput {
     fn hash<H: Hasher>(&self, state: &mut H) {
         // Hash by shader module handle + stage + entry point, not Arc pointer
@@ -334,6 +599,16 @@ pub trait VertexLayout {
 }
 
 /// Color attachment configuration used by dynamic rendering and (partially) by pipeline blend state.
+///
+/// Blend presets on [`ColorAttachmentDescBuilder`] (all use `BlendOp::ADD`):
+///
+/// | preset          | src color | dst color           | src alpha | dst alpha           |
+/// |-----------------|-----------|---------------------|-----------|---------------------|
+/// | `additive`      | ONE       | ONE                 | ONE       | ONE                 |
+/// | `alpha_blend`   | SRC_ALPHA | ONE_MINUS_SRC_ALPHA | ONE       | ONE_MINUS_SRC_ALPHA |
+/// | `premultiplied` | ONE       | ONE_MINUS_SRC_ALPHA | ONE       | ONE_MINUS_SRC_ALPHA |
+///
+/// `translucent` is an alias of `alpha_blend`.
 #[derive(Clone, Debug, Builder)]
 #[builder(setter(into), default)]
 pub struct ColorAttachmentDesc {
@@ -384,6 +659,53 @@ impl ColorAttachmentDesc {
     }
 }
 
+/// Load/store/clear of a color target, with optional blending.
+///
+/// Converts into a [`ColorAttachmentDesc`]: only the blend and write mask fields of `blend` are
+/// used, the load/store ops and clear value always come from this info. Without `blend`, the
+/// target is written without blending.
+#[derive(Clone, Debug)]
+pub struct ColorInfo {
+    pub load_op: vk::AttachmentLoadOp,
+    pub store_op: vk::AttachmentStoreOp,
+    pub clear_value: [f32; 4],
+    pub blend: Option<ColorAttachmentDesc>,
+}
+
+impl Default for ColorInfo {
+    fn default() -> Self {
+        let desc = ColorAttachmentDesc::default();
+        Self {
+            load_op: desc.load_op,
+            store_op: desc.store_op,
+            clear_value: desc.clear_value,
+            blend: None,
+        }
+    }
+}
+
+impl From<ColorInfo> for ColorAttachmentDesc {
+    fn from(info: ColorInfo) -> Self {
+        Self {
+            load_op: info.load_op,
+            store_op: info.store_op,
+            clear_value: info.clear_value,
+            ..info.blend.unwrap_or_default()
+        }
+    }
+}
+
+impl From<ColorAttachmentDesc> for ColorInfo {
+    fn from(desc: ColorAttachmentDesc) -> Self {
+        Self {
+            load_op: desc.load_op,
+            store_op: desc.store_op,
+            clear_value: desc.clear_value,
+            blend: Some(desc),
+        }
+    }
+}
+
 impl ColorAttachmentDescBuilder {
     pub fn discard_input(&mut self) -> &mut Self {
         self.load_op.replace(vk::AttachmentLoadOp::DONT_CARE);
@@ -400,13 +722,65 @@ impl ColorAttachmentDescBuilder {
         self
     }
 
+    /// Clear to a color authored in sRGB (e.g. picked from a color picker).
+    ///
+    /// `clear_value` is linear and encoded by the hardware when the attachment format is sRGB,
+    /// so the color is decoded to linear here, and an sRGB attachment displays exactly `color`.
+    /// Alpha is linear in both cases.
+    pub fn clear_color_srgb(&mut self, color: [f32; 4]) -> &mut Self {
+        self.clear_value.replace([srgb_to_linear(color[0]), srgb_to_linear(color[1]), srgb_to_linear(color[2]), color[3]]);
+        self
+    }
+
+    /// Classic alpha blending. Kept as an alias of [`Self::alpha_blend`].
     pub fn translucent(&mut self) -> &mut Self {
+        self.alpha_blend()
+    }
+
+    /// `src + dst`, e.g. for particles and light accumulation.
+    pub fn additive(&mut self) -> &mut Self {
+        self.blend_factors(
+            vk::BlendFactor::ONE,
+            vk::BlendFactor::ONE,
+            vk::BlendFactor::ONE,
+            vk::BlendFactor::ONE,
+        )
+    }
+
+    /// `src * src.a + dst * (1 - src.a)` for straight (non-premultiplied) alpha.
+    pub fn alpha_blend(&mut self) -> &mut Self {
+        self.blend_factors(
+            vk::BlendFactor::SRC_ALPHA,
+            vk::BlendFactor::ONE_MINUS_SRC_ALPHA,
+            vk::BlendFactor::ONE,
+            vk::BlendFactor::ONE_MINUS_SRC_ALPHA,
+        )
+    }
+
+    /// `src + dst * (1 - src.a)` for premultiplied alpha.
+    pub fn premultiplied(&mut self) -> &mut Self {
+        self.blend_factors(
+            vk::BlendFactor::ONE,
+            vk::BlendFactor::ONE_MINUS_SRC_ALPHA,
+            vk::BlendFactor::ONE,
+            vk::BlendFactor::ONE_MINUS_SRC_ALPHA,
+        )
+    }
+
+    fn blend_factors(
+        &mut self,
+        src_color: vk::BlendFactor,
+        dst_color: vk::BlendFactor,
+        src_alpha: vk::BlendFactor,
+        dst_alpha: vk::BlendFactor,
+    ) -> &mut Self {
         self.blend_enable.replace(true);
-        self.src_color_blend.replace(vk::BlendFactor::SRC_ALPHA);
-        self.dst_color_blend.replace(vk::BlendFactor::DST_ALPHA);
+        self.src_color_blend.replace(src_color);
+        self.dst_color_blend.replace(dst_color);
         self.color_blend_op.replace(vk::BlendOp::ADD);
-        self.src_alpha_blend.replace(vk::BlendFactor::ZERO);
-        self.dst_alpha_blend.replace(vk::BlendFactor::SRC_ALPHA);
+        self.src_alpha_blend.replace(src_alpha);
+        self.dst_alpha_blend.replace(dst_alpha);
+        self.alpha_blend_op.replace(vk::BlendOp::ADD);
         self
     }
 }
@@ -417,7 +791,10 @@ pub struct DepthStencilDesc {
     pub depth_test_enable: bool,
     pub depth_write_enable: bool,
     pub depth_compare_op: vk::CompareOp,
+    /// Requires the `depthBounds` device feature.
     pub depth_bounds_test_enable: bool,
+    pub min_depth_bounds: f32,
+    pub max_depth_bounds: f32,
 
     pub depth_load_op: vk::AttachmentLoadOp,
     pub depth_store_op: vk::AttachmentStoreOp,
@@ -439,6 +816,8 @@ impl Default for DepthStencilDesc {
             depth_write_enable: false,
             depth_compare_op: vk::CompareOp::LESS,
             depth_bounds_test_enable: false,
+            min_depth_bounds: 0.0,
+            max_depth_bounds: 1.0,
             depth_load_op: vk::AttachmentLoadOp::CLEAR,
             depth_store_op: vk::AttachmentStoreOp::STORE,
             depth_clear_value: 1.0,
@@ -452,6 +831,30 @@ impl Default for DepthStencilDesc {
     }
 }
 
+impl DepthStencilDescBuilder {
+    /// Reversed depth: near plane at 1.0, far plane cleared to 0.0.
+    pub fn reverse_z(&mut self) -> &mut Self {
+        self.depth_compare_op.replace(vk::CompareOp::GREATER_OR_EQUAL);
+        self.depth_clear_value.replace(0.0);
+        self
+    }
+
+    /// Depth test against an existing depth buffer without writing to it.
+    pub fn read_only_depth(&mut self) -> &mut Self {
+        self.depth_test_enable.replace(true);
+        self.depth_write_enable.replace(false);
+        self
+    }
+
+    pub fn stencil_write_mask(&mut self, front: u32, back: u32) -> &mut Self {
+        let stencil_front = self.stencil_front.unwrap_or_default().write_mask(front);
+        let stencil_back = self.stencil_back.unwrap_or_default().write_mask(back);
+        self.stencil_front.replace(stencil_front);
+        self.stencil_back.replace(stencil_back);
+        self
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq, Builder)]
 #[builder(setter(into), default)]
 pub struct InputAssemblyState {
@@ -473,6 +876,8 @@ impl DepthStencilDesc {
             .depth_write_enable(self.depth_write_enable)
             .depth_compare_op(self.depth_compare_op)
             .depth_bounds_test_enable(self.depth_bounds_test_enable)
+            .min_depth_bounds(self.min_depth_bounds)
+            .max_depth_bounds(self.max_depth_bounds)
             .stencil_test_enable(self.stencil_test_enable)
             .front(self.stencil_front)
             .back(self.stencil_back)
@@ -511,9 +916,14 @@ pub struct RasterizationState {
     pub cull_mode: vk::CullModeFlags,
     pub front_face: vk::FrontFace,
     pub depth_clamp: bool,
+    /// Discard all primitives right before rasterization, so no fragments are produced.
+    /// Only vertex processing side effects remain, e.g. storage writes, transform feedback or queries.
+    pub rasterizer_discard: bool,
     pub depth_bias_enable: bool,
     pub depth_bias_constant: f32,
     pub depth_bias_slope: f32,
+    /// Maximum (or minimum if negative) depth bias, 0.0 disables clamping.
+    pub depth_bias_clamp: f32,
     pub line_width: f32,
 }
 
@@ -524,9 +934,11 @@ impl Default for RasterizationState {
             cull_mode: vk::CullModeFlags::BACK,
             front_face: vk::FrontFace::COUNTER_CLOCKWISE,
             depth_clamp: false,
+            rasterizer_discard: false,
             depth_bias_enable: false,
             depth_bias_constant: 0.0,
             depth_bias_slope: 0.0,
+            depth_bias_clamp: 0.0,
             line_width: 1.0,
         }
     }
@@ -539,9 +951,11 @@ impl PartialEq for RasterizationState {
             && self.cull_mode.as_raw() == other.cull_mode.as_raw()
             && self.front_face.as_raw() == other.front_face.as_raw()
             && self.depth_clamp == other.depth_clamp
+            && self.rasterizer_discard == other.rasterizer_discard
             && self.depth_bias_enable == other.depth_bias_enable
             && self.depth_bias_constant.to_bits() == other.depth_bias_constant.to_bits()
             && self.depth_bias_slope.to_bits() == other.depth_bias_slope.to_bits()
+            && self.depth_bias_clamp.to_bits() == other.depth_bias_clamp.to_bits()
             && self.line_width.to_bits() == other.line_width.to_bits()
     }
 }
@@ -554,9 +968,11 @@ impl Hash for RasterizationState {
         self.cull_mode.as_raw().hash(state);
         (self.front_face.as_raw() as i32).hash(state);
         self.depth_clamp.hash(state);
+        self.rasterizer_discard.hash(state);
         self.depth_bias_enable.hash(state);
         self.depth_bias_constant.to_bits().hash(state);
         self.depth_bias_slope.to_bits().hash(state);
+        self.depth_bias_clamp.to_bits().hash(state);
         self.line_width.to_bits().hash(state);
     }
 }
@@ -566,13 +982,14 @@ impl RasterizationState {
     pub fn to_vk(&self) -> vk::PipelineRasterizationStateCreateInfo<'static> {
         vk::PipelineRasterizationStateCreateInfo::default()
             .depth_clamp_enable(self.depth_clamp)
-            .rasterizer_discard_enable(false)
+            .rasterizer_discard_enable(self.rasterizer_discard)
             .polygon_mode(self.polygon_mode)
             .cull_mode(self.cull_mode)
             .front_face(self.front_face)
             .depth_bias_enable(self.depth_bias_enable)
-            .depth_bias_constant_factor(f32::from_bits(self.depth_bias_constant as u32))
-            .depth_bias_slope_factor(f32::from_bits(self.depth_bias_slope as u32))
+            .depth_bias_constant_factor(self.depth_bias_constant)
+            .depth_bias_slope_factor(self.depth_bias_slope)
+            .depth_bias_clamp(self.depth_bias_clamp)
             .line_width(self.line_width)
     }
 }
@@ -747,6 +1164,8 @@ impl Hash for GraphicPipelineState {
             ds.depth_write_enable.hash(state);
             (ds.depth_compare_op.as_raw() as i32).hash(state);
             ds.depth_bounds_test_enable.hash(state);
+            ds.min_depth_bounds.to_bits().hash(state);
+            ds.max_depth_bounds.to_bits().hash(state);
             (ds.depth_load_op.as_raw() as i32).hash(state);
             (ds.depth_store_op.as_raw() as i32).hash(state);
             ds.depth_clear_value.to_bits().hash(state);
@@ -756,8 +1175,8 @@ impl Hash for GraphicPipelineState {
             ds.stencil_clear_value.hash(state);
 
             // front/back stencil op state
-            hash_vk_stencil_op_state(&ds.stencil_front, state);
-            hash_vk_stencil_op_state(&ds.stencil_back, state);
+            hash_vk_stencil_op_state(&ds.stencil_front, &self.dynamic_states, state);
+            hash_vk_stencil_op_state(&ds.stencil_back, &self.dynamic_states, state);
         }
         self.color_blend.hash(state);
         for ds in &self.dynamic_states {
@@ -771,7 +1190,7 @@ impl PartialEq for GraphicPipelineState {
         self.input_assembly == other.input_assembly
             && self.rasterization == other.rasterization
             && self.multisample == other.multisample
-            && eq_depth_stencil_opt(&self.depth_stencil, &other.depth_stencil)
+            && eq_depth_stencil_opt(&self.depth_stencil, &other.depth_stencil, &self.dynamic_states)
             && self.color_blend == other.color_blend
             && self.dynamic_states == other.dynamic_states
     }
@@ -827,32 +1246,62 @@ impl GraphicPipelineStateBuilder {
         self
     }
 
+    /// Set the stencil reference per draw with `CommandEncoder::set_stencil_reference`,
+    /// instead of the `reference` of the static stencil op states.
+    pub fn dynamic_stencil_reference(self) -> Self {
+        self.push_dynamic_state(vk::DynamicState::STENCIL_REFERENCE)
+    }
+
+    /// Set the stencil compare mask per draw with `CommandEncoder::set_stencil_compare_mask`.
+    pub fn dynamic_stencil_compare_mask(self) -> Self {
+        self.push_dynamic_state(vk::DynamicState::STENCIL_COMPARE_MASK)
+    }
+
+    /// Set the stencil write mask per draw with `CommandEncoder::set_stencil_write_mask`.
+    pub fn dynamic_stencil_write_mask(self) -> Self {
+        self.push_dynamic_state(vk::DynamicState::STENCIL_WRITE_MASK)
+    }
+
+    fn push_dynamic_state(mut self, dynamic_state: vk::DynamicState) -> Self {
+        if !self.state.dynamic_states.contains(&dynamic_state) {
+            self.state.dynamic_states.push(dynamic_state);
+        }
+        self
+    }
+
     pub fn build(self) -> GraphicPipelineState {
         self.state
     }
 }
 
-fn hash_vk_stencil_op_state<H: Hasher>(s: &vk::StencilOpState, state: &mut H) {
+// Stencil values set dynamically are ignored by the pipeline, so they are left out of the key.
+fn hash_vk_stencil_op_state<H: Hasher>(s: &vk::StencilOpState, dynamic: &[vk::DynamicState], state: &mut H) {
     (s.fail_op.as_raw() as i32).hash(state);
     (s.pass_op.as_raw() as i32).hash(state);
     (s.depth_fail_op.as_raw() as i32).hash(state);
     (s.compare_op.as_raw() as i32).hash(state);
-    s.compare_mask.hash(state);
-    s.write_mask.hash(state);
-    s.reference.hash(state);
+    if !dynamic.contains(&vk::DynamicState::STENCIL_COMPARE_MASK) {
+        s.compare_mask.hash(state);
+    }
+    if !dynamic.contains(&vk::DynamicState::STENCIL_WRITE_MASK) {
+        s.write_mask.hash(state);
+    }
+    if !dynamic.contains(&vk::DynamicState::STENCIL_REFERENCE) {
+        s.reference.hash(state);
+    }
 }
 
-fn eq_vk_stencil_op_state(a: &vk::StencilOpState, b: &vk::StencilOpState) -> bool {
+fn eq_vk_stencil_op_state(a: &vk::StencilOpState, b: &vk::StencilOpState, dynamic: &[vk::DynamicState]) -> bool {
     a.fail_op == b.fail_op
         && a.pass_op == b.pass_op
         && a.depth_fail_op == b.depth_fail_op
         && a.compare_op == b.compare_op
-        && a.compare_mask == b.compare_mask
-        && a.write_mask == b.write_mask
-        && a.reference == b.reference
+        && (dynamic.contains(&vk::DynamicState::STENCIL_COMPARE_MASK) || a.compare_mask == b.compare_mask)
+        && (dynamic.contains(&vk::DynamicState::STENCIL_WRITE_MASK) || a.write_mask == b.write_mask)
+        && (dynamic.contains(&vk::DynamicState::STENCIL_REFERENCE) || a.reference == b.reference)
 }
 
-fn eq_depth_stencil_opt(a: &Option<DepthStencilDesc>, b: &Option<DepthStencilDesc>) -> bool {
+fn eq_depth_stencil_opt(a: &Option<DepthStencilDesc>, b: &Option<DepthStencilDesc>, dynamic: &[vk::DynamicState]) -> bool {
     match (a, b) {
         (None, None) => true,
         (Some(a), Some(b)) => {
@@ -860,6 +1309,8 @@ fn eq_depth_stencil_opt(a: &Option<DepthStencilDesc>, b: &Option<DepthStencilDes
                 && a.depth_write_enable == b.depth_write_enable
                 && a.depth_compare_op == b.depth_compare_op
                 && a.depth_bounds_test_enable == b.depth_bounds_test_enable
+                && a.min_depth_bounds.to_bits() == b.min_depth_bounds.to_bits()
+                && a.max_depth_bounds.to_bits() == b.max_depth_bounds.to_bits()
                 && a.depth_load_op == b.depth_load_op
                 && a.depth_store_op == b.depth_store_op
                 && a.depth_clear_value.to_bits() == b.depth_clear_value.to_bits()
@@ -867,8 +1318,8 @@ fn eq_depth_stencil_opt(a: &Option<DepthStencilDesc>, b: &Option<DepthStencilDes
                 && a.stencil_load_op == b.stencil_load_op
                 && a.stencil_store_op == b.stencil_store_op
                 && a.stencil_clear_value == b.stencil_clear_value
-                && eq_vk_stencil_op_state(&a.stencil_front, &b.stencil_front)
-                && eq_vk_stencil_op_state(&a.stencil_back, &b.stencil_back)
+                && eq_vk_stencil_op_state(&a.stencil_front, &b.stencil_front, dynamic)
+                && eq_vk_stencil_op_state(&a.stencil_back, &b.stencil_back, dynamic)
         }
         _ => false,
     }
@@ -934,6 +1385,35 @@ impl CommonPipeline {
         desc: &GraphicPipelineDesc,
         cache: vk::PipelineCache,
     ) -> Result<(Vec<DescriptorSetLayout>, Self), vk::Result> {
+        let (layouts, layout) = Self::create_graphic_layouts(device, desc)?;
+
+        let pipeline = match Self::create_graphic_handle(device.handle(), desc, layout, cache) {
+            Ok(pipeline) => pipeline,
+            Err(err) => {
+                unsafe { device.handle().destroy_pipeline_layout(layout, None); }
+                return Err(err);
+            }
+        };
+
+        log::trace!("create graphic pipeline.");
+
+        let pipeline = Self::from_raw(name, device.handle().clone(), layout, pipeline);
+        device.set_debug_name(&pipeline);
+        Ok((layouts, pipeline))
+    }
+
+    /// Create the descriptor set layouts and pipeline layout of a graphic pipeline.
+    pub(crate) fn create_graphic_layouts(
+        device: &RenderDevice,
+        desc: &GraphicPipelineDesc,
+    ) -> Result<(Vec<DescriptorSetLayout>, vk::PipelineLayout), vk::Result> {
+        if desc.state.depth_stencil.as_ref().is_some_and(|ds| ds.depth_bounds_test_enable)
+            && device.enabled_features().core.depth_bounds != vk::TRUE
+        {
+            log::error!("create_graphic_layouts: depth bounds test enabled but the device doesn't support depthBounds.");
+            return Err(vk::Result::ERROR_FEATURE_NOT_PRESENT);
+        }
+
         let max_set = desc.shader.merged_reflection.max_set().unwrap_or(0);
 
         let layouts = (0..=max_set).into_iter()
@@ -944,7 +1424,18 @@ impl CommonPipeline {
             .collect::<Result<Vec<_>, _>>()?;
 
         let layout = desc.shader.create_pipeline_layout(device, &layouts)?;
+        Ok((layouts, layout))
+    }
 
+    /// Create the raw graphic pipeline handle.
+    ///
+    /// Only needs the raw device, so it can run on any thread.
+    pub(crate) fn create_graphic_handle(
+        device: &ash::Device,
+        desc: &GraphicPipelineDesc,
+        layout: vk::PipelineLayout,
+        cache: vk::PipelineCache,
+    ) -> Result<vk::Pipeline, vk::Result> {
         // Build shader stages (fragment shader is optional)
         let mut shader_stages = Vec::with_capacity(if desc.shader.fragment_shader.is_some() { 2 } else { 1 });
         shader_stages.push(
@@ -1004,9 +1495,11 @@ impl CommonPipeline {
             .as_ref()
             .map(|ds| ds.to_vk());
 
-        // Color blend
+        // Color blend, omitted for depth-only pipelines: with dynamic rendering and no color
+        // attachments `pColorBlendState` is ignored and may be null.
         let blend_attachments = desc.state.color_blend.to_vk_attachments();
-        let color_blend_state = desc.state.color_blend.to_vk(&blend_attachments);
+        let color_blend_state = (!desc.attachments.color_formats.is_empty())
+            .then(|| desc.state.color_blend.to_vk(&blend_attachments));
 
         // Viewport state (dynamic)
         let viewport_state = vk::PipelineViewportStateCreateInfo::default()
@@ -1027,7 +1520,6 @@ impl CommonPipeline {
             .viewport_state(&viewport_state)
             .rasterization_state(&rasterization)
             .multisample_state(&multisample)
-            .color_blend_state(&color_blend_state)
             .dynamic_state(&dynamic_state)
             .layout(layout)
             .push_next(&mut rendering_info);
@@ -1035,20 +1527,28 @@ impl CommonPipeline {
         if let Some(ref depth_stencil) = depth_stencil_state {
             pipeline_info = pipeline_info.depth_stencil_state(depth_stencil);
         }
+        if let Some(ref color_blend_state) = color_blend_state {
+            pipeline_info = pipeline_info.color_blend_state(color_blend_state);
+        }
 
-        let pipelines = unsafe { device.handle().create_graphics_pipelines(cache, &[pipeline_info], None) }
+        let pipelines = unsafe { device.create_graphics_pipelines(cache, &[pipeline_info], None) }
             .map_err(|e| e.1)?;
+        Ok(pipelines[0])
+    }
 
-        log::trace!("create graphic pipeline.");
-
-        let pipeline = Self {
+    /// Take ownership of already created pipeline and layout handles.
+    pub(crate) fn from_raw(
+        name: &str,
+        device: ash::Device,
+        layout: vk::PipelineLayout,
+        pipeline: vk::Pipeline,
+    ) -> Self {
+        Self {
             name: name.to_owned(),
             layout,
-            pipeline: pipelines[0],
-            device: device.handle().clone(),
-        };
-        device.set_debug_name(&pipeline);
-        Ok((layouts, pipeline))
+            pipeline,
+            device,
+        }
     }
 
     #[inline]
@@ -1086,8 +1586,49 @@ impl DebuggableObject for CommonPipeline {
 pub struct GraphicPipeline {
     pipeline: CommonPipeline,
     pub(crate) descriptor_layouts: Vec<DescriptorSetLayout>,
+    reflection: ShaderReflection,
 }
 
+/// SPIR-V of the built-in fullscreen triangle vertex shader, equivalent to:
+///
+/// ```glsl
+/// layout(location = 0) out vec2 uv;
+/// void main() {
+///     uv = vec2((gl_VertexIndex << 1) & 2, gl_VertexIndex & 2);
+///     gl_Position = vec4(uv * 2.0 - 1.0, 0.0, 1.0);
+/// }
+/// ```
+const FULLSCREEN_VS_SPIRV: [u32; 168] = [
+    0x07230203, 0x00010000, 0x00000000, 0x00000020, 0x00000000, 0x00020011,
+    0x00000001, 0x0003000e, 0x00000000, 0x00000001, 0x0008000f, 0x00000000,
+    0x00000012, 0x6e69616d, 0x00000000, 0x0000000a, 0x0000000b, 0x0000000c,
+    0x00040047, 0x0000000a, 0x0000000b, 0x0000002a, 0x00040047, 0x0000000c,
+    0x0000000b, 0x00000000, 0x00040047, 0x0000000b, 0x0000001e, 0x00000000,
+    0x00020013, 0x00000001, 0x00030021, 0x00000002, 0x00000001, 0x00040015,
+    0x00000003, 0x00000020, 0x00000001, 0x00030016, 0x00000004, 0x00000020,
+    0x00040017, 0x00000005, 0x00000004, 0x00000002, 0x00040017, 0x00000006,
+    0x00000004, 0x00000004, 0x00040020, 0x00000007, 0x00000001, 0x00000003,
+    0x00040020, 0x00000008, 0x00000003, 0x00000005, 0x00040020, 0x00000009,
+    0x00000003, 0x00000006, 0x0004003b, 0x00000007, 0x0000000a, 0x00000001,
+    0x0004003b, 0x00000008, 0x0000000b, 0x00000003, 0x0004003b, 0x00000009,
+    0x0000000c, 0x00000003, 0x0004002b, 0x00000003, 0x0000000d, 0x00000001,
+    0x0004002b, 0x00000003, 0x0000000e, 0x00000002, 0x0004002b, 0x00000004,
+    0x0000000f, 0x00000000, 0x0004002b, 0x00000004, 0x00000010, 0x3f800000,
+    0x0004002b, 0x00000004, 0x00000011, 0x40000000, 0x00050036, 0x00000001,
+    0x00000012, 0x00000000, 0x00000002, 0x000200f8, 0x00000013, 0x0004003d,
+    0x00000003, 0x00000014, 0x0000000a, 0x000500c4, 0x00000003, 0x00000015,
+    0x00000014, 0x0000000d, 0x000500c7, 0x00000003, 0x00000016, 0x00000015,
+    0x0000000e, 0x000500c7, 0x00000003, 0x00000017, 0x00000014, 0x0000000e,
+    0x0004006f, 0x00000004, 0x00000018, 0x00000016, 0x0004006f, 0x00000004,
+    0x00000019, 0x00000017, 0x00050050, 0x00000005, 0x0000001a, 0x00000018,
+    0x00000019, 0x0003003e, 0x0000000b, 0x0000001a, 0x00050085, 0x00000004,
+    0x0000001b, 0x00000018, 0x00000011, 0x00050083, 0x00000004, 0x0000001c,
+    0x0000001b, 0x00000010, 0x00050085, 0x00000004, 0x0000001d, 0x00000019,
+    0x00000011, 0x00050083, 0x00000004, 0x0000001e, 0x0000001d, 0x00000010,
+    0x00070050, 0x00000006, 0x0000001f, 0x0000001c, 0x0000001e, 0x0000000f,
+    0x00000010, 0x0003003e, 0x0000000c, 0x0000001f, 0x000100fd, 0x00010038,
+];
+
 impl GraphicPipeline {
     /// Create a new graphics pipeline with dynamic rendering.
     pub fn new(
@@ -1106,10 +1647,67 @@ impl GraphicPipeline {
         cache: vk::PipelineCache,
     ) -> Result<Self, vk::Result> {
         let (layouts, pipeline) = CommonPipeline::new_graphic(name, device, desc, cache)?;
-        Ok(Self {
-            descriptor_layouts: layouts,
-            pipeline
-        })
+        Ok(Self::from_parts(layouts, pipeline, desc))
+    }
+
+    /// Create a screen-space pipeline drawing one triangle that covers the whole render target.
+    ///
+    /// `fragment` is paired with a built-in vertex shader without vertex inputs, which writes the
+    /// `[0, 1]` screen uv (top-left origin) to location 0. Culling and depth testing are off and the
+    /// color attachments are fully overwritten, so they are not loaded. Record with `draw(3, 1, 0, 0)`.
+    pub fn fullscreen(
+        name: &str,
+        device: &RenderDevice,
+        fragment: Arc<Shader>,
+        attachments: GraphicPipelineAttachments,
+    ) -> Result<Self, vk::Result> {
+        let vertex = Shader::from_spirv(
+            &format!("{name}.fullscreen_vs"),
+            device,
+            bytemuck::cast_slice(&FULLSCREEN_VS_SPIRV),
+            "main",
+            crate::ShaderStage::Vertex,
+        ).map_err(|e| match e {
+            crate::ShaderError::VulkanError(e) => e,
+            e => {
+                log::error!("fullscreen pipeline '{name}': built-in vertex shader is invalid: {e:?}");
+                vk::Result::ERROR_INITIALIZATION_FAILED
+            }
+        })?;
+
+        let shader = GraphicShaderInputBuilder::default()
+            .vertex_shader(Arc::new(vertex))
+            .fragment_shader(fragment)
+            .no_vertex_input()
+            .build()
+            .map_err(|e| {
+                log::error!("fullscreen pipeline '{name}': {e:?}");
+                vk::Result::ERROR_INITIALIZATION_FAILED
+            })?;
+
+        let mut state = GraphicPipelineStateBuilder::default()
+            .rasterization(RasterizationState { cull_mode: vk::CullModeFlags::NONE, ..Default::default() });
+        for _ in &attachments.color_formats {
+            state = state.push_color_attachment(ColorAttachmentDesc {
+                load_op: vk::AttachmentLoadOp::DONT_CARE,
+                ..Default::default()
+            });
+        }
+
+        let desc = GraphicPipelineDesc::new(shader, state.build(), attachments);
+        Self::new(name, device, &desc)
+    }
+
+    pub(crate) fn from_parts(
+        descriptor_layouts: Vec<DescriptorSetLayout>,
+        pipeline: CommonPipeline,
+        desc: &GraphicPipelineDesc,
+    ) -> Self {
+        Self {
+            descriptor_layouts,
+            pipeline,
+            reflection: desc.shader.merged_reflection.clone(),
+        }
     }
 
     /// Get the raw Vulkan pipeline handle.
@@ -1118,4 +1716,159 @@ impl GraphicPipeline {
     }
 
     pub fn layout(&self) -> vk::PipelineLayout { self.pipeline.layout }
+
+    /// Merged reflection of the pipeline shaders.
+    pub fn reflection(&self) -> &ShaderReflection { &self.reflection }
+
+    /// Descriptor set layouts, indexed by set.
+    pub fn descriptor_layouts(&self) -> &[DescriptorSetLayout] { &self.descriptor_layouts }
+
+    /// Number of descriptor set layouts in the pipeline layout.
+    pub fn descriptor_set_count(&self) -> usize { self.descriptor_layouts.len() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::TestDevice;
+    use crate::shader::VertexInputAttr;
+
+    #[test]
+    #[ignore = "requires a Vulkan 1.3 device"]
+    fn depth_only_pipeline_without_color_attachments() {
+        let device = TestDevice::new().expect("no Vulkan 1.3 device with a graphics queue");
+
+        let vertex = Shader::from_spirv(
+            "shader.depth_only.vs",
+            &device,
+            bytemuck::cast_slice(&FULLSCREEN_VS_SPIRV),
+            "main",
+            crate::ShaderStage::Vertex,
+        ).unwrap();
+        let shader = GraphicShaderInputBuilder::default()
+            .vertex_shader(Arc::new(vertex))
+            .no_vertex_input()
+            .build()
+            .unwrap();
+        let state = GraphicPipelineStateBuilder::default()
+            .depth_stencil(DepthStencilDescBuilder::default()
+                .depth_test_enable(true)
+                .depth_write_enable(true)
+                .build()
+                .unwrap())
+            .build();
+        let attachments = GraphicPipelineAttachments::default().with_depth_stencil_format(vk::Format::D16_UNORM);
+        assert!(attachments.color_formats.is_empty());
+
+        let desc = GraphicPipelineDesc::new(shader, state, attachments);
+        assert!(GraphicPipeline::new("pipeline.depth_only", &device, &desc).is_ok());
+    }
+
+    #[test]
+    fn color_info_overrides_blend_load_store() {
+        let info = ColorInfo {
+            load_op: vk::AttachmentLoadOp::LOAD,
+            store_op: vk::AttachmentStoreOp::DONT_CARE,
+            clear_value: [0.5, 0.5, 0.5, 1.0],
+            blend: Some(ColorAttachmentDescBuilder::default().additive().clear_input().build().unwrap()),
+        };
+        let desc = ColorAttachmentDesc::from(info);
+        assert!(desc.blend_enable);
+        assert_eq!(desc.dst_color_blend, vk::BlendFactor::ONE);
+        assert_eq!(desc.load_op, vk::AttachmentLoadOp::LOAD);
+        assert_eq!(desc.store_op, vk::AttachmentStoreOp::DONT_CARE);
+        assert_eq!(desc.clear_value, [0.5, 0.5, 0.5, 1.0]);
+
+        let desc = ColorAttachmentDesc::from(ColorInfo::default());
+        assert!(!desc.blend_enable);
+        assert_eq!(desc.load_op, vk::AttachmentLoadOp::CLEAR);
+    }
+
+    #[test]
+    fn color_attachment_desc_round_trips_through_color_info() {
+        let desc = ColorAttachmentDescBuilder::default()
+            .alpha_blend()
+            .discard_output()
+            .clear_value([0.0, 0.25, 0.5, 1.0])
+            .build()
+            .unwrap();
+        let round_trip = ColorAttachmentDesc::from(ColorInfo::from(desc.clone()));
+        assert!(round_trip.blend_enable);
+        assert_eq!(round_trip.src_color_blend, desc.src_color_blend);
+        assert_eq!(round_trip.dst_color_blend, desc.dst_color_blend);
+        assert_eq!(round_trip.store_op, vk::AttachmentStoreOp::DONT_CARE);
+        assert_eq!(round_trip.clear_value, desc.clear_value);
+    }
+
+    fn reflection(inputs: &[(u32, vk::Format)]) -> ShaderReflection {
+        ShaderReflection {
+            vertex_inputs: inputs.iter().map(|&(location, format)| VertexInputAttr { location, format }).collect(),
+            ..Default::default()
+        }
+    }
+
+    fn attributes(formats: &[(u32, vk::Format)]) -> Vec<VertexAttribute> {
+        formats.iter().map(|&(location, format)| VertexAttribute { location, binding: 0, format, offset: 0 }).collect()
+    }
+
+    #[test]
+    fn vertex_inputs_accept_matching_and_compatible_formats() {
+        let vs = reflection(&[(0, vk::Format::R32G32B32_SFLOAT), (1, vk::Format::R32G32B32A32_SFLOAT)]);
+
+        let exact = attributes(&[(0, vk::Format::R32G32B32_SFLOAT), (1, vk::Format::R32G32B32A32_SFLOAT)]);
+        assert!(validate_vertex_inputs(&vs, &exact, true).is_ok());
+
+        let packed = attributes(&[(0, vk::Format::A2B10G10R10_SNORM_PACK32), (1, vk::Format::R8G8B8A8_UNORM)]);
+        assert!(validate_vertex_inputs(&vs, &packed, false).is_ok());
+        assert!(matches!(
+            validate_vertex_inputs(&vs, &packed, true),
+            Err(GraphicShaderInputBuildError::VertexAttributeFormatMismatch { .. })
+        ));
+
+        assert!(validate_vertex_inputs(&reflection(&[]), &[], true).is_ok());
+    }
+
+    #[test]
+    fn vertex_inputs_reject_each_mismatch() {
+        let vs = reflection(&[(0, vk::Format::R32G32B32_SFLOAT), (1, vk::Format::R32G32_SFLOAT)]);
+
+        assert!(matches!(
+            validate_vertex_inputs(&reflection(&[]), &attributes(&[(0, vk::Format::R32_SFLOAT)]), false),
+            Err(GraphicShaderInputBuildError::VertexInputReflectionMissing)
+        ));
+
+        let duplicate = attributes(&[
+            (0, vk::Format::R32G32B32_SFLOAT),
+            (1, vk::Format::R32G32_SFLOAT),
+            (1, vk::Format::R16G16_UNORM),
+        ]);
+        assert!(matches!(
+            validate_vertex_inputs(&vs, &duplicate, false),
+            Err(GraphicShaderInputBuildError::DuplicateVertexAttributeLocation { location: 1 })
+        ));
+
+        assert!(matches!(
+            validate_vertex_inputs(&vs, &attributes(&[(0, vk::Format::R32G32B32_SFLOAT)]), false),
+            Err(GraphicShaderInputBuildError::MissingVertexAttribute { location: 1, expected: vk::Format::R32G32_SFLOAT })
+        ));
+
+        // Component count and numeric type must match even when not strict.
+        for provided in [vk::Format::R32G32B32_SFLOAT, vk::Format::R32G32_UINT] {
+            let mismatched = attributes(&[(0, vk::Format::R32G32B32_SFLOAT), (1, provided)]);
+            assert!(matches!(
+                validate_vertex_inputs(&vs, &mismatched, false),
+                Err(GraphicShaderInputBuildError::VertexAttributeFormatMismatch { location: 1, .. })
+            ));
+        }
+
+        let unexpected = attributes(&[
+            (0, vk::Format::R32G32B32_SFLOAT),
+            (1, vk::Format::R32G32_SFLOAT),
+            (2, vk::Format::R32_SFLOAT),
+        ]);
+        assert!(matches!(
+            validate_vertex_inputs(&vs, &unexpected, false),
+            Err(GraphicShaderInputBuildError::UnexpectedVertexAttribute { location: 2, .. })
+        ));
+    }
 }
@@ -2,24 +2,51 @@
 
 use crate::pipeline::{GraphicPipeline, GraphicPipelineDesc};
 use ash::{vk};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
 use zenith_core::collections::hashmap::HashMap;
 use zenith_rhi_derive::DeviceObject;
 use crate::RenderDevice;
+use crate::pipeline::CommonPipeline;
+use crate::descriptor::DescriptorSetLayout;
 use crate::device::DebuggableObject;
 use crate::device::set_debug_name_handle;
 
 #[derive(Debug, Clone, Copy, Default)]
 pub struct PipelineCacheStats {
     pub graphic_pipeline_count: usize,
+    /// Lookups served by a cached pipeline.
+    pub hits: usize,
+    /// Lookups that had to create a pipeline, including failed creations.
+    pub misses: usize,
+    /// Pipelines successfully created.
+    pub created: usize,
+    /// Total time spent creating pipelines.
+    pub compile_time: Duration,
+}
+
+/// The `vk::PipelineCache`, shared with precompile workers so it outlives them.
+struct RawPipelineCache {
+    device: ash::Device,
+    handle: vk::PipelineCache,
+}
+
+impl Drop for RawPipelineCache {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.destroy_pipeline_cache(self.handle, None);
+        }
+    }
 }
 
 /// Pipeline cache for storing and reusing graphics pipelines.
 #[DeviceObject]
 pub struct PipelineCache {
     name: String,
-    cache: vk::PipelineCache,
-    pipelines: HashMap<GraphicPipelineDesc, Arc<GraphicPipeline>>,
+    cache: Arc<RawPipelineCache>,
+    pipelines: Arc<Mutex<HashMap<GraphicPipelineDesc, Arc<GraphicPipeline>>>>,
+    counters: PipelineCacheStats,
 }
 
 impl PipelineCache {
@@ -30,8 +57,9 @@ impl PipelineCache {
 
         let pc = Self {
             name: name.to_owned(),
-            cache: vk_cache,
-            pipelines: HashMap::new(),
+            cache: Arc::new(RawPipelineCache { device: device.handle().clone(), handle: vk_cache }),
+            pipelines: Arc::new(Mutex::new(HashMap::new())),
+            counters: PipelineCacheStats::default(),
             device: device.handle().clone(),
         };
         device.set_debug_name(&pc);
@@ -45,8 +73,9 @@ impl PipelineCache {
 
         let pc = Self {
             name: name.to_owned(),
-            cache: vk_cache,
-            pipelines: HashMap::new(),
+            cache: Arc::new(RawPipelineCache { device: device.handle().clone(), handle: vk_cache }),
+            pipelines: Arc::new(Mutex::new(HashMap::new())),
+            counters: PipelineCacheStats::default(),
             device: device.handle().clone(),
         };
         device.set_debug_name(&pc);
@@ -57,57 +86,174 @@ impl PipelineCache {
     pub fn name(&self) -> &str { &self.name }
 
     #[inline]
-    pub fn handle(&self) -> vk::PipelineCache { self.cache }
+    pub fn handle(&self) -> vk::PipelineCache { self.cache.handle }
 
     /// Get or create a graphics pipeline.
     pub fn get_or_create(&mut self, name: &str, device: &RenderDevice, desc: &GraphicPipelineDesc) -> Result<Arc<GraphicPipeline>, vk::Result> {
-        if let Some(cached) = self.pipelines.get(desc) {
+        if let Some(cached) = self.pipelines.lock().unwrap().get(desc) {
+            self.counters.hits += 1;
             return Ok(cached.clone());
         }
+        self.counters.misses += 1;
+
+        let start = Instant::now();
+        let pipeline = GraphicPipeline::with_cache(name, device, desc, self.cache.handle);
+        self.counters.compile_time += start.elapsed();
 
-        let pipeline = Arc::new(GraphicPipeline::with_cache(name, device, desc, self.cache)?);
-        self.pipelines.insert(desc.clone(), pipeline.clone());
+        let pipeline = Arc::new(pipeline?);
+        self.counters.created += 1;
+        self.pipelines.lock().unwrap().insert(desc.clone(), pipeline.clone());
         Ok(pipeline)
     }
 
+    /// Create pipelines for `descs` on worker threads ahead of first use.
+    ///
+    /// Layouts are created on the calling thread, the pipelines themselves are compiled
+    /// in parallel against this cache's `vk::PipelineCache` and inserted into the cache as
+    /// they finish. Descs already cached are skipped. Precompiled pipelines carry no debug name.
+    /// The workers share ownership of the `vk::PipelineCache`, so dropping this cache first is fine.
+    pub fn precompile(&self, device: &RenderDevice, descs: Vec<GraphicPipelineDesc>) -> Result<PrecompileHandle, vk::Result> {
+        let mut jobs = Vec::with_capacity(descs.len());
+        {
+            let pipelines = self.pipelines.lock().unwrap();
+            for desc in descs {
+                if pipelines.contains_key(&desc) || jobs.iter().any(|job: &PrecompileJob| job.desc == desc) {
+                    continue;
+                }
+                let (descriptor_layouts, layout) = match CommonPipeline::create_graphic_layouts(device, &desc) {
+                    Ok(layouts) => layouts,
+                    Err(err) => {
+                        // descriptor layouts drop with the jobs, the pipeline layouts are raw handles
+                        for job in jobs {
+                            unsafe { device.handle().destroy_pipeline_layout(job.layout, None); }
+                        }
+                        return Err(err);
+                    }
+                };
+                jobs.push(PrecompileJob {
+                    name: format!("{}.precompiled.{}", self.name, jobs.len()),
+                    desc,
+                    descriptor_layouts,
+                    layout,
+                });
+            }
+        }
+
+        let num_workers = std::thread::available_parallelism()
+            .map_or(1, |n| n.get())
+            .min(jobs.len());
+        let mut buckets: Vec<Vec<PrecompileJob>> = (0..num_workers).map(|_| Vec::new()).collect();
+        for (idx, job) in jobs.into_iter().enumerate() {
+            buckets[idx % num_workers].push(job);
+        }
+
+        let workers = buckets
+            .into_iter()
+            .map(|bucket| {
+                let device = self.device.clone();
+                let cache = self.cache.clone();
+                let pipelines = self.pipelines.clone();
+                std::thread::spawn(move || {
+                    let mut result = Ok(());
+                    for job in bucket {
+                        match CommonPipeline::create_graphic_handle(&device, &job.desc, job.layout, cache.handle) {
+                            Ok(handle) => {
+                                let pipeline = CommonPipeline::from_raw(&job.name, device.clone(), job.layout, handle);
+                                let pipeline = Arc::new(GraphicPipeline::from_parts(job.descriptor_layouts, pipeline, &job.desc));
+                                pipelines.lock().unwrap().entry(job.desc).or_insert(pipeline);
+                            }
+                            Err(err) => {
+                                unsafe { device.destroy_pipeline_layout(job.layout, None); }
+                                result = result.and(Err(err));
+                            }
+                        }
+                    }
+                    result
+                })
+            })
+            .collect();
+
+        Ok(PrecompileHandle { workers })
+    }
+
     /// Get cached pipeline data for serialization.
     pub fn get_cache_data(&self) -> Result<Vec<u8>, vk::Result> {
-        unsafe { self.device.get_pipeline_cache_data(self.cache) }
+        unsafe { self.device.get_pipeline_cache_data(self.cache.handle) }
     }
 
     /// Get the number of cached pipelines.
     pub fn len(&self) -> usize {
-        self.pipelines.len()
+        self.pipelines.lock().unwrap().len()
     }
 
     pub fn stats(&self) -> PipelineCacheStats {
         PipelineCacheStats {
-            graphic_pipeline_count: self.pipelines.len(),
+            graphic_pipeline_count: self.len(),
+            ..self.counters
         }
     }
 
     /// Check if cache is empty.
     pub fn is_empty(&self) -> bool {
-        self.pipelines.is_empty()
+        self.pipelines.lock().unwrap().is_empty()
     }
 
-    /// Clear all cached pipelines.
+    /// Clear all cached pipelines and reset the stats.
     pub fn clear(&mut self) {
-        self.pipelines.clear();
+        self.pipelines.lock().unwrap().clear();
+        self.counters = PipelineCacheStats::default();
     }
 }
 
 impl DebuggableObject for PipelineCache {
     fn set_debug_name(&self, device: &RenderDevice) {
-        set_debug_name_handle(device, self.cache, vk::ObjectType::PIPELINE_CACHE, self.name());
+        set_debug_name_handle(device, self.cache.handle, vk::ObjectType::PIPELINE_CACHE, self.name());
     }
 }
 
 impl Drop for PipelineCache {
     fn drop(&mut self) {
+        // the vk::PipelineCache is destroyed once the last precompile worker is done with it
         self.clear();
-        unsafe {
-            self.device.destroy_pipeline_cache(self.cache, None);
-        }
+    }
+}
+
+struct PrecompileJob {
+    name: String,
+    desc: GraphicPipelineDesc,
+    descriptor_layouts: Vec<DescriptorSetLayout>,
+    layout: vk::PipelineLayout,
+}
+
+/// In-flight pipeline precompilation started by [`PipelineCache::precompile`].
+///
+/// Dropping the handle blocks until all workers are done.
+#[must_use]
+pub struct PrecompileHandle {
+    workers: Vec<JoinHandle<Result<(), vk::Result>>>,
+}
+
+impl PrecompileHandle {
+    /// Check if all workers are done.
+    pub fn is_finished(&self) -> bool {
+        self.workers.iter().all(|worker| worker.is_finished())
+    }
+
+    /// Block until all pipelines are compiled, returning the first creation error if any.
+    pub fn wait(mut self) -> Result<(), vk::Result> {
+        self.join_all()
+    }
+
+    fn join_all(&mut self) -> Result<(), vk::Result> {
+        self.workers
+            .drain(..)
+            .map(|worker| worker.join().expect("pipeline precompile worker panicked"))
+            .fold(Ok(()), Result::and)
+    }
+}
+
+impl Drop for PrecompileHandle {
+    fn drop(&mut self) {
+        let _ = self.join_all();
     }
 }
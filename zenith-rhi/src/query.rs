@@ -0,0 +1,73 @@
+//! GPU query pools.
+
+use ash::vk;
+use zenith_rhi_derive::DeviceObject;
+use crate::RenderDevice;
+use crate::device::DebuggableObject;
+use crate::device::set_debug_name_handle;
+
+/// An owning Vulkan timestamp query pool.
+#[DeviceObject]
+pub struct QueryPool {
+    name: String,
+    pool: vk::QueryPool,
+    count: u32,
+}
+
+impl QueryPool {
+    pub fn new_timestamp(name: &str, device: &RenderDevice, count: u32) -> Result<Self, vk::Result> {
+        let create_info = vk::QueryPoolCreateInfo::default()
+            .query_type(vk::QueryType::TIMESTAMP)
+            .query_count(count);
+        let pool = unsafe { device.handle().create_query_pool(&create_info, None)? };
+
+        let p = Self {
+            name: name.to_string(),
+            pool,
+            count,
+            device: device.handle().clone(),
+        };
+        device.set_debug_name(&p);
+        Ok(p)
+    }
+
+    #[inline]
+    pub fn name(&self) -> &str { &self.name }
+
+    #[inline]
+    pub fn handle(&self) -> vk::QueryPool {
+        self.pool
+    }
+
+    #[inline]
+    pub fn count(&self) -> u32 { self.count }
+
+    /// Read `count` timestamps starting at `first` without waiting.
+    /// Returns `None` if the GPU hasn't written them yet.
+    pub fn timestamps(&self, first: u32, count: u32) -> Result<Option<Vec<u64>>, vk::Result> {
+        let mut data = vec![0u64; count as usize];
+        let result = unsafe {
+            self.device.get_query_pool_results(self.pool, first, &mut data, vk::QueryResultFlags::TYPE_64)
+        };
+
+        match result {
+            Ok(()) => Ok(Some(data)),
+            Err(vk::Result::NOT_READY) => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+}
+
+impl DebuggableObject for QueryPool {
+    fn set_debug_name(&self, device: &RenderDevice) {
+        set_debug_name_handle(device, self.pool, vk::ObjectType::QUERY_POOL, self.name());
+    }
+}
+
+impl Drop for QueryPool {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.destroy_query_pool(self.pool, None);
+        }
+    }
+}
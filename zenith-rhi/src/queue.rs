@@ -1,4 +1,5 @@
 use ash::vk;
+use crate::RenderDevice;
 
 /// A queue wrapper that carries its family index.
 #[derive(Clone, Copy, Debug)]
@@ -12,7 +13,18 @@ impl Queue {
         Self { handle, family_index }
     }
 
+    /// Placeholder for barriers that don't transfer queue family ownership.
+    pub(crate) fn ignored() -> Self {
+        Self::new(vk::Queue::null(), vk::QUEUE_FAMILY_IGNORED)
+    }
+
     pub fn handle(&self) -> vk::Queue { self.handle }
 
     pub fn family_index(&self) -> u32 { self.family_index }
+
+    /// Wait until this queue has finished all submitted work, a finer grained stall than
+    /// [`RenderDevice::wait_until_idle`].
+    pub fn wait_idle(&self, device: &RenderDevice) -> Result<(), vk::Result> {
+        unsafe { device.handle().queue_wait_idle(self.handle) }
+    }
 }
@@ -1,10 +1,13 @@
 use crate::{vk, Buffer, BufferDesc, RenderDevice, Texture, TextureDesc};
 use std::collections::HashMap;
+use std::sync::Arc;
 
 #[derive(Default)]
 pub struct ResourceCache {
     available_buffers: HashMap<BufferDesc, Vec<Buffer>>,
     available_textures: HashMap<TextureDesc, Vec<Texture>>,
+    /// Shared render targets, kept alive across frames until invalidated.
+    render_targets: HashMap<TextureDesc, Arc<Texture>>,
 }
 
 impl ResourceCache {
@@ -46,6 +49,29 @@ impl ResourceCache {
         self.available_textures.entry(desc).or_default().push(texture);
     }
 
+    /// Get the render target matching `desc`, creating it on first use.
+    /// Unlike `acquire_texture` the texture stays in the cache and is shared by every caller.
+    pub fn get_or_create_texture(
+        &mut self,
+        device: &RenderDevice,
+        desc: &TextureDesc,
+    ) -> Result<Arc<Texture>, vk::Result> {
+        if let Some(tex) = self.render_targets.get(desc) {
+            return Ok(tex.clone());
+        }
+        let tex: Arc<Texture> = Texture::new(device, desc)?.into();
+        self.render_targets.insert(desc.clone(), tex.clone());
+        Ok(tex)
+    }
+
+    /// Drop render targets sized to `old_extent`, e.g. the swapchain extent before a resize.
+    /// The GPU must no longer use them, which holds after the device-wide wait of a swapchain resize.
+    pub fn invalidate_size_dependent(&mut self, old_extent: vk::Extent2D) {
+        self.render_targets.retain(|desc, _| {
+            desc.extent.width != old_extent.width || desc.extent.height != old_extent.height
+        });
+    }
+
     pub fn clear_buffers(&mut self) {
         self.available_buffers.clear();
     }
@@ -54,9 +80,14 @@ impl ResourceCache {
         self.available_textures.clear();
     }
 
+    pub fn clear_render_targets(&mut self) {
+        self.render_targets.clear();
+    }
+
     pub fn clear(&mut self) {
         self.clear_buffers();
         self.clear_textures();
+        self.clear_render_targets();
     }
 
     pub fn stats(&self) -> ResourceCacheStats {
@@ -66,6 +97,7 @@ impl ResourceCache {
         ResourceCacheStats {
             available_buffer_count,
             available_texture_count,
+            render_target_count: self.render_targets.len(),
         }
     }
 }
@@ -74,6 +106,7 @@ impl ResourceCache {
 pub struct ResourceCacheStats {
     pub available_buffer_count: usize,
     pub available_texture_count: usize,
+    pub render_target_count: usize,
 }
 
 
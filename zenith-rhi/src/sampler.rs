@@ -73,6 +73,53 @@ impl SamplerConfig {
         }
     }
 
+    /// Create a sampler config addressing texels by integer coordinates, e.g. for texel fetches in compute passes.
+    ///
+    /// Unnormalized coordinates only sample mip level 0 with nearest mip selection, clamp u and v to the
+    /// edge or border, and can't use anisotropy or depth compare; [`Sampler::new`] rejects configs that don't.
+    pub fn unnormalized() -> Self {
+        Self {
+            address_mode_u: vk::SamplerAddressMode::CLAMP_TO_EDGE,
+            address_mode_v: vk::SamplerAddressMode::CLAMP_TO_EDGE,
+            address_mode_w: vk::SamplerAddressMode::CLAMP_TO_EDGE,
+            max_lod: 0.0,
+            unnormalized_coordinates: true,
+            ..Self::nearest()
+        }
+    }
+
+    /// Constraints of unnormalized coordinates that this config violates, empty if valid.
+    pub fn unnormalized_violations(&self) -> Vec<&'static str> {
+        if !self.unnormalized_coordinates {
+            return Vec::new();
+        }
+
+        let is_clamp = |mode: vk::SamplerAddressMode| {
+            matches!(mode, vk::SamplerAddressMode::CLAMP_TO_EDGE | vk::SamplerAddressMode::CLAMP_TO_BORDER)
+        };
+
+        let mut violations = Vec::new();
+        if self.min_filter != self.mag_filter {
+            violations.push("min_filter must equal mag_filter");
+        }
+        if self.mipmap_mode != vk::SamplerMipmapMode::NEAREST {
+            violations.push("mipmap_mode must be NEAREST");
+        }
+        if self.min_lod != 0.0 || self.max_lod != 0.0 {
+            violations.push("min_lod and max_lod must be 0");
+        }
+        if !is_clamp(self.address_mode_u) || !is_clamp(self.address_mode_v) {
+            violations.push("address_mode_u/v must be CLAMP_TO_EDGE or CLAMP_TO_BORDER");
+        }
+        if self.anisotropy_enable {
+            violations.push("anisotropy must be disabled");
+        }
+        if self.compare_enable {
+            violations.push("compare must be disabled");
+        }
+        violations
+    }
+
     /// Set address mode for all axes.
     pub fn with_address_mode(mut self, mode: vk::SamplerAddressMode) -> Self {
         self.address_mode_u = mode;
@@ -82,6 +129,36 @@ impl SamplerConfig {
     }
 }
 
+/// Sampler creation error.
+#[derive(Debug)]
+pub enum SamplerError {
+    /// The config uses unnormalized coordinates against these constraints.
+    UnnormalizedViolations(Vec<&'static str>),
+    /// Vulkan sampler creation failed.
+    Vulkan(vk::Result),
+}
+
+impl std::fmt::Display for SamplerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SamplerError::UnnormalizedViolations(violations) => write!(
+                f,
+                "Sampler uses unnormalized coordinates but {}",
+                violations.join(", ")
+            ),
+            SamplerError::Vulkan(result) => write!(f, "Sampler creation failed: {:?}", result),
+        }
+    }
+}
+
+impl std::error::Error for SamplerError {}
+
+impl From<vk::Result> for SamplerError {
+    fn from(result: vk::Result) -> Self {
+        SamplerError::Vulkan(result)
+    }
+}
+
 /// Vulkan sampler for texture sampling.
 #[DeviceObject]
 pub struct Sampler {
@@ -91,7 +168,14 @@ pub struct Sampler {
 
 impl Sampler {
     /// Create a new sampler with the given configuration.
-    pub fn new(name: &str, device: &ash::Device, config: &SamplerConfig) -> Result<Self, vk::Result> {
+    /// Fails with [`SamplerError::UnnormalizedViolations`] if the config uses unnormalized coordinates
+    /// against their constraints, see [`SamplerConfig::unnormalized_violations`].
+    pub fn new(name: &str, device: &ash::Device, config: &SamplerConfig) -> Result<Self, SamplerError> {
+        let violations = config.unnormalized_violations();
+        if !violations.is_empty() {
+            return Err(SamplerError::UnnormalizedViolations(violations));
+        }
+
         let create_info = vk::SamplerCreateInfo::default()
             .mag_filter(config.mag_filter)
             .min_filter(config.min_filter)
@@ -140,3 +224,35 @@ impl DebuggableObject for Sampler {
         set_debug_name_handle(device, self.sampler, vk::ObjectType::SAMPLER, self.name());
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unnormalized_preset_is_valid() {
+        assert!(SamplerConfig::unnormalized().unnormalized_violations().is_empty());
+        // Normalized configs are never checked.
+        assert!(SamplerConfig::anisotropic(16.0).unnormalized_violations().is_empty());
+    }
+
+    #[test]
+    fn unnormalized_rejects_each_violation() {
+        type Violate = fn(&mut SamplerConfig);
+        let cases: [(Violate, &str); 7] = [
+            (|c| c.min_filter = vk::Filter::LINEAR, "min_filter must equal mag_filter"),
+            (|c| c.mipmap_mode = vk::SamplerMipmapMode::LINEAR, "mipmap_mode must be NEAREST"),
+            (|c| c.max_lod = 1.0, "min_lod and max_lod must be 0"),
+            (|c| c.address_mode_u = vk::SamplerAddressMode::REPEAT, "address_mode_u/v must be CLAMP_TO_EDGE or CLAMP_TO_BORDER"),
+            (|c| c.address_mode_v = vk::SamplerAddressMode::MIRRORED_REPEAT, "address_mode_u/v must be CLAMP_TO_EDGE or CLAMP_TO_BORDER"),
+            (|c| c.anisotropy_enable = true, "anisotropy must be disabled"),
+            (|c| c.compare_enable = true, "compare must be disabled"),
+        ];
+
+        for (violate, expected) in cases {
+            let mut config = SamplerConfig::unnormalized();
+            violate(&mut config);
+            assert_eq!(config.unnormalized_violations(), vec![expected]);
+        }
+    }
+}
@@ -1,10 +1,10 @@
 //! Vulkan Shader - HLSL compilation and SPIR-V reflection.
 
 use ash::{vk, Device};
-// use hassle_rs::HassleError;
 use rspirv_reflect::{Reflection, DescriptorType, BindingCount};
 use std::ffi::CString;
 use std::collections::HashMap;
+use std::sync::Arc;
 use zenith_rhi_derive::DeviceObject;
 use crate::RenderDevice;
 use crate::device::DebuggableObject;
@@ -24,11 +24,48 @@ impl ShaderModel {
     }
 }
 
-/// Compiled shader with Vulkan shader module and reflection data.
+/// First word of every SPIR-V module, in host (little-endian) byte order.
+const SPIRV_MAGIC: u32 = 0x0723_0203;
+
+/// `vk::ShaderModule` owned by every [`Shader`] created from it.
 #[DeviceObject]
-pub struct Shader {
+struct ShaderModule {
     name: String,
     module: vk::ShaderModule,
+}
+
+impl ShaderModule {
+    fn new(name: &str, device: &RenderDevice, spirv: &[u8]) -> Result<Arc<Self>, ShaderError> {
+        let module = Self {
+            name: name.to_owned(),
+            module: create_shader_module(device.handle(), spirv)?,
+            device: device.handle().clone(),
+        };
+        device.set_debug_name(&module);
+        Ok(Arc::new(module))
+    }
+}
+
+impl DebuggableObject for ShaderModule {
+    fn set_debug_name(&self, device: &RenderDevice) {
+        set_debug_name_handle(device, self.module, vk::ObjectType::SHADER_MODULE, &self.name);
+    }
+}
+
+impl Drop for ShaderModule {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.destroy_shader_module(self.module, None);
+        }
+    }
+}
+
+/// Compiled shader with Vulkan shader module and reflection data.
+///
+/// Shaders created by [`Shader::from_spirv_multi`] share one module, each with its own entry point.
+pub struct Shader {
+    name: String,
+    module: Arc<ShaderModule>,
     stage: ShaderStage,
     entry_point: CString,
     reflection: ShaderReflection,
@@ -41,26 +78,38 @@ impl Shader {
         path: &Path,
         entry_point: &str,
         stage: ShaderStage,
+        options: &ShaderCompileOptions,
     ) -> Result<Self, ShaderError> {
+        // Catch a mistyped entry point or stage before slangc produces a less obvious error.
+        let entry_points = list_entry_points(path)?;
+        if !entry_points.is_empty()
+            && !entry_points.iter().any(|(name, s)| name == entry_point && *s == stage)
+        {
+            return Err(ShaderError::compilation_failed(format!(
+                "Entry point '{}' ({:?}) is not declared in {}; available: {:?}",
+                entry_point,
+                stage,
+                path.display(),
+                entry_points
+            )));
+        }
+
         // Compile two variants:
         // - runtime SPIR-V: has embedded debug info for RenderDoc
         // - reflection SPIR-V: no debug info to keep reflection robust
-        let runtime_spirv = compile_slang_file_to_spirv(name, path, entry_point, stage, true)?;
-        let reflection_spirv = compile_slang_file_to_spirv(name, path, entry_point, stage, false)?;
+        let runtime_spirv = compile_slang_file_to_spirv(name, path, entry_point, stage, options, true)?;
+        let reflection_spirv = compile_slang_file_to_spirv(name, path, entry_point, stage, options, false)?;
 
         let reflection = reflect_spirv(&reflection_spirv, stage)?;
-        let module = create_shader_module(device.handle(), &runtime_spirv)?;
+        let module = ShaderModule::new(name, device, &runtime_spirv)?;
 
-        let shader = Self {
+        Ok(Self {
             name: name.to_owned(),
             module,
             stage,
             entry_point: CString::new(entry_point).unwrap(),
             reflection,
-            device: device.handle().clone(),
-        };
-        device.set_debug_name(&shader);
-        Ok(shader)
+        })
     }
 
     /// Create a shader from pre-compiled SPIR-V bytecode.
@@ -75,30 +124,83 @@ impl Shader {
         let reflection = reflect_spirv(spirv, stage)?;
 
         // Create shader module
-        let module = create_shader_module(device.handle(), spirv)?;
+        let module = ShaderModule::new(name, device, spirv)?;
 
-        let shader = Self {
+        Ok(Self {
             name: name.to_owned(),
             module,
             stage,
             entry_point: CString::new(entry_point).unwrap(),
             reflection,
-            // descriptor_set_layouts,
-            device: device.handle().clone(),
-        };
-        device.set_debug_name(&shader);
-        Ok(shader)
+        })
+    }
+
+    /// Create one shader per entry point of a SPIR-V module, e.g. `vsmain` and `psmain` emitted
+    /// together by slang. The shaders share a single `vk::ShaderModule`, which is destroyed with
+    /// the last of them.
+    ///
+    /// Vertex inputs are reflected from each entry point's interface. Descriptor bindings and push
+    /// constants are reflected from the whole module, so every shader reports all of them.
+    pub fn from_spirv_multi(
+        name: &str,
+        device: &RenderDevice,
+        spirv: &[u8],
+        entries: &[(&str, ShaderStage)],
+    ) -> Result<Vec<Self>, ShaderError> {
+        let reflections = entries
+            .iter()
+            .map(|&(entry_point, stage)| reflect_spirv_entry(spirv, entry_point, stage))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let module = ShaderModule::new(name, device, spirv)?;
+
+        Ok(entries
+            .iter()
+            .zip(reflections)
+            .map(|(&(entry_point, stage), reflection)| Self {
+                name: format!("{}.{}", name, entry_point),
+                module: module.clone(),
+                stage,
+                entry_point: CString::new(entry_point).unwrap(),
+                reflection,
+            })
+            .collect())
+    }
+
+    /// Create a shader from a pre-compiled `.spv` file, without requiring slangc.
+    pub fn from_spirv_file(
+        name: &str,
+        device: &RenderDevice,
+        path: &Path,
+        entry_point: &str,
+        stage: ShaderStage,
+    ) -> Result<Self, ShaderError> {
+        let spirv = std::fs::read(path)?;
+
+        if spirv.is_empty() || !spirv.len().is_multiple_of(4) {
+            return Err(ShaderError::ReflectionFailed(format!(
+                "{}: SPIR-V size ({} bytes) is not a multiple of 4", path.display(), spirv.len()
+            )));
+        }
+        let magic = u32::from_le_bytes([spirv[0], spirv[1], spirv[2], spirv[3]]);
+        if magic != SPIRV_MAGIC {
+            return Err(ShaderError::ReflectionFailed(format!(
+                "{}: not a SPIR-V module (magic {:#010x}, expected {:#010x})", path.display(), magic, SPIRV_MAGIC
+            )));
+        }
+
+        Self::from_spirv(name, device, &spirv, entry_point, stage)
     }
 
     #[inline]
     pub fn name(&self) -> &str { &self.name }
 
     #[inline]
-    pub fn handle(&self) -> vk::ShaderModule { self.module }
+    pub fn handle(&self) -> vk::ShaderModule { self.module.module }
 
     /// Get the Vulkan shader module handle.
     pub fn module(&self) -> vk::ShaderModule {
-        self.module
+        self.module.module
     }
 
     /// Get the shader stage.
@@ -122,24 +224,34 @@ impl Shader {
     }
 }
 
-impl DebuggableObject for Shader {
-    fn set_debug_name(&self, device: &RenderDevice) {
-        set_debug_name_handle(device, self.module, vk::ObjectType::SHADER_MODULE, self.name());
-    }
+
+/// Extra preprocessor state passed to the shader compiler.
+#[derive(Debug, Clone, Default)]
+pub struct ShaderCompileOptions {
+    /// Emitted as `-D name=value`.
+    pub defines: Vec<(String, String)>,
+    /// Searched after the directory of the source file.
+    pub include_dirs: Vec<PathBuf>,
 }
 
-impl Drop for Shader {
-    fn drop(&mut self) {
-        unsafe {
-            self.device.destroy_shader_module(self.module, None);
-        }
+impl ShaderCompileOptions {
+    pub fn with_define(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.defines.push((name.into(), value.into()));
+        self
+    }
+
+    pub fn with_include_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.include_dirs.push(dir.into());
+        self
     }
 }
 
 /// Shader compilation and reflection errors.
 #[derive(Debug)]
 pub enum ShaderError {
-    CompilationFailed(String),
+    /// `summary` is a short, human readable description (first error with source context),
+    /// `raw` is the complete compiler output.
+    CompilationFailed { summary: String, raw: String },
     ReflectionFailed(String),
     VulkanError(vk::Result),
     IoError(std::io::Error),
@@ -160,7 +272,7 @@ impl From<std::io::Error> for ShaderError {
 impl std::fmt::Display for ShaderError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            ShaderError::CompilationFailed(msg) => write!(f, "Shader compilation failed: {}", msg),
+            ShaderError::CompilationFailed { summary, .. } => write!(f, "Shader compilation failed: {}", summary),
             ShaderError::ReflectionFailed(msg) => write!(f, "Shader reflection failed: {}", msg),
             ShaderError::VulkanError(e) => write!(f, "Vulkan error: {:?}", e),
             ShaderError::IoError(e) => write!(f, "IO error: {}", e),
@@ -170,6 +282,14 @@ impl std::fmt::Display for ShaderError {
 
 impl std::error::Error for ShaderError {}
 
+impl ShaderError {
+    /// Compilation error that does not come from compiler output.
+    pub(crate) fn compilation_failed(msg: impl Into<String>) -> Self {
+        let msg = msg.into();
+        ShaderError::CompilationFailed { summary: msg.clone(), raw: msg }
+    }
+}
+
 /// Shader stage type.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ShaderStage {
@@ -211,9 +331,15 @@ pub struct VertexInputAttr {
 #[derive(Debug, Clone, Default)]
 pub struct ShaderReflection {
     pub bindings: Vec<ShaderBinding>,
+    /// Byte offset of the first push constant member used by the shader.
+    pub push_constant_offset: u32,
+    /// Byte size of the push constant range, starting at `push_constant_offset`.
     pub push_constant_size: u32,
     /// Vertex inputs (only populated for vertex stage).
     pub vertex_inputs: Vec<VertexInputAttr>,
+    /// Workgroup size declared by a compute shader.
+    /// `None` for other stages and when the size is driven by specialization constants.
+    pub local_size: Option<[u32; 3]>,
 }
 
 impl ShaderReflection {
@@ -221,11 +347,22 @@ impl ShaderReflection {
     /// Combines stage_flags for bindings at the same (set, binding).
     pub fn merge(reflections: &[&ShaderReflection]) -> Self {
         let mut binding_map: HashMap<(u32, u32), ShaderBinding> = HashMap::new();
-        let mut push_constant_size = 0u32;
+        // Union of all push constant ranges: min offset, max end.
+        let mut push_constant_range: Option<(u32, u32)> = None;
         let mut vertex_inputs_map: HashMap<u32, vk::Format> = HashMap::new();
+        let mut local_size = None;
 
         for reflection in reflections {
-            push_constant_size = push_constant_size.max(reflection.push_constant_size);
+            local_size = local_size.or(reflection.local_size);
+
+            if reflection.push_constant_size > 0 {
+                let start = reflection.push_constant_offset;
+                let end = start + reflection.push_constant_size;
+                push_constant_range = Some(match push_constant_range {
+                    Some((s, e)) => (s.min(start), e.max(end)),
+                    None => (start, end),
+                });
+            }
 
             for binding in &reflection.bindings {
                 let key = (binding.set, binding.binding);
@@ -251,18 +388,51 @@ impl ShaderReflection {
             .collect();
         vertex_inputs.sort_by_key(|v| v.location);
 
+        let (push_constant_offset, push_constant_end) = push_constant_range.unwrap_or((0, 0));
+
         Self {
             bindings,
-            push_constant_size,
+            push_constant_offset,
+            push_constant_size: push_constant_end - push_constant_offset,
             vertex_inputs,
+            local_size,
         }
     }
 
+    /// Number of workgroups to dispatch to cover `total` threads, rounding up.
+    /// Returns `None` if the workgroup size is unknown, the caller must compute it then.
+    pub fn group_count_for(&self, total: [u32; 3]) -> Option<[u32; 3]> {
+        let local_size = self.local_size?;
+        Some(std::array::from_fn(|i| total[i].div_ceil(local_size[i].max(1))))
+    }
+
     /// Find a binding by name.
     pub fn find_binding(&self, name: &str) -> Option<&ShaderBinding> {
         self.bindings.iter().find(|b| b.name == name)
     }
 
+    /// Find a binding by its set and binding index.
+    pub fn find_binding_at(&self, set: u32, binding: u32) -> Option<&ShaderBinding> {
+        self.bindings.iter().find(|b| b.set == set && b.binding == binding)
+    }
+
+    /// Find the separate `SAMPLER` paired with a `SAMPLED_IMAGE` binding.
+    ///
+    /// Slang and HLSL declare `Texture2D` and `SamplerState` separately, so they reflect as two bindings
+    /// instead of one `COMBINED_IMAGE_SAMPLER`. A sampler in the same set pairs with `image` only when
+    /// it is named `{image}Sampler` or `{image}_sampler`; any other sampler may be shared between images
+    /// and must be bound on its own.
+    pub fn paired_sampler(&self, image: &ShaderBinding) -> Option<&ShaderBinding> {
+        if image.descriptor_type != vk::DescriptorType::SAMPLED_IMAGE {
+            return None;
+        }
+
+        let names = [format!("{}Sampler", image.name), format!("{}_sampler", image.name)];
+        self.bindings.iter()
+            .filter(|b| b.set == image.set && b.descriptor_type == vk::DescriptorType::SAMPLER)
+            .find(|b| names.contains(&b.name))
+    }
+
     /// Get the maximum set index used.
     pub fn max_set(&self) -> Option<u32> {
         self.bindings.iter().map(|b| b.set).max()
@@ -293,6 +463,103 @@ fn sanitize_filename(s: &str) -> String {
         .collect()
 }
 
+/// Encode `defines` for a file name, distinct define sets always give distinct suffixes.
+///
+/// Names and values keep ASCII alphanumerics and `-`, every other byte is escaped as `_xx`.
+/// They never contain `.` or `=` afterwards, so those can separate the defines unambiguously.
+fn define_suffix(defines: &[(String, String)]) -> String {
+    fn escape(s: &str) -> String {
+        s.bytes()
+            .map(|b| if b.is_ascii_alphanumeric() || b == b'-' { (b as char).to_string() } else { format!("_{:02x}", b) })
+            .collect()
+    }
+
+    defines
+        .iter()
+        .map(|(name, value)| format!(".{}={}", escape(name), escape(value)))
+        .collect()
+}
+
+/// List the entry points declared with `[shader("...")]` attributes in a Slang source file.
+///
+/// Stages the engine does not support (e.g. geometry) are skipped. Files that rely on `-entry`
+/// without attributes yield an empty list.
+pub fn list_entry_points(path: &Path) -> Result<Vec<(String, ShaderStage)>, ShaderError> {
+    let source = std::fs::read_to_string(path)?;
+    Ok(parse_entry_points(&strip_comments(&source)))
+}
+
+/// Replace `//` line comments and `/* */` block comments with whitespace, keeping line breaks.
+fn strip_comments(source: &str) -> String {
+    let mut out = String::with_capacity(source.len());
+    let mut rest = source;
+    while !rest.is_empty() {
+        if let Some(comment) = rest.strip_prefix("//") {
+            let end = comment.find('\n').unwrap_or(comment.len());
+            rest = &comment[end..];
+        } else if let Some(comment) = rest.strip_prefix("/*") {
+            let end = comment.find("*/").map_or(comment.len(), |end| end + 2);
+            out.push(' ');
+            out.extend(comment[..end].chars().filter(|&c| c == '\n'));
+            rest = &comment[end..];
+        } else {
+            let c = rest.chars().next().unwrap();
+            out.push(c);
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+    out
+}
+
+/// Find the `[shader("...")]` entry points of comment-free Slang source.
+fn parse_entry_points(source: &str) -> Vec<(String, ShaderStage)> {
+    const ATTRIBUTE: &str = "[shader(\"";
+
+    let mut entry_points = Vec::new();
+    let mut rest = source;
+    while let Some(start) = rest.find(ATTRIBUTE) {
+        rest = &rest[start + ATTRIBUTE.len()..];
+
+        let Some(end) = rest.find('"') else { break };
+        let stage = match &rest[..end] {
+            "vertex" => Some(ShaderStage::Vertex),
+            "fragment" | "pixel" => Some(ShaderStage::Fragment),
+            "compute" => Some(ShaderStage::Compute),
+            _ => None,
+        };
+        let Some(close) = rest.find(']') else { break };
+        rest = &rest[close + 1..];
+
+        // Skip any further attributes (e.g. `[numthreads(8, 8, 1)]`) before the declaration.
+        loop {
+            let trimmed = rest.trim_start();
+            if !trimmed.starts_with('[') {
+                break;
+            }
+            match trimmed.find(']') {
+                Some(close) => rest = &trimmed[close + 1..],
+                None => break,
+            }
+        }
+
+        // The function name is the last identifier before the parameter list.
+        let Some(paren) = rest.find('(') else { break };
+        let name = rest[..paren]
+            .trim_end()
+            .rsplit(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .next()
+            .unwrap_or("");
+
+        if let Some(stage) = stage
+            && !name.is_empty()
+        {
+            entry_points.push((name.to_owned(), stage));
+        }
+    }
+
+    entry_points
+}
+
 /// Compile Slang source file to SPIR-V using VulkanSDK `slangc.exe`.
 ///
 /// We enable SPIR-V debug info (DWARF) for RenderDoc.
@@ -301,9 +568,10 @@ pub fn compile_slang_file_to_spirv(
     path: &Path,
     entry_point: &str,
     stage: ShaderStage,
+    options: &ShaderCompileOptions,
     debug: bool,
 ) -> Result<Vec<u8>, ShaderError> {
-    compile_slang_file_to_spirv_cli(shader_name, path, entry_point, stage, debug)
+    compile_slang_file_to_spirv_cli(shader_name, path, entry_point, stage, options, debug)
 }
 
 fn compile_slang_file_to_spirv_cli(
@@ -311,6 +579,7 @@ fn compile_slang_file_to_spirv_cli(
     path: &Path,
     entry_point: &str,
     stage: ShaderStage,
+    options: &ShaderCompileOptions,
     debug: bool,
 ) -> Result<Vec<u8>, ShaderError> {
     let slangc = slangc_path()?;
@@ -318,17 +587,21 @@ fn compile_slang_file_to_spirv_cli(
     let out_dir = PathBuf::from("target").join("shader_pdb");
     std::fs::create_dir_all(&out_dir)?;
 
+    // Different define sets of the same entry point must not overwrite each other.
+    let defines = define_suffix(&options.defines);
+
     let out_spv = out_dir.join(format!(
-        "{}.{}.{}.{}.spv",
+        "{}.{}.{}{}.{}.spv",
         sanitize_filename(shader_name),
         stage_arg(stage),
         sanitize_filename(entry_point),
+        defines,
         if debug { "debug" } else { "nodebug" },
     ));
 
     let include_dir = path
         .parent()
-        .ok_or_else(|| ShaderError::CompilationFailed("Shader path has no parent dir".into()))?;
+        .ok_or_else(|| ShaderError::compilation_failed("Shader path has no parent dir"))?;
 
     let mut cmd = Command::new(slangc);
     cmd.arg(path)
@@ -346,6 +619,13 @@ fn compile_slang_file_to_spirv_cli(
         .arg("-o")
         .arg(&out_spv);
 
+    for dir in &options.include_dirs {
+        cmd.arg("-I").arg(dir);
+    }
+    for (name, value) in &options.defines {
+        cmd.arg("-D").arg(format!("{}={}", name, value));
+    }
+
     if debug {
         // Debug: include debug info (level 3) in DWARF format for SPIR-V.
         cmd.arg("-g3").arg("-gdwarf");
@@ -353,18 +633,146 @@ fn compile_slang_file_to_spirv_cli(
 
     let output = cmd.output()?;
     if !output.status.success() {
-        let mut msg = String::new();
-        msg.push_str("slangc failed\n");
-        msg.push_str(&String::from_utf8_lossy(&output.stdout));
-        msg.push_str(&String::from_utf8_lossy(&output.stderr));
-        return Err(ShaderError::CompilationFailed(msg));
+        let mut raw = String::new();
+        raw.push_str(&String::from_utf8_lossy(&output.stdout));
+        raw.push_str(&String::from_utf8_lossy(&output.stderr));
+
+        let summary = format!(
+            "{} ({}, {}): {}",
+            shader_name,
+            entry_point,
+            stage_arg(stage),
+            summarize_slangc_error(&raw).unwrap_or_else(|| "slangc failed".to_owned()),
+        );
+        return Err(ShaderError::CompilationFailed { summary, raw });
+    }
+
+    Ok(std::fs::read(out_spv)?)
+}
+
+/// `DXC` if set, then the Vulkan SDK's DXC if installed, otherwise `dxc` from `PATH`.
+#[cfg(feature = "hlsl")]
+fn dxc_path() -> PathBuf {
+    if let Ok(p) = std::env::var("DXC") {
+        return PathBuf::from(p);
+    }
+
+    // The Windows SDK installs to `Bin`, the Linux and macOS SDKs to `bin`.
+    let (bin_dir, exe) = if cfg!(windows) { ("Bin", "dxc.exe") } else { ("bin", "dxc") };
+    if let Ok(vk) = std::env::var("VULKAN_SDK") {
+        let sdk_dxc = PathBuf::from(vk).join(bin_dir).join(exe);
+        if sdk_dxc.is_file() {
+            return sdk_dxc;
+        }
+    }
+    PathBuf::from(exe)
+}
+
+/// Compile HLSL source to SPIR-V using DXC (`dxc -spirv`).
+///
+/// Like slangc, DXC is invoked as an external process, found through `DXC`, `VULKAN_SDK` or `PATH`.
+#[cfg(feature = "hlsl")]
+pub fn compile_hlsl(
+    shader_name: &str,
+    source: &str,
+    entry_point: &str,
+    stage: ShaderStage,
+    shader_model: ShaderModel,
+) -> Result<Vec<u8>, ShaderError> {
+    let out_dir = PathBuf::from("target").join("shader_pdb");
+    std::fs::create_dir_all(&out_dir)?;
+
+    let base_name = format!(
+        "{}.{}.{}",
+        sanitize_filename(shader_name),
+        stage_arg(stage),
+        sanitize_filename(entry_point),
+    );
+    let src_hlsl = out_dir.join(format!("{}.hlsl", base_name));
+    let out_spv = out_dir.join(format!("{}.spv", base_name));
+    std::fs::write(&src_hlsl, source)?;
+
+    let profile_prefix = match stage {
+        ShaderStage::Vertex => "vs",
+        ShaderStage::Fragment => "ps",
+        ShaderStage::Compute => "cs",
+    };
+
+    let output = Command::new(dxc_path())
+        .arg(&src_hlsl)
+        .arg("-spirv")
+        .arg("-fspv-target-env=vulkan1.3")
+        .arg("-T")
+        .arg(format!("{}_{}", profile_prefix, shader_model.as_str()))
+        .arg("-E")
+        .arg(entry_point)
+        .arg("-Fo")
+        .arg(&out_spv)
+        .output()?;
+
+    if !output.status.success() {
+        let mut raw = String::new();
+        raw.push_str(&String::from_utf8_lossy(&output.stdout));
+        raw.push_str(&String::from_utf8_lossy(&output.stderr));
+
+        let first_error = raw
+            .lines()
+            .find(|line| line.contains("error"))
+            .unwrap_or("dxc failed")
+            .to_owned();
+        let summary = format!("{} ({}, {}): {}", shader_name, entry_point, stage_arg(stage), first_error);
+        return Err(ShaderError::CompilationFailed { summary, raw });
     }
 
     Ok(std::fs::read(out_spv)?)
 }
 
+/// Find the first `file(line): error ...` diagnostic in slangc output and attach the offending
+/// source line with a caret under it.
+fn summarize_slangc_error(output: &str) -> Option<String> {
+    let diagnostic = output.lines().find(|line| line.contains("): error"))?;
+
+    let location_end = diagnostic.find("): error")?;
+    let location_start = diagnostic[..location_end].rfind('(')?;
+    let file = &diagnostic[..location_start];
+
+    // Location is either `line` or `line, column` (1-based).
+    let mut location = diagnostic[location_start + 1..location_end].split(',');
+    let line_number: usize = location.next()?.trim().parse().ok()?;
+    let column: Option<usize> = location.next().and_then(|c| c.trim().parse().ok());
+
+    let source_line = std::fs::read_to_string(file)
+        .ok()
+        .and_then(|source| source.lines().nth(line_number.saturating_sub(1)).map(str::to_owned));
+
+    let Some(source_line) = source_line else {
+        return Some(diagnostic.to_owned());
+    };
+
+    // Without a column, point at the first non-whitespace character.
+    let caret = column
+        .map(|c| c.saturating_sub(1))
+        .unwrap_or_else(|| source_line.len() - source_line.trim_start().len());
+    let padding: String = source_line
+        .chars()
+        .take(caret)
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect();
+
+    Some(format!("{}\n    {}\n    {}^", diagnostic, source_line, padding))
+}
+
 /// Reflect SPIR-V bytecode to extract resource bindings using rspirv_reflect.
 pub fn reflect_spirv(spirv: &[u8], stage: ShaderStage) -> Result<ShaderReflection, ShaderError> {
+    reflect_spirv_impl(spirv, None, stage)
+}
+
+/// Reflect a single entry point of a SPIR-V module that may declare several.
+pub fn reflect_spirv_entry(spirv: &[u8], entry_point: &str, stage: ShaderStage) -> Result<ShaderReflection, ShaderError> {
+    reflect_spirv_impl(spirv, Some(entry_point), stage)
+}
+
+fn reflect_spirv_impl(spirv: &[u8], entry_point: Option<&str>, stage: ShaderStage) -> Result<ShaderReflection, ShaderError> {
     // rspirv_reflect takes &[u8] directly
     if spirv.len() % 4 != 0 {
         return Err(ShaderError::ReflectionFailed("SPIR-V must be 4-byte aligned".to_string()));
@@ -377,6 +785,8 @@ pub fn reflect_spirv(spirv: &[u8], stage: ShaderStage) -> Result<ShaderReflectio
         }
     };
 
+    let interface = find_entry_point(spirv, entry_point, stage)?;
+
     let mut bindings = Vec::new();
     let stage_flags = stage.to_vk_stage();
 
@@ -405,27 +815,157 @@ pub fn reflect_spirv(spirv: &[u8], stage: ShaderStage) -> Result<ShaderReflectio
     }
 
     // Get push constants
-    let push_constant_size = reflection
+    let (push_constant_offset, push_constant_size) = reflection
         .get_push_constant_range()
         .ok()
         .flatten()
-        .map(|info| info.size)
-        .unwrap_or(0);
+        .map(|info| (info.offset, info.size))
+        .unwrap_or((0, 0));
 
     // Vertex inputs (VS only)
     let vertex_inputs = if stage == ShaderStage::Vertex {
-        reflect_vertex_inputs_from_spirv(spirv)?
+        reflect_vertex_inputs_from_spirv(spirv, interface.as_deref())?
     } else {
         Vec::new()
     };
 
+    // Workgroup size (CS only)
+    let local_size = if stage == ShaderStage::Compute {
+        reflect_local_size(&reflection, spirv)
+    } else {
+        None
+    };
+
     Ok(ShaderReflection {
         bindings,
+        push_constant_offset,
         push_constant_size,
         vertex_inputs,
+        local_size,
     })
 }
 
+/// Check the `OpEntryPoint` execution models against the requested stage, so a wrong stage
+/// is reported here instead of as an obscure pipeline creation failure.
+///
+/// With `entry_point`, only the entry point of that name is considered and its interface
+/// variable ids are returned.
+fn find_entry_point(spirv: &[u8], entry_point: Option<&str>, stage: ShaderStage) -> Result<Option<Vec<u32>>, ShaderError> {
+    const OP_ENTRY_POINT: u32 = 15;
+
+    let words = spirv_words(spirv);
+
+    let mut declared = Vec::new();
+    // Skip header (5 words).
+    let mut i = 5usize;
+    while i < words.len() {
+        let wc = (words[i] >> 16) as usize;
+        if wc == 0 || i + wc > words.len() {
+            return Err(ShaderError::ReflectionFailed("invalid SPIR-V instruction word count".into()));
+        }
+
+        let inst = &words[i..i + wc];
+        if (inst[0] & 0xFFFF) == OP_ENTRY_POINT && wc >= 4 {
+            // OpEntryPoint ExecutionModel %function "name" %interface...
+            let model = inst[1];
+            let (name, name_words) = decode_spirv_string(&inst[3..]);
+            if entry_point.is_none_or(|entry| entry == name) {
+                if execution_model_stage(model) == Some(stage) {
+                    return Ok(entry_point.map(|_| inst[3 + name_words..].to_vec()));
+                }
+                declared.push(execution_model_name(model));
+            }
+        }
+        i += wc;
+    }
+
+    if declared.is_empty() {
+        // Modules without entry points are rejected by Vulkan anyway.
+        return match entry_point {
+            Some(entry) => Err(ShaderError::ReflectionFailed(format!("entry point '{}' not found in SPIR-V module", entry))),
+            None => Ok(None),
+        };
+    }
+    Err(ShaderError::ReflectionFailed(format!(
+        "shader declares {} but {:?} was requested",
+        declared.join(", "),
+        stage
+    )))
+}
+
+/// Decode a nul terminated SPIR-V literal string, returning it and the number of words it occupies.
+fn decode_spirv_string(words: &[u32]) -> (String, usize) {
+    let mut bytes = Vec::new();
+    for (index, word) in words.iter().enumerate() {
+        for byte in word.to_le_bytes() {
+            if byte == 0 {
+                return (String::from_utf8_lossy(&bytes).into_owned(), index + 1);
+            }
+            bytes.push(byte);
+        }
+    }
+    (String::from_utf8_lossy(&bytes).into_owned(), words.len())
+}
+
+/// Map a SPIR-V `ExecutionModel` to the supported shader stages.
+fn execution_model_stage(model: u32) -> Option<ShaderStage> {
+    match model {
+        0 => Some(ShaderStage::Vertex),
+        4 => Some(ShaderStage::Fragment),
+        5 => Some(ShaderStage::Compute),
+        _ => None,
+    }
+}
+
+fn execution_model_name(model: u32) -> String {
+    match model {
+        0 => "Vertex".into(),
+        1 => "TessellationControl".into(),
+        2 => "TessellationEvaluation".into(),
+        3 => "Geometry".into(),
+        4 => "Fragment".into(),
+        5 => "Compute".into(),
+        _ => format!("execution model {}", model),
+    }
+}
+
+/// Decode little endian SPIR-V words, `spirv` need not be 4 byte aligned. A trailing partial word is dropped.
+fn spirv_words(spirv: &[u8]) -> Vec<u32> {
+    spirv
+        .chunks_exact(4)
+        .map(|word| u32::from_le_bytes([word[0], word[1], word[2], word[3]]))
+        .collect()
+}
+
+/// Literal `LocalSize` of a compute shader.
+///
+/// `LocalSizeId` and the `WorkgroupSize` builtin (which overrides `LocalSize`) are usually driven by
+/// specialization constants, so they are reported as unknown.
+fn reflect_local_size(reflection: &Reflection, spirv: &[u8]) -> Option<[u32; 3]> {
+    const OP_DECORATE: u32 = 71;
+    const DECORATION_BUILTIN: u32 = 11;
+    const BUILTIN_WORKGROUP_SIZE: u32 = 25;
+
+    let words = spirv_words(spirv);
+
+    // Skip header (5 words).
+    let mut i = 5usize;
+    while i < words.len() {
+        let wc = (words[i] >> 16) as usize;
+        if wc == 0 || i + wc > words.len() {
+            return None;
+        }
+
+        let inst = &words[i..i + wc];
+        if (inst[0] & 0xFFFF) == OP_DECORATE && wc >= 4 && inst[2] == DECORATION_BUILTIN && inst[3] == BUILTIN_WORKGROUP_SIZE {
+            return None;
+        }
+        i += wc;
+    }
+
+    reflection.get_compute_group_size().map(|(x, y, z)| [x, y, z])
+}
+
 #[derive(Debug, Clone)]
 enum SpirvType {
     Int { width: u32, signed: bool },
@@ -443,15 +983,14 @@ struct MemberDecos {
     builtin: Option<u32>,
 }
 
-fn reflect_vertex_inputs_from_spirv(spirv: &[u8]) -> Result<Vec<VertexInputAttr>, ShaderError> {
+/// With `interface`, only the stage inputs listed by the entry point are reflected.
+fn reflect_vertex_inputs_from_spirv(spirv: &[u8], interface: Option<&[u32]>) -> Result<Vec<VertexInputAttr>, ShaderError> {
     // Minimal SPIR-V parser for stage inputs:
     // - OpVariable (Input)
     // - OpDecorate / OpMemberDecorate (Location/BuiltIn)
     // - Type graph enough to map to vk::Format
 
-    let words: &[u32] = unsafe {
-        std::slice::from_raw_parts(spirv.as_ptr() as *const u32, spirv.len() / 4)
-    };
+    let words = spirv_words(spirv);
     if words.len() < 5 {
         return Err(ShaderError::ReflectionFailed("SPIR-V header too small".into()));
     }
@@ -639,6 +1178,9 @@ fn reflect_vertex_inputs_from_spirv(spirv: &[u8]) -> Result<Vec<VertexInputAttr>
         if storage_class != STORAGE_CLASS_INPUT {
             continue;
         }
+        if interface.is_some_and(|ids| !ids.contains(&var_id)) {
+            continue;
+        }
         if var_builtin.contains_key(&var_id) {
             continue;
         }
@@ -748,6 +1290,9 @@ fn expand_type_to_vertex_attrs(
 }
 
 /// Convert rspirv_reflect descriptor type to Vulkan descriptor type.
+///
+/// Separate textures and samplers stay `SAMPLED_IMAGE` and `SAMPLER`, they are never folded into a
+/// `COMBINED_IMAGE_SAMPLER`; see [`ShaderReflection::paired_sampler`].
 fn convert_descriptor_type(reflect_type: DescriptorType) -> vk::DescriptorType {
     // DescriptorType is a transparent wrapper around u32, matching Vulkan values
     vk::DescriptorType::from_raw(reflect_type.0 as i32)
@@ -757,9 +1302,9 @@ fn convert_descriptor_type(reflect_type: DescriptorType) -> vk::DescriptorType {
 fn create_shader_module(device: &Device, spirv: &[u8]) -> Result<vk::ShaderModule, ShaderError> {
     assert_eq!(spirv.len() % 4, 0, "SPIR-V bytecode must be 4-byte aligned");
 
-    let code: &[u32] = unsafe { std::slice::from_raw_parts(spirv.as_ptr() as *const u32, spirv.len() / 4) };
+    let code = spirv_words(spirv);
 
-    let create_info = vk::ShaderModuleCreateInfo::default().code(code);
+    let create_info = vk::ShaderModuleCreateInfo::default().code(&code);
     let module = unsafe { device.create_shader_module(&create_info, None)? };
 
     Ok(module)
@@ -780,4 +1325,210 @@ fn create_shader_module(device: &Device, spirv: &[u8]) -> Result<vk::ShaderModul
 //     }
 //
 //     Ok(layouts)
-// }
\ No newline at end of file
+// }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::VertexLayout;
+
+    fn op(code: &mut Vec<u32>, opcode: u32, operands: &[u32]) {
+        code.push(((operands.len() as u32 + 1) << 16) | opcode);
+        code.extend_from_slice(operands);
+    }
+
+    fn string(name: &str) -> Vec<u32> {
+        let mut bytes = name.as_bytes().to_vec();
+        bytes.resize(name.len() / 4 * 4 + 4, 0);
+        bytes.chunks(4).map(|c| u32::from_le_bytes([c[0], c[1], c[2], c[3]])).collect()
+    }
+
+    /// Assemble a fragment shader declaring `resources` in set 0, numbered in order, as
+    /// `Texture2D` (`false`) or `SamplerState` (`true`) globals like Slang emits for separate samplers.
+    fn separate_sampler_module(resources: &[(&str, bool)]) -> Vec<u8> {
+        let (void, func_ty, float, image, sampler, image_ptr, sampler_ptr, main, label) = (1, 2, 3, 4, 5, 6, 7, 8, 9);
+        let first_var = 10;
+        let bound = first_var + resources.len() as u32;
+
+        let mut code = vec![0x07230203, 0x00010000, 0, bound, 0];
+        op(&mut code, 17, &[1]); // OpCapability Shader
+        op(&mut code, 14, &[0, 1]); // OpMemoryModel Logical GLSL450
+        op(&mut code, 15, &[&[4, main][..], &string("main")].concat()); // OpEntryPoint Fragment
+        op(&mut code, 16, &[main, 7]); // OpExecutionMode OriginUpperLeft
+        for (index, (name, _)) in resources.iter().enumerate() {
+            op(&mut code, 5, &[&[first_var + index as u32][..], &string(name)].concat()); // OpName
+        }
+        for index in 0..resources.len() as u32 {
+            op(&mut code, 71, &[first_var + index, 34, 0]); // OpDecorate DescriptorSet 0
+            op(&mut code, 71, &[first_var + index, 33, index]); // OpDecorate Binding
+        }
+        op(&mut code, 19, &[void]); // OpTypeVoid
+        op(&mut code, 33, &[func_ty, void]); // OpTypeFunction
+        op(&mut code, 22, &[float, 32]); // OpTypeFloat
+        op(&mut code, 25, &[image, float, 1, 0, 0, 0, 1, 0]); // OpTypeImage 2D, sampled
+        op(&mut code, 26, &[sampler]); // OpTypeSampler
+        op(&mut code, 32, &[image_ptr, 0, image]); // OpTypePointer UniformConstant
+        op(&mut code, 32, &[sampler_ptr, 0, sampler]);
+        for (index, (_, is_sampler)) in resources.iter().enumerate() {
+            let ty = if *is_sampler { sampler_ptr } else { image_ptr };
+            op(&mut code, 59, &[ty, first_var + index as u32, 0]); // OpVariable UniformConstant
+        }
+        op(&mut code, 54, &[void, main, 0, func_ty]); // OpFunction
+        op(&mut code, 248, &[label]); // OpLabel
+        op(&mut code, 253, &[]); // OpReturn
+        op(&mut code, 56, &[]); // OpFunctionEnd
+
+        bytemuck::cast_slice(&code).to_vec()
+    }
+
+    #[test]
+    fn separate_samplers_pair_by_name() {
+        let spirv = separate_sampler_module(&[
+            ("albedo", false),
+            ("albedoSampler", true),
+            ("normal", false),
+            ("linear", true),
+            ("roughness", false),
+            ("roughness_sampler", true),
+        ]);
+        let reflection = reflect_spirv(&spirv, ShaderStage::Fragment).unwrap();
+
+        let albedo = reflection.find_binding("albedo").unwrap();
+        assert_eq!(albedo.descriptor_type, vk::DescriptorType::SAMPLED_IMAGE);
+        let albedo_sampler = reflection.paired_sampler(albedo).unwrap();
+        assert_eq!(albedo_sampler.name, "albedoSampler");
+        assert_eq!(albedo_sampler.descriptor_type, vk::DescriptorType::SAMPLER);
+
+        let roughness = reflection.find_binding("roughness").unwrap();
+        assert_eq!(reflection.paired_sampler(roughness).unwrap().name, "roughness_sampler");
+
+        // `linear` sits at the next binding but may be shared, so it must be bound explicitly.
+        let normal = reflection.find_binding("normal").unwrap();
+        assert!(reflection.paired_sampler(normal).is_none());
+        assert_eq!(reflection.find_binding("linear").unwrap().descriptor_type, vk::DescriptorType::SAMPLER);
+
+        assert!(reflection.paired_sampler(albedo_sampler).is_none());
+    }
+
+    /// Assemble a vertex shader whose float vector inputs have the given component counts,
+    /// at locations numbered in order.
+    fn vertex_input_module(components: &[u32]) -> Vec<u8> {
+        let (void, func_ty, float, main, label) = (1, 2, 3, 4, 5);
+        let first_vector = 6;
+        let first_pointer = first_vector + components.len() as u32;
+        let first_var = first_pointer + components.len() as u32;
+        let bound = first_var + components.len() as u32;
+        let vars = (first_var..bound).collect::<Vec<_>>();
+
+        let mut code = vec![0x07230203, 0x00010000, 0, bound, 0];
+        op(&mut code, 17, &[1]); // OpCapability Shader
+        op(&mut code, 14, &[0, 1]); // OpMemoryModel Logical GLSL450
+        op(&mut code, 15, &[&[0, main][..], &string("main"), &vars].concat()); // OpEntryPoint Vertex
+        for (location, var) in vars.iter().enumerate() {
+            op(&mut code, 71, &[*var, 30, location as u32]); // OpDecorate Location
+        }
+        op(&mut code, 19, &[void]); // OpTypeVoid
+        op(&mut code, 33, &[func_ty, void]); // OpTypeFunction
+        op(&mut code, 22, &[float, 32]); // OpTypeFloat
+        for (index, count) in components.iter().enumerate() {
+            let vector = first_vector + index as u32;
+            op(&mut code, 23, &[vector, float, *count]); // OpTypeVector
+            op(&mut code, 32, &[first_pointer + index as u32, 1, vector]); // OpTypePointer Input
+        }
+        for (index, var) in vars.iter().enumerate() {
+            op(&mut code, 59, &[first_pointer + index as u32, *var, 1]); // OpVariable Input
+        }
+        op(&mut code, 54, &[void, main, 0, func_ty]); // OpFunction
+        op(&mut code, 248, &[label]); // OpLabel
+        op(&mut code, 253, &[]); // OpReturn
+        op(&mut code, 56, &[]); // OpFunctionEnd
+
+        bytemuck::cast_slice(&code).to_vec()
+    }
+
+    /// Mirrors the asset mesh vertex, whose layout shaders consume as
+    /// `float3 position, float3 normal, float2 uv, float4 tangent`.
+    #[repr(C)]
+    #[derive(VertexLayout)]
+    #[allow(dead_code)]
+    struct TangentVertex {
+        position: [f32; 3],
+        normal: [f32; 3],
+        tex_coord: [f32; 2],
+        tangent: [f32; 4],
+    }
+
+    #[test]
+    fn vertex_layout_matches_reflected_inputs() {
+        let (binding, attributes) = TangentVertex::vertex_layout();
+        assert_eq!(binding.stride as usize, std::mem::size_of::<TangentVertex>());
+
+        let offsets = [
+            std::mem::offset_of!(TangentVertex, position),
+            std::mem::offset_of!(TangentVertex, normal),
+            std::mem::offset_of!(TangentVertex, tex_coord),
+            std::mem::offset_of!(TangentVertex, tangent),
+        ];
+        assert_eq!(attributes.len(), offsets.len());
+        for (attribute, offset) in attributes.iter().zip(offsets) {
+            assert_eq!(attribute.offset as usize, offset, "location {}", attribute.location);
+        }
+
+        let spirv = vertex_input_module(&[3, 3, 2, 4]);
+        let reflection = reflect_spirv(&spirv, ShaderStage::Vertex).unwrap();
+        assert_eq!(reflection.vertex_inputs.len(), attributes.len());
+        for (attribute, input) in attributes.iter().zip(&reflection.vertex_inputs) {
+            assert_eq!(attribute.location, input.location);
+            assert_eq!(attribute.format, input.format, "location {}", attribute.location);
+        }
+        assert_eq!(attributes[3].format, vk::Format::R32G32B32A32_SFLOAT);
+    }
+
+    #[test]
+    fn define_suffix_is_injective() {
+        let define = |name: &str, value: &str| (name.to_owned(), value.to_owned());
+
+        assert_ne!(define_suffix(&[define("A-B", "1")]), define_suffix(&[define("A_B", "1")]));
+        assert_ne!(
+            define_suffix(&[define("A", "1.B=2")]),
+            define_suffix(&[define("A", "1"), define("B", "2")])
+        );
+        assert_eq!(define_suffix(&[define("SHADOWS", "1")]), ".SHADOWS=1");
+        assert!(define_suffix(&[define("A B/C", "x\\y")]).chars().all(|c| c.is_ascii_alphanumeric() || "-_.=".contains(c)));
+    }
+
+    #[test]
+    fn entry_points_skip_comments() {
+        let source = r#"
+            [shader("vertex")]
+            float4 vsmain(float3 position) { return float4(position, 1.0); }
+
+            // [shader("compute")] void line_commented() {}
+            /* [shader("compute")]
+               void block_commented() {} */
+            /* inline */ [shader("fragment")] /* [shader("vertex")] */
+            float4 psmain() : SV_Target { return 0; } // trailing /*
+        "#;
+
+        let entry_points = parse_entry_points(&strip_comments(source));
+        assert_eq!(entry_points, vec![
+            ("vsmain".to_owned(), ShaderStage::Vertex),
+            ("psmain".to_owned(), ShaderStage::Fragment),
+        ]);
+    }
+
+    #[test]
+    fn spirv_words_decode_unaligned_bytes() {
+        let spirv = vertex_input_module(&[3, 2]);
+        let mut shifted = vec![0u8];
+        shifted.extend_from_slice(&spirv);
+
+        let words = spirv_words(&shifted[1..]);
+        assert_eq!(words[0], 0x07230203);
+        assert_eq!(words, spirv_words(&spirv));
+
+        let inputs = reflect_vertex_inputs_from_spirv(&shifted[1..], None).unwrap();
+        let formats: Vec<_> = inputs.iter().map(|input| input.format).collect();
+        assert_eq!(formats, vec![vk::Format::R32G32B32_SFLOAT, vk::Format::R32G32_SFLOAT]);
+    }
+}
@@ -4,12 +4,17 @@ use std::sync::{Arc, Weak};
 use ash::{vk, Device};
 use raw_window_handle::{HasDisplayHandle, HasWindowHandle};
 use winit::window::Window;
-use zenith_core::log::info;
+use zenith_core::log::{info, warn};
 use anyhow::{anyhow, Result};
 use zenith_rhi_derive::DeviceObject;
-use crate::{RhiCore, RenderDevice, Texture, Queue, Fence, Semaphore, NUM_BACK_BUFFERS};
+use crate::{Buffer, BufferDesc, RhiCore, RenderDevice, Texture, Queue, Fence, Semaphore, NUM_BACK_BUFFERS};
+use crate::barrier::{PipelineStage, TextureBarrier, TextureState};
+use crate::command::CommandEncoder;
+use crate::texture::TextureRange;
 use crate::device::DebuggableObject;
 use crate::device::set_debug_name_handle;
+use crate::device::wait_idle_for_teardown;
+use crate::utility::srgb_to_linear;
 
 #[derive(Clone)]
 pub struct SwapchainWindow {
@@ -63,7 +68,14 @@ pub struct SwapchainConfig {
     pub preferred_format: vk::Format,
     pub preferred_color_space: vk::ColorSpaceKHR,
     pub preferred_present_mode: vk::PresentModeKHR,
+    /// Present modes tried in order when `preferred_present_mode` is unsupported.
+    /// FIFO is always used as the final fallback, as it is guaranteed to be available.
+    pub present_mode_fallbacks: Vec<vk::PresentModeKHR>,
+    /// Requested swapchain image count, e.g. 2 for lower latency or 4 to absorb frame time spikes.
+    /// Clamped to the surface's supported image count range.
     pub num_back_buffers: u32,
+    /// Fall back to a 10-bit or float HDR format if the preferred format is unavailable.
+    pub prefer_hdr: bool,
 }
 
 impl Default for SwapchainConfig {
@@ -72,11 +84,60 @@ impl Default for SwapchainConfig {
             preferred_format: vk::Format::B8G8R8A8_SRGB,
             preferred_color_space: vk::ColorSpaceKHR::SRGB_NONLINEAR,
             preferred_present_mode: vk::PresentModeKHR::MAILBOX,
+            present_mode_fallbacks: vec![
+                vk::PresentModeKHR::MAILBOX,
+                vk::PresentModeKHR::FIFO_RELAXED,
+                vk::PresentModeKHR::FIFO,
+            ],
             num_back_buffers: NUM_BACK_BUFFERS,
+            prefer_hdr: false,
         }
     }
 }
 
+/// Color space of the presented images, which decides how the final pass encodes its output.
+///
+/// Presenting never converts color spaces, the swapchain format only stores the values written to it.
+/// The last pass writing to the back buffer must therefore encode as follows:
+/// - `SrgbNonlinear`: BT.709 primaries. `_SRGB` formats apply the sRGB curve on write, so write
+///   linear values; `_UNORM` formats need the curve applied in the shader.
+/// - `Hdr10St2084`: BT.2020 primaries, encoded with the PQ (ST 2084) curve where 1.0 is 10000 nits.
+/// - `ScRgbLinear`: BT.709 primaries, linear, where 1.0 is 80 nits. Values may exceed 1.0 or be negative.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorSpace {
+    SrgbNonlinear,
+    Hdr10St2084,
+    ScRgbLinear,
+    /// A color space without a dedicated encode path, e.g. from `SwapchainConfig::preferred_color_space`.
+    Other(vk::ColorSpaceKHR),
+}
+
+impl ColorSpace {
+    pub fn from_vk(color_space: vk::ColorSpaceKHR) -> Self {
+        match color_space {
+            vk::ColorSpaceKHR::SRGB_NONLINEAR => ColorSpace::SrgbNonlinear,
+            vk::ColorSpaceKHR::HDR10_ST2084_EXT => ColorSpace::Hdr10St2084,
+            vk::ColorSpaceKHR::EXTENDED_SRGB_LINEAR_EXT => ColorSpace::ScRgbLinear,
+            other => ColorSpace::Other(other),
+        }
+    }
+
+    pub fn to_vk(self) -> vk::ColorSpaceKHR {
+        match self {
+            ColorSpace::SrgbNonlinear => vk::ColorSpaceKHR::SRGB_NONLINEAR,
+            ColorSpace::Hdr10St2084 => vk::ColorSpaceKHR::HDR10_ST2084_EXT,
+            ColorSpace::ScRgbLinear => vk::ColorSpaceKHR::EXTENDED_SRGB_LINEAR_EXT,
+            ColorSpace::Other(other) => other,
+        }
+    }
+
+    /// Check if values above SDR white can be displayed.
+    #[inline]
+    pub fn is_hdr(self) -> bool {
+        matches!(self, ColorSpace::Hdr10St2084 | ColorSpace::ScRgbLinear)
+    }
+}
+
 /// Synchronization objects for a single frame.
 pub struct FrameSync<'a> {
     pub image_available: &'a Semaphore,
@@ -84,6 +145,68 @@ pub struct FrameSync<'a> {
     pub in_flight_fence: &'a Fence,
 }
 
+/// Host copy of a swapchain image, see [`Swapchain::record_capture`].
+pub struct FrameCapture {
+    pub width: u32,
+    pub height: u32,
+    pub format: vk::Format,
+    /// Tightly packed texels in `format`.
+    pub data: Vec<u8>,
+}
+
+impl FrameCapture {
+    /// Convert the texels to tightly packed RGBA8, swapping channels for BGRA swapchains.
+    ///
+    /// Stored values are returned as-is by default, which for `*_SRGB` formats is already the
+    /// sRGB encoding image files expect. With `linearize` those values are decoded to linear instead.
+    /// Returns `None` for formats other than 8-bit RGBA/BGRA (e.g. HDR swapchains).
+    pub fn to_rgba8(&self, linearize: bool) -> Option<Vec<u8>> {
+        let bgra = match self.format {
+            vk::Format::B8G8R8A8_SRGB | vk::Format::B8G8R8A8_UNORM => true,
+            vk::Format::R8G8B8A8_SRGB | vk::Format::R8G8B8A8_UNORM => false,
+            _ => return None,
+        };
+
+        let mut rgba = self.data.clone();
+        for texel in rgba.chunks_exact_mut(4) {
+            if bgra {
+                texel.swap(0, 2);
+            }
+            if linearize {
+                for channel in &mut texel[..3] {
+                    *channel = (srgb_to_linear(*channel as f32 / 255.0) * 255.0).round() as u8;
+                }
+            }
+        }
+        Some(rgba)
+    }
+}
+
+/// Map the result of `vkAcquireNextImageKHR`, flagging `needs_recreate` when suboptimal or out of date.
+/// Out of date is not an error, the frame is skipped instead.
+fn acquire_outcome(result: Result<(u32, bool), vk::Result>, needs_recreate: &mut bool) -> Result<Option<(u32, bool)>, vk::Result> {
+    match result {
+        Ok((image_index, suboptimal)) => {
+            *needs_recreate |= suboptimal;
+            Ok(Some((image_index, suboptimal)))
+        }
+        Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => {
+            *needs_recreate = true;
+            Ok(None)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Swapchain image copy recorded by [`Swapchain::record_capture`], waiting for its frame to finish.
+pub struct PendingCapture {
+    readback: Buffer,
+    frame_slot: usize,
+    width: u32,
+    height: u32,
+    format: vk::Format,
+}
+
 /// Vulkan swapchain management.
 #[DeviceObject]
 pub struct Swapchain {
@@ -96,6 +219,14 @@ pub struct Swapchain {
 
     textures: Vec<Arc<Texture>>,
     extent: vk::Extent2D,
+    /// Window extent the swapchain was last created for, before clamping to the surface capabilities.
+    requested_extent: vk::Extent2D,
+    /// Image count from [`SwapchainConfig::num_back_buffers`], kept to recreate with the same request.
+    requested_back_buffers: u32,
+    /// Set when acquire or present reported the swapchain as suboptimal or out of date.
+    needs_recreate: bool,
+    /// The window has a zero extent (minimized), frames are skipped until it is resized.
+    suspended: bool,
 
     image_available_semaphores: Vec<Semaphore>,
     render_finished_semaphores: Vec<Semaphore>,
@@ -104,12 +235,19 @@ pub struct Swapchain {
     format: vk::SurfaceFormatKHR,
     present_mode: vk::PresentModeKHR,
 
+    capabilities: vk::SurfaceCapabilitiesKHR,
+    supported_formats: Vec<vk::SurfaceFormatKHR>,
+    supported_present_modes: Vec<vk::PresentModeKHR>,
+
+    /// Usage the swapchain images are created with, see [`swapchain_image_usage`].
+    image_usage: vk::ImageUsageFlags,
+
     current_frame: usize,
 }
 
 impl Drop for Swapchain {
     fn drop(&mut self) {
-        unsafe { self.device.device_wait_idle().unwrap(); }
+        wait_idle_for_teardown(&self.device, "swapchain");
         self.clean_up_render_resources();
 
         unsafe {
@@ -150,8 +288,10 @@ impl Swapchain {
             width: os_window.inner_size().width,
             height: os_window.inner_size().height,
         };
+        let requested_extent = extent;
         let extent = get_swapchain_extent(&capabilities, extent);
 
+        let image_usage = swapchain_image_usage(&capabilities);
         let swapchain_loader = ash::khr::swapchain::Device::new(core.instance(), device.handle());
         let swapchain = Swapchain::create_or_recreate(
             &swapchain_loader,
@@ -173,6 +313,7 @@ impl Swapchain {
                 format!("swapchain.backbuffer.f{idx}"),
                 image.clone(),
                 format.format,
+                image_usage,
                 extent.clone(),
             );
             textures.push(Arc::new(texture));
@@ -191,11 +332,19 @@ impl Swapchain {
             textures,
             format,
             extent,
+            requested_extent,
+            requested_back_buffers: config.num_back_buffers,
+            needs_recreate: false,
+            suspended: false,
             image_available_semaphores,
             render_finished_semaphores,
             in_flight_fences,
+            image_usage,
             current_frame: 0,
             present_mode,
+            capabilities,
+            supported_formats: formats,
+            supported_present_modes: present_modes,
             device: device.handle().clone(),
         })
     }
@@ -206,8 +355,15 @@ impl Swapchain {
     #[inline]
     pub fn handle(&self) -> vk::SwapchainKHR { self.swapchain }
 
+    /// Acquire the next image, returning its index and whether the swapchain is suboptimal.
+    /// Returns `None` while the swapchain is suspended or when it is out of date, in which case the
+    /// frame must be skipped. An out of date swapchain is recreated by [`Self::recreate_if_needed`].
     #[profiling::function]
-    pub fn acquire_next_image(&mut self, device: &Device) -> Result<(u32, bool), vk::Result> {
+    pub fn acquire_next_image(&mut self, device: &Device) -> Result<Option<(u32, bool)>, vk::Result> {
+        if self.suspended {
+            return Ok(None);
+        }
+
         // Wait for the fence of the current frame
         unsafe {
             device.wait_for_fences(
@@ -218,16 +374,16 @@ impl Swapchain {
         }
 
         // Acquire next image
-        let (image_index, suboptimal) = unsafe {
+        let result = unsafe {
             self.swapchain_loader.acquire_next_image(
                 self.swapchain,
                 u64::MAX,
                 self.image_available_semaphores[self.current_frame].handle(),
                 vk::Fence::null(),
-            )?
+            )
         };
 
-        Ok((image_index, suboptimal))
+        acquire_outcome(result, &mut self.needs_recreate)
     }
 
     /// Reset the fence for the current frame before submitting work.
@@ -256,11 +412,35 @@ impl Swapchain {
 
         self.current_frame = (self.current_frame + 1) % self.textures.len();
 
-        match result {
-            Ok(suboptimal) => Ok(suboptimal),
-            Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => Ok(true),
-            Err(e) => Err(e),
+        let suboptimal = match result {
+            Ok(suboptimal) => suboptimal,
+            Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => true,
+            Err(e) => return Err(e),
+        };
+        self.needs_recreate |= suboptimal;
+        Ok(suboptimal)
+    }
+
+    /// Check if the last acquire or present reported the swapchain as suboptimal or out of date.
+    #[inline]
+    pub fn needs_recreate(&self) -> bool { self.needs_recreate }
+
+    /// Check if frames are skipped because the window was resized to a zero extent.
+    #[inline]
+    pub fn is_suspended(&self) -> bool { self.suspended }
+
+    /// Recreate the swapchain if acquire/present flagged it, or if `window_extent` changed since it
+    /// was last created. Returns whether it was recreated.
+    ///
+    /// Meant to be called once per frame before acquiring. A zero extent (minimized window)
+    /// suspends the swapchain instead, see [`Self::resize`].
+    pub fn recreate_if_needed(&mut self, device: &RenderDevice, window_extent: vk::Extent2D) -> Result<bool> {
+        if !self.needs_recreate && window_extent == self.requested_extent {
+            return Ok(false);
         }
+
+        self.resize(device, window_extent)?;
+        Ok(!self.suspended)
     }
 
     /// Get current frame synchronization objects.
@@ -272,23 +452,48 @@ impl Swapchain {
         }
     }
 
+    /// Recreate the swapchain for `extent`.
+    ///
+    /// A zero-sized swapchain is invalid, so a zero extent (minimized window) suspends the swapchain
+    /// instead: [`Self::acquire_next_image`] skips frames until it is resized to a non-zero extent.
     pub fn resize(&mut self, device: &RenderDevice, extent: vk::Extent2D) -> Result<()> {
+        if extent.width == 0 || extent.height == 0 {
+            self.suspended = true;
+            self.needs_recreate = true;
+            self.requested_extent = extent;
+            return Ok(());
+        }
+
         device.wait_until_idle()?;
 
-        // re-query surface capabilities as they may have changed
+        // re-query surface support as it may have changed (e.g. window moved to another monitor)
         let capabilities = unsafe {
             self.window.surface_loader.get_physical_device_surface_capabilities(self.physical_device, self.window.surface)?
         };
+        let formats = unsafe {
+            self.window.surface_loader.get_physical_device_surface_formats(self.physical_device, self.window.surface)?
+        };
+        let present_modes = unsafe {
+            self.window.surface_loader.get_physical_device_surface_present_modes(self.physical_device, self.window.surface)?
+        };
+        let requested_extent = extent;
         let extent = get_swapchain_extent(&capabilities, extent);
+        if extent.width == 0 || extent.height == 0 {
+            // The surface itself reports a zero extent.
+            self.suspended = true;
+            self.needs_recreate = true;
+            self.requested_extent = requested_extent;
+            return Ok(());
+        }
 
-        let config = SwapchainConfig::default();
+        let image_usage = swapchain_image_usage(&capabilities);
         let swapchain = Swapchain::create_or_recreate(
             &self.swapchain_loader,
             self.window.surface,
             capabilities,
             self.format,
             self.present_mode,
-            config.num_back_buffers,
+            self.requested_back_buffers,
             extent,
             self.swapchain,
         )?;
@@ -304,6 +509,7 @@ impl Swapchain {
                 format!("swapchain.backbuffer.f{idx}"),
                 image.clone(),
                 self.format.format,
+                image_usage,
                 extent.clone(),
             );
             textures.push(Arc::new(texture));
@@ -316,9 +522,19 @@ impl Swapchain {
         self.image_available_semaphores = image_available_semaphores;
         self.render_finished_semaphores = render_finished_semaphores;
         self.in_flight_fences = in_flight_fences;
+        // the image count may have changed, and the new sync objects start over from the first slot
+        self.current_frame = 0;
 
         self.extent = extent;
+        self.requested_extent = requested_extent;
+        self.needs_recreate = false;
+        self.suspended = false;
         self.swapchain = swapchain;
+        self.image_usage = image_usage;
+
+        self.capabilities = capabilities;
+        self.supported_formats = formats;
+        self.supported_present_modes = present_modes;
 
         Ok(())
     }
@@ -340,6 +556,15 @@ impl Swapchain {
         if capabilities.max_image_count > 0 {
             image_count = image_count.min(capabilities.max_image_count);
         }
+        if image_count != num_back_buffers {
+            warn!(
+                "Requested {} swapchain images, clamped to {} (surface supports {}..={}).",
+                num_back_buffers,
+                image_count,
+                capabilities.min_image_count,
+                if capabilities.max_image_count > 0 { capabilities.max_image_count.to_string() } else { "unbounded".to_owned() },
+            );
+        }
 
         info!(
             "Creating new swapchain: {:?} {:?}, {}x{}, {} images, {:?}",
@@ -358,7 +583,7 @@ impl Swapchain {
             .image_color_space(format.color_space)
             .image_extent(extent)
             .image_array_layers(1)
-            .image_usage(vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::TRANSFER_DST)
+            .image_usage(swapchain_image_usage(&capabilities))
             .image_sharing_mode(vk::SharingMode::EXCLUSIVE)
             .queue_family_indices(&[][..])
             .pre_transform(capabilities.current_transform)
@@ -392,6 +617,28 @@ impl Swapchain {
     pub fn format(&self) -> vk::Format {
         self.format.format
     }
+
+    pub fn surface_format(&self) -> vk::SurfaceFormatKHR {
+        self.format
+    }
+
+    /// Color space the final pass must encode its output for, see [`ColorSpace`].
+    pub fn color_space(&self) -> ColorSpace {
+        ColorSpace::from_vk(self.format.color_space)
+    }
+
+    /// Surface capabilities queried at creation or on the last resize.
+    pub fn capabilities(&self) -> &vk::SurfaceCapabilitiesKHR {
+        &self.capabilities
+    }
+
+    pub fn supported_formats(&self) -> &[vk::SurfaceFormatKHR] {
+        &self.supported_formats
+    }
+
+    pub fn supported_present_modes(&self) -> &[vk::PresentModeKHR] {
+        &self.supported_present_modes
+    }
     
     pub fn num_back_buffers(&self) -> u32 { self.textures.len() as u32 }
 
@@ -402,6 +649,120 @@ impl Swapchain {
     pub fn window(&self) -> &SwapchainWindow {
         &self.window
     }
+
+    /// Check if the swapchain images can be copied from, which [`Self::record_capture`] requires.
+    #[inline]
+    pub fn supports_capture(&self) -> bool {
+        self.image_usage.contains(vk::ImageUsageFlags::TRANSFER_SRC)
+    }
+
+    /// Record a copy of swapchain image `image_index` into a host visible buffer.
+    ///
+    /// Record it into the command buffer presenting the image, after the last pass writing it and
+    /// before its transition to `Present`, with the image in `TransferSrc` state. The image is only
+    /// owned by the application until it is presented, so it cannot be read back afterwards.
+    pub fn record_capture(&self, device: &RenderDevice, cmd: &CommandEncoder, image_index: u32) -> Result<PendingCapture> {
+        if !self.supports_capture() {
+            return Err(anyhow!("Swapchain [{}] images do not support TRANSFER_SRC usage, frames cannot be captured.", self.name));
+        }
+
+        let format = self.format.format;
+        let texel_size = match format {
+            vk::Format::R16G16B16A16_SFLOAT => 8,
+            _ => 4,
+        };
+        let size = self.extent.width as vk::DeviceSize * self.extent.height as vk::DeviceSize * texel_size;
+        let readback = Buffer::new(
+            device,
+            &BufferDesc::staging("buffer.swapchain_capture", size)
+                .with_usage(vk::BufferUsageFlags::TRANSFER_DST),
+        )?;
+
+        let region = vk::BufferImageCopy::default()
+            .image_subresource(vk::ImageSubresourceLayers {
+                aspect_mask: vk::ImageAspectFlags::COLOR,
+                mip_level: 0,
+                base_array_layer: 0,
+                layer_count: 1,
+            })
+            .image_extent(vk::Extent3D {
+                width: self.extent.width,
+                height: self.extent.height,
+                depth: 1,
+            });
+        cmd.copy_image_to_buffer(
+            self.textures[image_index as usize].handle(),
+            vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+            readback.handle(),
+            &[region],
+        );
+
+        Ok(PendingCapture {
+            readback,
+            frame_slot: self.current_frame,
+            width: self.extent.width,
+            height: self.extent.height,
+            format,
+        })
+    }
+
+    /// Wait for the frame that recorded `pending` and copy the captured image to the host.
+    ///
+    /// Blocks on the in-flight fence of that frame, so the capture is complete. Must be called
+    /// before the frame slot is acquired again, i.e. right after presenting the captured frame.
+    #[profiling::function]
+    pub fn finish_capture(&self, device: &RenderDevice, pending: PendingCapture) -> Result<FrameCapture> {
+        // A resize in between waited for the device to idle and replaced the fences.
+        if let Some(fence) = self.in_flight_fences.get(pending.frame_slot) {
+            unsafe {
+                device.handle().wait_for_fences(&[fence.handle()], true, u64::MAX)?;
+            }
+        }
+
+        let mut data = vec![0; pending.readback.size() as usize];
+        pending.readback.as_range(..)?.read(&mut data)?;
+
+        Ok(FrameCapture {
+            width: pending.width,
+            height: pending.height,
+            format: pending.format,
+            data,
+        })
+    }
+
+    /// Blit `src` onto swapchain image `image_index`, scaling it to the swapchain extent,
+    /// and leave the image in `Present` state.
+    ///
+    /// `src` must be in `TransferSrc` state. The previous image contents are discarded.
+    pub fn blit_from(&self, cmd: &CommandEncoder, src: &TextureRange, image_index: u32) -> Result<(), vk::Result> {
+        let texture = &self.textures[image_index as usize];
+        let range = || texture.as_range(..1, ..1);
+
+        cmd.texture_barriers(&[TextureBarrier::new(
+            range()?,
+            TextureState::Undefined,
+            TextureState::TransferDst,
+            PipelineStage::Transfer.into(),
+            PipelineStage::Transfer.into(),
+            Queue::ignored(),
+            Queue::ignored(),
+            false,
+            true,
+        )]);
+        cmd.blit_image(src, &range()?, vk::Filter::LINEAR);
+        cmd.texture_barriers(&[TextureBarrier::new(
+            range()?,
+            TextureState::TransferDst,
+            TextureState::Present,
+            PipelineStage::Transfer.into(),
+            PipelineStage::Transfer.into(),
+            Queue::ignored(),
+            Queue::ignored(),
+            false,
+            false,
+        )]);
+        Ok(())
+    }
 }
 
 impl DebuggableObject for Swapchain {
@@ -410,17 +771,45 @@ impl DebuggableObject for Swapchain {
     }
 }
 
+/// HDR formats in order of preference.
+const HDR_SURFACE_FORMATS: [(vk::Format, vk::ColorSpaceKHR); 2] = [
+    (vk::Format::A2B10G10R10_UNORM_PACK32, vk::ColorSpaceKHR::HDR10_ST2084_EXT),
+    (vk::Format::R16G16B16A16_SFLOAT, vk::ColorSpaceKHR::EXTENDED_SRGB_LINEAR_EXT),
+];
+
+/// 8-bit sRGB formats in order of preference.
+const SDR_SURFACE_FORMATS: [vk::Format; 2] = [
+    vk::Format::B8G8R8A8_SRGB,
+    vk::Format::R8G8B8A8_SRGB,
+];
+
+/// Pick a surface format: the configured one, then HDR (if requested), then 8-bit sRGB.
 fn choose_surface_format(
     formats: &[vk::SurfaceFormatKHR],
     config: &SwapchainConfig,
 ) -> vk::SurfaceFormatKHR {
-    // Try to find preferred format
-    formats
+    let find = |format: vk::Format, color_space: vk::ColorSpaceKHR| {
+        formats
+            .iter()
+            .find(|f| f.format == format && f.color_space == color_space)
+            .copied()
+    };
+
+    if let Some(format) = find(config.preferred_format, config.preferred_color_space) {
+        return format;
+    }
+
+    if config.prefer_hdr
+        && let Some(format) = HDR_SURFACE_FORMATS
+            .iter()
+            .find_map(|&(format, color_space)| find(format, color_space))
+    {
+        return format;
+    }
+
+    SDR_SURFACE_FORMATS
         .iter()
-        .find(|f| {
-            f.format == config.preferred_format && f.color_space == config.preferred_color_space
-        })
-        .copied()
+        .find_map(|&format| find(format, vk::ColorSpaceKHR::SRGB_NONLINEAR))
         .unwrap_or(formats[0])
 }
 
@@ -428,13 +817,24 @@ fn choose_present_mode(
     modes: &[vk::PresentModeKHR],
     config: &SwapchainConfig,
 ) -> vk::PresentModeKHR {
-    // Prefer requested mode, fallback to mailbox, then FIFO (always available)
-    if modes.contains(&config.preferred_present_mode) {
-        config.preferred_present_mode
-    } else if modes.contains(&vk::PresentModeKHR::MAILBOX) {
-        vk::PresentModeKHR::MAILBOX
+    // Prefer requested mode, then the configured fallbacks, then FIFO (always available)
+    let present_mode = std::iter::once(config.preferred_present_mode)
+        .chain(config.present_mode_fallbacks.iter().copied())
+        .find(|mode| modes.contains(mode))
+        .unwrap_or(vk::PresentModeKHR::FIFO);
+
+    info!("Swapchain present mode: {:?} (preferred {:?})", present_mode, config.preferred_present_mode);
+    present_mode
+}
+
+/// Swapchain images are rendered to and blitted to, and copied from for frame captures when the
+/// surface supports it.
+fn swapchain_image_usage(capabilities: &vk::SurfaceCapabilitiesKHR) -> vk::ImageUsageFlags {
+    let usage = vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::TRANSFER_DST;
+    if capabilities.supported_usage_flags.contains(vk::ImageUsageFlags::TRANSFER_SRC) {
+        usage | vk::ImageUsageFlags::TRANSFER_SRC
     } else {
-        vk::PresentModeKHR::FIFO // Guaranteed to be available
+        usage
     }
 }
 
@@ -473,4 +873,28 @@ fn create_sync_objects(
     }
 
     Ok((image_available, render_finished, in_flight))
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn out_of_date_acquire_skips_the_frame() {
+        let mut needs_recreate = false;
+        assert_eq!(acquire_outcome(Err(vk::Result::ERROR_OUT_OF_DATE_KHR), &mut needs_recreate), Ok(None));
+        assert!(needs_recreate);
+
+        let mut needs_recreate = false;
+        assert_eq!(acquire_outcome(Ok((2, true)), &mut needs_recreate), Ok(Some((2, true))));
+        assert!(needs_recreate);
+
+        let mut needs_recreate = false;
+        assert_eq!(acquire_outcome(Ok((1, false)), &mut needs_recreate), Ok(Some((1, false))));
+        assert!(!needs_recreate);
+
+        let mut needs_recreate = false;
+        assert_eq!(acquire_outcome(Err(vk::Result::ERROR_DEVICE_LOST), &mut needs_recreate), Err(vk::Result::ERROR_DEVICE_LOST));
+        assert!(!needs_recreate);
+    }
+}
@@ -1,4 +1,5 @@
 ﻿use ash::{vk};
+use std::cell::{Cell, RefCell};
 use zenith_rhi_derive::DeviceObject;
 use crate::{RenderDevice};
 use crate::device::DebuggableObject;
@@ -52,6 +53,58 @@ impl Drop for Fence {
 }
 }
 
+/// Recycles unsignaled fences, so frequent immediate submits don't create a `vk::Fence` each.
+pub struct FencePool {
+    name: String,
+    free: RefCell<Vec<Fence>>,
+    created: Cell<usize>,
+}
+
+impl FencePool {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            free: RefCell::new(Vec::new()),
+            created: Cell::new(0),
+        }
+    }
+
+    /// Take an unsignaled fence from the pool, creating one if it is empty.
+    pub fn acquire(&self, device: &RenderDevice) -> Result<Fence, vk::Result> {
+        if let Some(fence) = self.free.borrow_mut().pop() {
+            return Ok(fence);
+        }
+
+        self.created.set(self.created.get() + 1);
+        Fence::new(&format!("{}.{}", self.name, self.created.get() - 1), device, false)
+    }
+
+    /// Return a fence to the pool. It must not be used by a pending submission,
+    /// i.e. it was never submitted or has been waited on; signaled fences are reset.
+    pub fn release(&self, fence: Fence) -> Result<(), vk::Result> {
+        unsafe {
+            if fence.device.get_fence_status(fence.handle())? {
+                fence.device.reset_fences(&[fence.handle()])?;
+            }
+        }
+        self.free.borrow_mut().push(fence);
+        Ok(())
+    }
+
+    /// Number of fences created by the pool, including the ones handed out.
+    #[inline]
+    pub fn created_count(&self) -> usize { self.created.get() }
+
+    /// Number of fences ready to be acquired without creating a new one.
+    #[inline]
+    pub fn free_count(&self) -> usize { self.free.borrow().len() }
+
+    /// Destroy the free fences, before the device goes away.
+    pub(crate) fn clear(&mut self) {
+        self.free.get_mut().clear();
+    }
+}
+
 /// An owning Vulkan semaphore.
 #[DeviceObject]
 pub struct Semaphore {
@@ -6,10 +6,13 @@ use std::cell::RefCell;
 use std::hash::{Hash, Hasher};
 use std::ops::RangeBounds;
 use zenith_core::collections::hashmap::HashMap;
+use zenith_core::log;
 use zenith_rhi_derive::DeviceObject;
-use crate::{RenderDevice, Sampler};
+use crate::{ImmediateCommandEncoder, Queue, RenderDevice, Sampler};
+use crate::barrier::{PipelineStage, TextureBarrier, TextureState};
 use crate::device::DebuggableObject;
 use crate::device::set_debug_name_handle;
+use crate::memory::MemoryAllocation;
 use crate::utility::{find_memory_type, normalize_range_u32};
 
 /// Texture descriptor for creating GPU textures.
@@ -161,6 +164,44 @@ impl TextureDesc {
         }
     }
 
+    /// Create a transient color attachment descriptor, e.g. an MSAA target that is only resolved.
+    ///
+    /// Uses lazily allocated memory, which tile based GPUs never back with VRAM as long as the
+    /// contents aren't loaded or stored. Falls back to device local memory where unsupported.
+    pub fn new_transient_color(name: &str, width: u32, height: u32, format: vk::Format, samples: vk::SampleCountFlags) -> Self {
+        Self {
+            usage: vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::TRANSIENT_ATTACHMENT,
+            memory_flags: vk::MemoryPropertyFlags::DEVICE_LOCAL | vk::MemoryPropertyFlags::LAZILY_ALLOCATED,
+            samples,
+            ..Self::new_color(name, width, height, format)
+        }
+    }
+
+    /// Create a transient depth attachment descriptor, see [`Self::new_transient_color`].
+    pub fn new_transient_depth(name: &str, width: u32, height: u32, format: vk::Format, samples: vk::SampleCountFlags) -> Self {
+        Self {
+            format,
+            usage: vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT | vk::ImageUsageFlags::TRANSIENT_ATTACHMENT,
+            memory_flags: vk::MemoryPropertyFlags::DEVICE_LOCAL | vk::MemoryPropertyFlags::LAZILY_ALLOCATED,
+            samples,
+            ..Self::new_depth(name, width, height)
+        }
+    }
+
+    /// Pick the first depth format usable as an optimal tiling depth/stencil attachment,
+    /// preferring `D32_SFLOAT_S8_UINT`, `D24_UNORM_S8_UINT` with `stencil`, and `D32_SFLOAT`, `D16_UNORM` otherwise.
+    pub fn best_depth_format(device: &RenderDevice, stencil: bool) -> Result<vk::Format, vk::Result> {
+        let candidates: &[vk::Format] = if stencil {
+            &[vk::Format::D32_SFLOAT_S8_UINT, vk::Format::D24_UNORM_S8_UINT]
+        } else {
+            &[vk::Format::D32_SFLOAT, vk::Format::D16_UNORM]
+        };
+
+        device
+            .first_supported_format(candidates, vk::ImageTiling::OPTIMAL, vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT)
+            .ok_or(vk::Result::ERROR_FORMAT_NOT_SUPPORTED)
+    }
+
     /// Create a depth-stencil attachment descriptor.
     pub fn new_depth_stencil(name: &str, width: u32, height: u32) -> Self {
         Self {
@@ -297,8 +338,8 @@ impl Hash for TextureDesc {
 pub struct Texture {
     desc: TextureDesc,
     image: vk::Image,
-    /// If memory is null, it is a swapchain texture
-    memory: vk::DeviceMemory,
+    /// If memory is `None`, it is a swapchain texture
+    memory: Option<MemoryAllocation>,
     views: RefCell<HashMap<TextureSubresource, vk::ImageView>>,
 }
 
@@ -327,24 +368,38 @@ impl Texture {
         // Get memory requirements
         let mem_requirements = unsafe { device.handle().get_image_memory_requirements(image) };
 
-        // Find suitable memory type
+        // Find suitable memory type, lazily allocated memory is optional
         let memory_type_index = find_memory_type(memory_properties, mem_requirements.memory_type_bits, desc.memory_flags)
-            .ok_or(vk::Result::ERROR_OUT_OF_DEVICE_MEMORY)?;
+            .or_else(|| {
+                if !desc.memory_flags.contains(vk::MemoryPropertyFlags::LAZILY_ALLOCATED) {
+                    return None;
+                }
+                log::debug!("no lazily allocated memory for {}, using device local memory.", desc.name);
+                let flags = (desc.memory_flags & !vk::MemoryPropertyFlags::LAZILY_ALLOCATED) | vk::MemoryPropertyFlags::DEVICE_LOCAL;
+                find_memory_type(memory_properties, mem_requirements.memory_type_bits, flags)
+            });
+        let Some(memory_type_index) = memory_type_index else {
+            unsafe { device.handle().destroy_image(image, None) };
+            return Err(vk::Result::ERROR_OUT_OF_DEVICE_MEMORY);
+        };
 
         // Allocate memory
-        let alloc_info = vk::MemoryAllocateInfo::default()
-            .allocation_size(mem_requirements.size)
-            .memory_type_index(memory_type_index);
-
-        let memory = unsafe { device.handle().allocate_memory(&alloc_info, None)? };
+        let linear = desc.tiling == vk::ImageTiling::LINEAR;
+        let memory = match MemoryAllocation::new(device, mem_requirements, memory_type_index, linear) {
+            Ok(memory) => memory,
+            Err(err) => {
+                unsafe { device.handle().destroy_image(image, None) };
+                return Err(err);
+            }
+        };
 
         // Bind memory to image
-        unsafe { device.handle().bind_image_memory(image, memory, 0)? };
+        unsafe { device.handle().bind_image_memory(image, memory.memory(), memory.offset())? };
 
         let texture = Self {
             desc: desc.clone(),
             image,
-            memory,
+            memory: Some(memory),
             views: RefCell::new(Default::default()),
             device: device.handle().clone(),
         };
@@ -358,6 +413,7 @@ impl Texture {
         name: String,
         image: vk::Image,
         format: vk::Format,
+        usage: vk::ImageUsageFlags,
         extent: vk::Extent2D,
     ) -> Self {
         let desc = TextureDesc {
@@ -368,20 +424,20 @@ impl Texture {
                 height: extent.height,
                 depth: 1,
             },
-            usage: vk::ImageUsageFlags::COLOR_ATTACHMENT,
+            usage,
             memory_flags: Default::default(),
             image_type: Default::default(),
             view_type: vk::ImageViewType::TYPE_2D,
             mip_levels: 1,
             array_layers: 1,
-            samples: Default::default(),
+            samples: vk::SampleCountFlags::TYPE_1,
             tiling: Default::default(),
         };
 
         let texture = Self {
             desc,
             image,
-            memory: vk::DeviceMemory::null(),
+            memory: None,
             views: RefCell::new(Default::default()),
             device: device.handle().clone(),
         };
@@ -456,7 +512,29 @@ impl Texture {
     }
     
     pub fn is_swapchain_texture(&self) -> bool {
-        self.memory == vk::DeviceMemory::null() 
+        self.memory.is_none()
+    }
+
+    /// Transition every subresource from `src` to `dst` on `queue` and block until the GPU is done.
+    ///
+    /// Meant for static textures outside the render graph, e.g. moving a freshly created texture
+    /// from `Undefined` (which discards its contents) to `Sampled`. This stalls the CPU on a fence,
+    /// so don't use it per frame.
+    pub fn transition_now(&self, device: &RenderDevice, queue: Queue, src: TextureState, dst: TextureState) -> Result<(), vk::Result> {
+        let range = self.as_range(.., ..)?;
+        ImmediateCommandEncoder::run(device, queue, |cmd| {
+            cmd.texture_barriers(&[TextureBarrier::new(
+                range,
+                src,
+                dst,
+                PipelineStage::AllCommands.into(),
+                PipelineStage::AllCommands.into(),
+                Queue::ignored(),
+                Queue::ignored(),
+                false,
+                src == TextureState::Undefined,
+            )]);
+        })
     }
 }
 
@@ -467,9 +545,9 @@ impl Drop for Texture {
                 self.device.destroy_image_view(*view, None);
             }
 
-            if self.memory != vk::DeviceMemory::null() {
+            if let Some(memory) = &mut self.memory {
                 self.device.destroy_image(self.image, None);
-                self.device.free_memory(self.memory, None);
+                memory.free(&self.device);
             }
         }
     }
@@ -479,10 +557,11 @@ impl DebuggableObject for Texture {
     fn set_debug_name(&self, device: &RenderDevice) {
         set_debug_name_handle(device, self.image, vk::ObjectType::IMAGE, self.name());
 
-        if self.memory != vk::DeviceMemory::null() {
+        // Suballocated memory is shared with other resources.
+        if let Some(memory) = self.memory.as_ref().filter(|memory| memory.is_dedicated()) {
             set_debug_name_handle(
                 device,
-                self.memory,
+                memory.memory(),
                 vk::ObjectType::DEVICE_MEMORY,
                 &format!("{}.memory", self.name()),
             );
@@ -491,7 +570,7 @@ impl DebuggableObject for Texture {
 }
 
 /// Get the appropriate aspect mask for an image format.
-fn format_to_aspect_mask(format: vk::Format) -> vk::ImageAspectFlags {
+pub(crate) fn format_to_aspect_mask(format: vk::Format) -> vk::ImageAspectFlags {
     match format {
         vk::Format::D16_UNORM | vk::Format::D32_SFLOAT | vk::Format::X8_D24_UNORM_PACK32 => {
             vk::ImageAspectFlags::DEPTH
@@ -533,16 +612,67 @@ impl<'a> TextureRange<'a> {
     #[inline]
     pub fn texture(&self) -> &'a Texture { self.texture }
 
+    #[inline]
+    pub fn base_mip(&self) -> u32 { self.subresource.base_mip }
+
+    #[inline]
+    pub fn num_mips(&self) -> u32 { self.subresource.num_mips }
+
+    #[inline]
+    pub fn base_layer(&self) -> u32 { self.subresource.base_layer }
+
+    #[inline]
+    pub fn num_layers(&self) -> u32 { self.subresource.num_layers }
+
+    /// Full subresource range, including all aspects of the texture format.
+    pub fn subresource_range(&self) -> vk::ImageSubresourceRange {
+        self.subresource.to_vk(self.texture.aspect())
+    }
+
+    /// Subresource layers of a single mip level (relative to `base_mip`) for copy/blit commands.
+    pub fn subresource_layers(&self, mip_offset: u32) -> vk::ImageSubresourceLayers {
+        vk::ImageSubresourceLayers {
+            aspect_mask: self.texture.aspect(),
+            mip_level: self.subresource.base_mip + mip_offset,
+            base_array_layer: self.subresource.base_layer,
+            layer_count: self.subresource.num_layers,
+        }
+    }
+
+    /// Extent of a mip level (relative to `base_mip`).
+    pub fn mip_extent(&self, mip_offset: u32) -> vk::Extent3D {
+        let extent = self.texture.extent();
+        let mip = self.subresource.base_mip + mip_offset;
+        vk::Extent3D {
+            width: (extent.width >> mip).max(1),
+            height: (extent.height >> mip).max(1),
+            depth: (extent.depth >> mip).max(1),
+        }
+    }
+
     pub fn view(&self) -> Result<vk::ImageView, vk::Result> {
         // Cached per-subresource view.
         if let Some(v) = { self.texture.views.borrow().get(&self.subresource).copied() } {
             return Ok(v);
         }
 
+        // A partial layer range can not use cube/array view types of the full texture.
+        let desc = &self.texture.desc;
+        let view_type = if self.subresource.num_layers == desc.array_layers {
+            desc.view_type
+        } else {
+            match (desc.view_type, self.subresource.num_layers) {
+                (vk::ImageViewType::TYPE_1D | vk::ImageViewType::TYPE_1D_ARRAY, 1) => vk::ImageViewType::TYPE_1D,
+                (vk::ImageViewType::TYPE_1D | vk::ImageViewType::TYPE_1D_ARRAY, _) => vk::ImageViewType::TYPE_1D_ARRAY,
+                (_, 1) => vk::ImageViewType::TYPE_2D,
+                _ => vk::ImageViewType::TYPE_2D_ARRAY,
+            }
+        };
+
         let aspect_mask = format_to_aspect_mask(self.texture.desc.format);
         let view_info = vk::ImageViewCreateInfo::default()
             .image(self.texture.image)
-            .view_type(self.texture.desc.view_type)
+            .view_type(view_type)
             .format(self.texture.desc.format)
             .components(vk::ComponentMapping {
                 r: vk::ComponentSwizzle::IDENTITY,
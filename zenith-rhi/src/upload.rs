@@ -95,7 +95,6 @@ impl<'a> UploadPool<'a> {
             return Ok(());
         }
 
-        let staging_handle = self.staging.handle();
         let staging_size = self.staging.size() as usize;
         let q = device.graphics_queue();
 
@@ -138,7 +137,7 @@ impl<'a> UploadPool<'a> {
                     .src_offset(p.src_offset)
                     .dst_offset(p.dst.offset() as vk::DeviceSize)
                     .size(p.size);
-                encoder.copy_buffer(staging_handle, p.dst.buffer().handle(), std::slice::from_ref(&region));
+                encoder.copy_buffer(&self.staging, p.dst.buffer(), std::slice::from_ref(&region));
             }
 
             // Post-copy barriers: TRANSFER_DST -> final_state
@@ -36,6 +36,15 @@ macro_rules! normalize_range_function {
 normalize_range_function!(u64);
 normalize_range_function!(u32);
 
+/// Decode an sRGB encoded channel in `[0, 1]` to linear.
+pub(crate) fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
 /// Find a suitable memory type index.
 pub(crate) fn find_memory_type(
     memory_properties: &vk::PhysicalDeviceMemoryProperties,
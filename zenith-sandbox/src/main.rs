@@ -28,7 +28,7 @@ impl RenderableApp for SimpleApp {
         let output_access = node.write_hint(&mut output, TextureState::General, vk::PipelineStageFlags2::TRANSFER);
         node.execute(move |ctx| {
             let rt = ctx.get(&output_access);
-            let encoder = ctx.command_encoder();
+            let encoder = ctx.encoder();
 
             encoder.custom(|device, cmd| {
                 unsafe {
@@ -1,14 +1,15 @@
 use crate::app::RenderContext;
 use crate::RenderableApp;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use winit::window::Window;
 use zenith_rendergraph::RenderGraphBuilder;
 use zenith_rhi::core::{select_physical_device, PhysicalDevice};
 use zenith_rhi::swapchain::SwapchainWindow;
-use zenith_rhi::{vk, CommandPool, PipelineCache, RenderDevice, RhiCore, Swapchain, SwapchainConfig};
+use zenith_rhi::{vk, CommandPool, FramePools, PendingCapture, PipelineCache, RenderDevice, RhiCore, RhiCoreConfig, Swapchain, SwapchainConfig};
 
 pub struct Engine {
-    execute_command_pools: Vec<CommandPool>,
+    execute_command_pools: FramePools,
     present_command_pools: Vec<CommandPool>,
     pipeline_cache: PipelineCache,
     swapchain: Swapchain,
@@ -17,6 +18,8 @@ pub struct Engine {
     _rhi_core: RhiCore,
 
     pub main_window: Arc<Window>,
+    /// Where to write the next presented frame, see [`Engine::capture_frame`].
+    capture_path: Option<PathBuf>,
     // _puffin_server: puffin_http::Server,
 
     should_exit: bool,
@@ -27,7 +30,7 @@ impl Engine {
         // let server_addr = format!("127.0.0.1:{}", puffin_http::DEFAULT_PORT);
         // let _puffin_server = puffin_http::Server::new(&server_addr)?;
 
-        let core = RhiCore::new(&main_window)?;
+        let core = RhiCore::new(&main_window, RhiCoreConfig::default())?;
         let swapchain_window = SwapchainWindow::new(&main_window, &core)?;
         let physical_device = select_physical_device(core.instance(), &swapchain_window)?;
         let device = core.create_render_device(&physical_device)?;
@@ -43,27 +46,21 @@ impl Engine {
 
         let pipeline_cache = PipelineCache::new("pipeline_cache.main", &device)?;
 
-        let num_frames = device.num_frames();
-        let (execute_command_pools, present_command_pools) = (0..num_frames)
-            .map(|idx| -> Result<(CommandPool, CommandPool), vk::Result> {
-                Ok((
-                    CommandPool::new(
-                        &format!("command_pool.execute.f{idx}"),
-                        &device,
-                        physical_device.graphics_queue_family(),
-                        vk::CommandPoolCreateFlags::empty(),
-                    )?,
-                    CommandPool::new(
-                        &format!("command_pool.present.f{idx}"),
-                        &device,
-                        physical_device.present_queue_family(),
-                        vk::CommandPoolCreateFlags::empty(),
-                    )?,
-                ))
+        let execute_command_pools = FramePools::new(
+            "command_pool.execute",
+            &device,
+            physical_device.graphics_queue_family(),
+        )?;
+        let present_command_pools = (0..device.num_frames())
+            .map(|idx| {
+                CommandPool::new(
+                    &format!("command_pool.present.f{idx}"),
+                    &device,
+                    physical_device.present_queue_family(),
+                    vk::CommandPoolCreateFlags::empty(),
+                )
             })
-            .collect::<Result<Vec<_>, _>>()?
-            .into_iter()
-            .unzip();
+            .collect::<Result<Vec<_>, _>>()?;
 
         Ok(Self {
             execute_command_pools,
@@ -77,6 +74,7 @@ impl Engine {
             // _puffin_server,
 
             main_window,
+            capture_path: None,
             should_exit: false,
         })
     }
@@ -87,8 +85,15 @@ impl Engine {
 
     #[profiling::function]
     pub fn render<A: RenderableApp>(&mut self, app: &mut A) {
+        self.recreate_swapchain();
+        if self.swapchain.is_suspended() {
+            return;
+        }
+
         let frame_index = self.render_device.begin_frame();
-        self.execute_command_pools[frame_index].reset().expect("Failed to reset execute command pool");
+        let execute_command_pool = self.execute_command_pools
+            .reset_frame(frame_index)
+            .expect("Failed to reset execute command pool");
 
         let mut builder = RenderGraphBuilder::new();
         let render_context = RenderContext::new(
@@ -101,11 +106,28 @@ impl Engine {
         let render_graph = builder.build();
         let mut compiled = render_graph.compile(&mut self.render_device, &mut self.pipeline_cache);
 
-        compiled.execute(&mut self.render_device, &self.execute_command_pools[frame_index])
+        compiled.execute(&mut self.render_device, execute_command_pool)
             .expect("Failed to execute render graph!");
 
-        let retired = compiled.present(&mut self.render_device, &self.present_command_pools[frame_index], &mut self.swapchain)
-            .expect("Failed to present swapchain!");
+        let present_command_pool = &self.present_command_pools[frame_index];
+        let retired = match self.capture_path.take() {
+            Some(path) => {
+                let (retired, pending) = compiled.present_captured(&mut self.render_device, present_command_pool, &mut self.swapchain)
+                    .expect("Failed to present swapchain!");
+                match pending {
+                    Some(pending) => {
+                        if let Err(err) = self.write_capture(&path, pending) {
+                            log::error!("Failed to capture frame to {}: {}", path.display(), err);
+                        }
+                    }
+                    // nothing was presented, capture the next frame instead
+                    None => self.capture_path = Some(path),
+                }
+                retired
+            }
+            None => compiled.present(&mut self.render_device, present_command_pool, &mut self.swapchain)
+                .expect("Failed to present swapchain!"),
+        };
 
         retired.release_frame_resources(&mut self.render_device);
         self.render_device.end_frame();
@@ -113,23 +135,44 @@ impl Engine {
 
     fn recreate_swapchain(&mut self) {
         let inner_size = self.main_window.inner_size();
-        if inner_size.width == 0 || inner_size.height == 0 {
-            return;
-        }
-
         let window_extent = vk::Extent2D {
             width: inner_size.width,
             height: inner_size.height,
         };
 
-        self.swapchain.resize(&self.render_device, window_extent).unwrap();
+        let old_extent = self.swapchain.extent();
+        if self.swapchain.recreate_if_needed(&self.render_device, window_extent).unwrap() {
+            self.render_device.invalidate_size_dependent(old_extent);
+        }
     }
 
-    #[profiling::function]
-    pub fn resize(&mut self, width: u32, height: u32) {
-        if width > 0 && height > 0 {
-            self.recreate_swapchain();
+    /// Write the next presented frame to `path` as an RGBA8 image (PNG for a `.png` extension).
+    ///
+    /// The swapchain image is copied right before it is presented and written in that same
+    /// [`Self::render`] call, which waits for the GPU to finish the frame first. Capturing therefore
+    /// stalls the CPU once per captured frame. The stored sRGB values are written unchanged.
+    pub fn capture_frame(&mut self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        if !self.swapchain.supports_capture() {
+            return Err(anyhow::anyhow!("The swapchain images of this surface cannot be copied from"));
         }
+        self.capture_path = Some(path.as_ref().to_path_buf());
+        Ok(())
+    }
+
+    #[profiling::function]
+    fn write_capture(&self, path: &Path, pending: PendingCapture) -> anyhow::Result<()> {
+        let capture = self.swapchain.finish_capture(&self.render_device, pending)?;
+        let rgba = capture.to_rgba8(false)
+            .ok_or_else(|| anyhow::anyhow!("Unsupported swapchain format for capture: {:?}", capture.format))?;
+
+        image::save_buffer(path, &rgba, capture.width, capture.height, image::ExtendedColorType::Rgba8)?;
+        Ok(())
+    }
+
+    #[profiling::function]
+    pub fn resize(&mut self, _width: u32, _height: u32) {
+        // A zero extent suspends the swapchain until the window is restored.
+        self.recreate_swapchain();
     }
 
     #[inline]